@@ -0,0 +1,100 @@
+//! Administration of the content table.
+//!
+//! ```text
+//! valnk-admin <diff|apply> [--table <name>] [--endpoint <url>]
+//! ```
+//!
+//! `diff` prints the changes the table misses, `apply` makes them. The table and the
+//! endpoint are the ones of the [`ValnkConfig`] unless they are given.
+
+#![allow(clippy::needless_return)]
+
+use std::process::ExitCode;
+
+use valnk::config::ValnkConfig;
+use valnk::data::api::store::schema::TableSchema;
+
+const USAGE: &str = "usage: valnk-admin <diff|apply> [--table <name>] [--endpoint <url>]";
+
+struct Args {
+    command: String,
+    table: Option<String>,
+    endpoint: Option<String>,
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Args, String> {
+    let command = args.next().ok_or("missing command")?;
+    let mut table = None;
+    let mut endpoint = None;
+
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or(format!("missing value of `{arg}`"));
+        match arg.as_str() {
+            "--table" => table = Some(value()?),
+            "--endpoint" => endpoint = Some(value()?),
+            _ => return Err(format!("unknown argument `{arg}`")),
+        }
+    }
+
+    return Ok(Args {
+        command,
+        table,
+        endpoint,
+    });
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let args = match parse_args(std::env::args().skip(1)) {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("{e}\n{USAGE}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let mut config = match ValnkConfig::load() {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("invalid configuration: {e}");
+            return ExitCode::FAILURE;
+        }
+    };
+    if args.endpoint.is_some() {
+        config.endpoint_url = args.endpoint;
+    }
+    let table = args.table.unwrap_or_else(|| config.table());
+
+    let aws_cli = match config.dynamodb_client().await {
+        Ok(cli) => cli,
+        Err(e) => {
+            eprintln!("{e}");
+            return ExitCode::FAILURE;
+        }
+    };
+    let schema = TableSchema::content(&table);
+
+    let changes = match args.command.as_str() {
+        "diff" => schema.diff(&aws_cli).await,
+        "apply" => schema.apply(&aws_cli).await,
+        cmd => {
+            eprintln!("unknown command `{cmd}`\n{USAGE}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    return match changes {
+        Ok(changes) if changes.is_empty() => {
+            println!("`{table}` is up to date");
+            ExitCode::SUCCESS
+        }
+        Ok(changes) => {
+            changes.iter().for_each(|change| println!("{change:?}"));
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            eprintln!("failed to {} the schema of `{table}`: {e}", args.command);
+            ExitCode::FAILURE
+        }
+    };
+}
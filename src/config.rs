@@ -0,0 +1,185 @@
+//! The configuration of valnk, loaded with figment.
+//!
+//! The values are read, each source over the previous one, from:
+//!
+//! - the defaults of [`ValnkConfig`],
+//! - the `valnk` table of the profile in `Rocket.toml` (or `ROCKET_CONFIG`), e.g. `[default.valnk]`,
+//! - the profile in `Valnk.toml` (or `VALNK_CONFIG`), e.g. `[default]`,
+//! - the `VALNK_` environment variables, e.g. `VALNK_TABLE_PREFIX=dev-`.
+//!
+//! The profile is the one of Rocket, `ROCKET_PROFILE`, `debug` or `release` by default.
+
+use std::time::Duration;
+
+use aws_config::timeout::TimeoutConfig;
+use aws_sdk_dynamodb::Client as DynamodbClient;
+use aws_sdk_dynamodb::Region;
+use figment::Figment;
+use figment::providers::{Env, Format, Serialized, Toml};
+use serde::{Serialize, Deserialize};
+
+use crate::data::api::page::{PageLimits, DEFAULT_LIMIT, MAX_LIMIT};
+use crate::data::api::result::Result;
+use crate::data::api::retry::RetryPolicy;
+use crate::data::api::store::{dynamodb_client, DynamoStore};
+
+/// The table of the valnk configuration in `Rocket.toml`.
+pub const CONFIG_KEY: &str = "valnk";
+
+const DEFAULT_TABLE_NAME: &str = "valnk-content";
+
+#[derive(Clone, Serialize, Deserialize, PartialEq, Debug)]
+#[serde(default)]
+pub struct ValnkConfig {
+    /// The name of the content table, after the `table_prefix`.
+    pub table_name: String,
+    /// Prepended to the name of the tables, e.g. `dev-` to keep the tables of a stage apart.
+    pub table_prefix: String,
+    /// The AWS region, the one of the AWS config by default.
+    pub region: Option<String>,
+    /// The DynamoDB endpoint, e.g. `http://localhost:8000` for a DynamoDB Local stand-in.
+    pub endpoint_url: Option<String>,
+    pub connect_timeout_ms: Option<u64>,
+    /// How long one attempt of a request to DynamoDB can take, each retry of the [`RetryPolicy`] has its own.
+    pub operation_timeout_ms: Option<u64>,
+    /// The number of attempts of a throttled or failed request, see [`RetryPolicy`],
+    /// the only retries of the requests since the ones of the SDK are disabled.
+    pub max_attempts: Option<u32>,
+    /// The page size of the lists when no `limit` is given, at least `1`.
+    pub default_page_limit: i32,
+    /// The largest page size of the lists, at least `1`.
+    pub max_page_limit: i32,
+}
+
+impl Default for ValnkConfig {
+    fn default() -> Self {
+        return Self {
+            table_name: DEFAULT_TABLE_NAME.to_string(),
+            table_prefix: String::new(),
+            region: None,
+            endpoint_url: None,
+            connect_timeout_ms: None,
+            operation_timeout_ms: None,
+            max_attempts: None,
+            default_page_limit: DEFAULT_LIMIT,
+            max_page_limit: MAX_LIMIT,
+        };
+    }
+}
+
+impl ValnkConfig {
+    /// The sources of the configuration, see the [module](self) docs.
+    pub fn figment() -> Figment {
+        let rocket_toml = Figment::from(Toml::file(Env::var_or("ROCKET_CONFIG", "Rocket.toml")).nested())
+            .focus(CONFIG_KEY);
+
+        return Figment::from(Serialized::defaults(ValnkConfig::default()))
+            .merge(rocket_toml)
+            .merge(Toml::file(Env::var_or("VALNK_CONFIG", "Valnk.toml")).nested())
+            .merge(Env::prefixed("VALNK_").ignore(&["CONFIG"]).global())
+            .select(rocket::Config::figment().profile().clone());
+    }
+
+    /// Loads the configuration from its sources, fails if a page limit is not positive.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use figment::Jail;
+    /// use valnk::config::ValnkConfig;
+    ///
+    /// Jail::expect_with(|jail| {
+    ///     jail.create_file("Rocket.toml", r#"
+    ///         [default.valnk]
+    ///         table_prefix = "dev-"
+    ///         max_page_limit = 50
+    ///     "#)?;
+    ///     jail.set_env("VALNK_ENDPOINT_URL", "http://localhost:8000");
+    ///
+    ///     let config = ValnkConfig::load()?;
+    ///     assert_eq!(config.table(), "dev-valnk-content");
+    ///     assert_eq!(config.endpoint_url.as_deref(), Some("http://localhost:8000"));
+    ///     assert_eq!(config.page_limits().max_limit, 50);
+    ///
+    ///     jail.set_env("VALNK_DEFAULT_PAGE_LIMIT", "0");
+    ///     assert!(ValnkConfig::load().is_err());
+    ///     Ok(())
+    /// });
+    /// ```
+    #[allow(clippy::result_large_err)]
+    pub fn load() -> std::result::Result<Self, figment::Error> {
+        let config: Self = Self::figment().extract()?;
+
+        let page_limits = [("default_page_limit", config.default_page_limit), ("max_page_limit", config.max_page_limit)];
+        for (name, limit) in page_limits {
+            if limit < 1 {
+                return Err(figment::Error::from(format!("`{name}` must be at least 1, not {limit}")));
+            }
+        }
+
+        return Ok(config);
+    }
+
+    /// The name of the content table, with its prefix.
+    pub fn table(&self) -> String {
+        return format!("{}{}", self.table_prefix, self.table_name);
+    }
+
+    pub fn page_limits(&self) -> PageLimits {
+        return PageLimits::new(self.default_page_limit, self.max_page_limit);
+    }
+
+    pub fn retry_policy(&self) -> RetryPolicy {
+        return match self.max_attempts {
+            Some(n) => RetryPolicy::new().with_max_attempts(n),
+            None => RetryPolicy::default(),
+        };
+    }
+
+    /// The AWS config from the environment, with the region and the timeouts of this one.
+    pub async fn sdk_config(&self) -> aws_config::SdkConfig {
+        let mut loader = aws_config::from_env();
+        if let Some(region) = &self.region {
+            loader = loader.region(Region::new(region.clone()));
+        }
+
+        let mut timeouts = TimeoutConfig::builder();
+        if let Some(ms) = self.connect_timeout_ms {
+            timeouts = timeouts.connect_timeout(Duration::from_millis(ms));
+        }
+        if let Some(ms) = self.operation_timeout_ms {
+            timeouts = timeouts.operation_timeout(Duration::from_millis(ms));
+        }
+
+        return loader.timeout_config(timeouts.build()).load().await;
+    }
+
+    /// A DynamoDB client on the endpoint of this config,
+    /// fails with `Error::BadRequest` if the endpoint is not a valid url.
+    pub async fn dynamodb_client(&self) -> Result<DynamodbClient> {
+        return dynamodb_client(&self.sdk_config().await, self.endpoint_url.as_deref());
+    }
+
+    /// A store on the content table, with the retry policy of this config.
+    ///
+    /// # Example:
+    ///
+    /// ```no_run
+    /// use tokio;
+    /// use valnk::config::ValnkConfig;
+    /// use valnk::data::api::submission::Client;
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let config = ValnkConfig::load().unwrap();
+    ///     let store = config.store().await.unwrap();
+    ///     let cli = Client::new(&store).with_page_limits(config.page_limits());
+    /// }
+    /// ```
+    pub async fn store(&self) -> Result<DynamoStore> {
+        let store = DynamoStore::new(self.dynamodb_client().await?, self.table())
+            .with_retry_policy(self.retry_policy());
+
+        return Ok(store);
+    }
+}
@@ -1,2 +1,4 @@
+#[allow(clippy::needless_return)]
 pub mod model;
+#[allow(clippy::needless_return)]
 pub mod api;
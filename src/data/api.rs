@@ -1,7 +1,27 @@
 pub mod client;
+pub mod valnk;
 pub mod submission;
+pub mod comment;
+pub mod reply;
+pub mod author;
+pub mod vote;
+pub mod ranking;
+pub mod migration;
+pub mod purge;
 pub mod cursor;
 pub mod result;
+pub mod retry;
+pub mod page;
+pub mod lookup;
+pub mod idempotency;
+pub mod edit;
+pub mod revision;
+pub mod thread;
+pub mod tombstone;
+pub mod decode;
+pub mod store;
+
+pub use valnk::Valnk;
 
 #[cfg(test)]
 mod tests;
@@ -0,0 +1,410 @@
+use std::collections::{HashMap, VecDeque};
+
+use chrono::{DateTime, Utc};
+use aws_sdk_dynamodb::model::AttributeValue;
+
+use crate::data::model::entity::{Entity, EntityType};
+use crate::data::model::key_codec;
+use crate::data::model::submission as subm_model;
+use crate::data::model::comment as comm_model;
+use crate::data::model::reply as reply_model;
+
+use super::result::{Error, Result};
+use super::cursor::{Cursor, Direction, scope_of};
+use super::decode::{decode_entity, decode_items, DecodeError};
+use super::tombstone::blank_deleted;
+use super::page::{query_items, paginate, ItemStream, PageLimits};
+use super::store::{ContentStore, Index, Item, SkCondition, QueryInput};
+
+/// The entity types of an interleaved feed, ties in time are broken in this order.
+const FEED_TYPES: [EntityType; 3] = [EntityType::Submission, EntityType::Comment, EntityType::Reply];
+
+#[derive(Clone, Debug)]
+pub struct ListItemsByAuthorInput {
+    pub author_id: String,
+    /// Only lists one type of entities, all of them are interleaved by time if `None`.
+    pub entity_type: Option<EntityType>,
+    /// Only lists the entities created at or after `since`.
+    pub since: Option<DateTime<Utc>>,
+    /// Only lists the entities created at or before `until`.
+    pub until: Option<DateTime<Utc>>,
+    pub limit: Option<i32>,
+    pub reverse: Option<bool>,
+    pub start_cursor: Option<Cursor>,
+}
+
+impl ListItemsByAuthorInput {
+    pub fn new(author_id: impl Into<String>) -> Self {
+        Self {
+            author_id: author_id.into(),
+            entity_type: None,
+            since: None,
+            until: None,
+            limit: None,
+            reverse: None,
+            start_cursor: None,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct ListItemsByAuthorOutput {
+    pub items: Vec<Entity>,
+    /// The items of the page that could not be decoded, they are left out of `items`.
+    pub invalid_items: Vec<DecodeError>,
+    pub next_cursor: Option<Cursor>,
+    /// The cursor of the page before this one, `None` on the first page.
+    pub prev_cursor: Option<Cursor>,
+}
+
+impl ListItemsByAuthorOutput {
+    pub fn new(items: Vec<Entity>) -> Self {
+        Self {
+            items,
+            invalid_items: vec![],
+            next_cursor: None,
+            prev_cursor: None,
+        }
+    }
+}
+
+
+/// The `AuthorIndexKey` sort-key prefix of an entity type.
+fn author_sk_prefix(entity_type: &EntityType) -> Result<String> {
+    return match entity_type {
+        EntityType::Submission => Ok(subm_model::AuthorIndexKey::sk_prefix()),
+        EntityType::Comment => Ok(comm_model::AuthorIndexKey::sk_prefix()),
+        EntityType::Reply => Ok(reply_model::AuthorIndexKey::sk_prefix()),
+        EntityType::Vote | EntityType::IdempotencyKey | EntityType::Revision => {
+            Err(Error::BadRequest(format!("`{entity_type:?}` is not indexed by author")))
+        }
+    };
+}
+
+fn sk_condition(entity_type: &EntityType, since: &Option<DateTime<Utc>>, until: &Option<DateTime<Utc>>) -> Result<SkCondition> {
+    let sk_prefix = author_sk_prefix(entity_type)?;
+    // `$` follows `#`, so it sorts after every sort key with the `<TAG>#` prefix
+    let sk_end = format!("{}$", sk_prefix.trim_end_matches('#'));
+
+    let start = |t: &DateTime<Utc>| key_codec::sort_key_start(&sk_prefix, t.timestamp());
+    let end = |t: &DateTime<Utc>| key_codec::sort_key_end(&sk_prefix, t.timestamp());
+
+    let condition = match (since, until) {
+        (None, None) => SkCondition::BeginsWith(sk_prefix.clone()),
+        (Some(s), None) => SkCondition::Between(start(s), sk_end),
+        (None, Some(u)) => SkCondition::Between(sk_prefix.clone(), end(u)),
+        (Some(s), Some(u)) => SkCondition::Between(start(s), end(u)),
+    };
+
+    return Ok(condition);
+}
+
+/// The position of one entity type in an interleaved cursor,
+/// its keys are stored as `<TAG>.<attr>`, and `<TAG>.done` marks the end.
+///
+/// No position is the start of the type, in the direction of the cursor.
+fn stream_position(positions: &Item, cursor_prefix: &str) -> Option<Item> {
+    let key: Item = positions.iter()
+        .filter_map(|(k, v)| k.strip_prefix(cursor_prefix).map(|attr| (attr.to_string(), v.clone())))
+        .filter(|(attr, _)| attr != "done")
+        .collect();
+
+    return if key.is_empty() { None } else { Some(key) };
+}
+
+fn is_done(positions: &Item, cursor_prefix: &str) -> bool {
+    return positions.contains_key(&format!("{cursor_prefix}done"));
+}
+
+fn set_position(positions: &mut Item, cursor_prefix: &str, key: Item) {
+    positions.retain(|k, _| !k.starts_with(cursor_prefix));
+    for (attr, v) in key {
+        positions.insert(format!("{cursor_prefix}{attr}"), v);
+    }
+}
+
+fn set_done(positions: &mut Item, cursor_prefix: &str) {
+    positions.retain(|k, _| !k.starts_with(cursor_prefix));
+    positions.insert(format!("{cursor_prefix}done"), AttributeValue::S("1".to_string()));
+}
+
+/// The items of one entity type, with the ones that failed to decode in their place,
+/// so the position of the type moves past them too.
+#[derive(Debug)]
+struct Stream {
+    cursor_prefix: String,
+    query: QueryInput,
+    items: VecDeque<(Item, std::result::Result<Entity, DecodeError>)>,
+    /// The `last_evaluated_key` of the last query, `None` at the end of the type.
+    next_key: Option<Item>,
+    first_key: Option<Item>,
+    last_consumed: Option<Item>,
+}
+
+impl Stream {
+    /// Queries the next page of the type into `items`.
+    async fn fetch(&mut self, store: &dyn ContentStore) -> Result<()> {
+        let results = store.query(self.query.clone()).await?;
+        self.next_key = results.last_evaluated_key;
+        self.query.exclusive_start_key = self.next_key.clone();
+        if self.first_key.is_none() {
+            self.first_key = results.items.first().map(|it| Index::Gsi2.start_key(it));
+        }
+
+        for mut item in results.items {
+            let key = Index::Gsi2.start_key(&item);
+            blank_deleted(&mut item);
+            self.items.push_back((key, decode_entity(item)));
+        }
+
+        Ok(())
+    }
+
+    /// Consumes the items at the front that failed to decode, into `invalid_items`,
+    /// and queries the next pages while nothing else is left, so the next item
+    /// of the type is never one that has not been read yet.
+    async fn skip_invalid(&mut self, store: &dyn ContentStore, invalid_items: &mut Vec<DecodeError>) -> Result<()> {
+        loop {
+            while let Some((_, Err(_))) = self.items.front() {
+                let Some((key, Err(e))) = self.items.pop_front() else { break; };
+                self.last_consumed = Some(key);
+                invalid_items.push(e);
+            }
+
+            if !self.items.is_empty() || self.next_key.is_none() {
+                return Ok(());
+            }
+            self.fetch(store).await?;
+        }
+    }
+
+    fn front(&self) -> Option<&Entity> {
+        return self.items.front().and_then(|(_, entity)| entity.as_ref().ok());
+    }
+}
+
+
+#[derive(Debug)]
+pub struct Client<'c> {
+    store: &'c dyn ContentStore,
+    page_limits: PageLimits,
+}
+
+impl<'c> Client<'c> {
+    pub fn new(store: &'c dyn ContentStore) -> Self {
+        return Self {
+            store,
+            page_limits: PageLimits::default(),
+        };
+    }
+
+    pub fn with_page_limits(mut self, page_limits: PageLimits) -> Self {
+        self.page_limits = page_limits;
+        self
+    }
+
+    /// Lists the submissions, comments and replies of an author, the newest first
+    /// (or the oldest first when `reverse` is set).
+    ///
+    /// # Example:
+    ///
+    /// ```no_run
+    /// use tokio;
+    /// use chrono::{Duration, Utc};
+    /// use valnk::data::api::author::*;
+    /// use valnk::data::api::store::{dynamodb_client, DynamoStore};
+    /// use valnk::data::model::entity::EntityType;
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let shared_config = aws_config::load_from_env().await;
+    ///     let aws_cli = dynamodb_client(&shared_config, None).unwrap();
+    ///     let store = DynamoStore::new(aws_cli, "valnk-content");
+    ///     let cli = Client::new(&store);
+    ///
+    ///     // the comments of the last week
+    ///     let mut input = ListItemsByAuthorInput::new("py0x");
+    ///     input.entity_type = Some(EntityType::Comment);
+    ///     input.since = Some(Utc::now() - Duration::days(7));
+    ///
+    ///     let output = cli.list_items_by_author(input).await.unwrap();
+    /// }
+    /// ```
+    pub async fn list_items_by_author(&self, input: ListItemsByAuthorInput) -> Result<ListItemsByAuthorOutput> {
+        let limit = self.page_limits.resolve(input.limit)?;
+
+        return match &input.entity_type {
+            Some(entity_type) => self.list_by_type(entity_type, &input, limit).await,
+            None => self.list_interleaved(&input, limit).await,
+        };
+    }
+
+    fn author_query(&self, entity_type: &EntityType, input: &ListItemsByAuthorInput, limit: i32) -> Result<QueryInput> {
+        let mut query = QueryInput::new(Index::Gsi2, subm_model::AuthorIndexKey::pk(&input.author_id));
+        query.sk = Some(sk_condition(entity_type, &input.since, &input.until)?);
+        query.scan_index_forward = input.reverse.unwrap_or(false);
+        query.limit = Some(limit);
+
+        return Ok(query);
+    }
+
+    async fn list_by_type(&self, entity_type: &EntityType, input: &ListItemsByAuthorInput, limit: i32) -> Result<ListItemsByAuthorOutput> {
+        let query = self.author_query(entity_type, input, limit)?;
+        let mut page = query_items(self.store, query, input.start_cursor.clone()).await?;
+        page.items.iter_mut().for_each(blank_deleted);
+        let decoded = decode_items(page.items);
+
+        let mut output = ListItemsByAuthorOutput::new(decoded.entities);
+        output.invalid_items = decoded.errors;
+        output.next_cursor = page.next_cursor;
+        output.prev_cursor = page.prev_cursor;
+
+        Ok(output)
+    }
+
+    /// Queries a page of every entity type and merges them by time,
+    /// the cursor keeps the position of each type.
+    ///
+    /// A `Prev` cursor queries every type the other way round from its position,
+    /// merges them in the reverse order and reverses the page back.
+    async fn list_interleaved(&self, input: &ListItemsByAuthorInput, limit: i32) -> Result<ListItemsByAuthorOutput> {
+        let reverse = input.reverse.unwrap_or(false);
+        let scope = scope_of(&format!(
+            "author|{}|{:?}|{:?}|{reverse}",
+            input.author_id, input.since, input.until,
+        ));
+
+        let mut direction = Direction::Next;
+        let mut positions: Item = HashMap::new();
+        if let Some(cur) = input.start_cursor.clone() {
+            direction = cur.direction();
+            positions = cur.into_key(&scope)?;
+        }
+        let backward = direction == Direction::Prev;
+
+        let mut streams = vec![];
+        let mut invalid_items = vec![];
+        for entity_type in FEED_TYPES.iter() {
+            let cursor_prefix = format!("{}.", entity_type.tag());
+            if is_done(&positions, &cursor_prefix) {
+                continue;
+            }
+
+            let mut query = self.author_query(entity_type, input, limit)?;
+            if backward {
+                query.scan_index_forward = !query.scan_index_forward;
+            }
+            query.exclusive_start_key = stream_position(&positions, &cursor_prefix);
+
+            let mut stream = Stream {
+                cursor_prefix,
+                query,
+                items: VecDeque::new(),
+                next_key: None,
+                first_key: None,
+                last_consumed: None,
+            };
+            stream.fetch(self.store).await?;
+            streams.push(stream);
+        }
+
+        // the order of the feed: by time, then by the order of the types
+        let rank = |entity: &Entity, i: usize| {
+            let ts = entity.created_at().timestamp();
+            return if reverse { (ts, i) } else { (-ts, i) };
+        };
+
+        let mut items = vec![];
+        while items.len() < limit as usize {
+            let mut next: Option<(usize, (i64, usize))> = None;
+            for (i, stream) in streams.iter_mut().enumerate() {
+                stream.skip_invalid(self.store, &mut invalid_items).await?;
+                if let Some(entity) = stream.front() {
+                    let r = rank(entity, i);
+                    let is_next = match next {
+                        None => true,
+                        Some((_, next_r)) if backward => r > next_r,
+                        Some((_, next_r)) => r < next_r,
+                    };
+                    if is_next {
+                        next = Some((i, r));
+                    }
+                }
+            }
+
+            let Some((i, _)) = next else { break; };
+            let Some((key, Ok(entity))) = streams[i].items.pop_front() else { break; };
+            streams[i].last_consumed = Some(key);
+            items.push(entity);
+        }
+        if backward {
+            items.reverse();
+        }
+
+        // the positions behind the page: a type with nothing behind the start
+        // position is done, a type that was done has all of its items behind it
+        let mut behind: Item = HashMap::new();
+        for entity_type in FEED_TYPES.iter() {
+            let cursor_prefix = format!("{}.", entity_type.tag());
+            if is_done(&positions, &cursor_prefix) {
+                continue;
+            }
+            if stream_position(&positions, &cursor_prefix).is_none() {
+                set_done(&mut behind, &cursor_prefix);
+                continue;
+            }
+
+            let stream = streams.iter().find(|s| s.cursor_prefix == cursor_prefix);
+            if let Some(key) = stream.and_then(|s| s.first_key.clone()) {
+                set_position(&mut behind, &cursor_prefix, key);
+            }
+        }
+        let resumed = !positions.is_empty();
+
+        let mut has_more = false;
+        for stream in streams {
+            if stream.items.is_empty() && stream.next_key.is_none() {
+                set_done(&mut positions, &stream.cursor_prefix);
+                continue;
+            }
+
+            has_more = true;
+            if let Some(key) = stream.last_consumed {
+                set_position(&mut positions, &stream.cursor_prefix, key);
+            }
+        }
+
+        let mut ahead = None;
+        if has_more {
+            ahead = Some(Cursor::new(positions, &scope, direction)?);
+        }
+        let mut behind_cursor = None;
+        if resumed {
+            behind_cursor = Some(Cursor::new(behind, &scope, direction.reversed())?);
+        }
+
+        let mut output = ListItemsByAuthorOutput::new(items);
+        output.invalid_items = invalid_items;
+        (output.next_cursor, output.prev_cursor) = match direction {
+            Direction::Next => (ahead, behind_cursor),
+            Direction::Prev => (behind_cursor, ahead),
+        };
+
+        Ok(output)
+    }
+
+    /// Streams the submissions, comments and replies of an author, following the cursors
+    /// from `input.start_cursor`, `input.limit` is the size of the pages and `max_items` caps the number of items.
+    ///
+    /// The items that cannot be decoded are left out, as in the `items` of the pages.
+    pub fn stream_items_by_author(&self, input: ListItemsByAuthorInput, max_items: Option<usize>) -> ItemStream<'_, Entity> {
+        return paginate(input.start_cursor.clone(), max_items, move |cursor| {
+            let mut input = input.clone();
+            input.start_cursor = cursor;
+            async move {
+                let output = self.list_items_by_author(input).await?;
+                Ok((output.items, output.next_cursor))
+            }
+        });
+    }
+}
@@ -2,6 +2,12 @@ use aws_config;
 use aws_sdk_dynamodb::Client as AwsDdbClient;
 use std::ops::Deref;
 
+use crate::config::ValnkConfig;
+
+use super::result::Result;
+use super::store::{dynamodb_config, DynamoStore};
+use super::valnk::Valnk;
+
 #[derive(Debug)]
 enum DdbClient<'c> {
     SharedClient(&'c AwsDdbClient),
@@ -13,7 +19,7 @@ impl<'c> Deref for DdbClient<'c> {
 
     fn deref(&self) -> &Self::Target {
         return match self {
-            Self::SharedClient(cli) => *cli,
+            Self::SharedClient(cli) => cli,
             Self::OwnedClient(cli) => cli
         };
     }
@@ -22,6 +28,7 @@ impl<'c> Deref for DdbClient<'c> {
 #[derive(Debug)]
 pub struct Client<'c> {
     ddb_cli: DdbClient<'c>,
+    config: ValnkConfig,
 }
 
 impl<'c> Client<'c> {
@@ -41,13 +48,40 @@ impl<'c> Client<'c> {
     /// ```
     pub async fn new() -> Client<'c> {
         let shared_config = aws_config::load_from_env().await;
-        let aws_cli = AwsDdbClient::new(&shared_config);
+        let aws_cli = AwsDdbClient::from_conf(dynamodb_config(&shared_config).build());
 
         return Client {
             ddb_cli: DdbClient::OwnedClient(aws_cli),
+            config: ValnkConfig::default(),
         };
     }
 
+    /// Creates a new client with the region, endpoint and timeouts of the config,
+    /// fails with `Error::BadRequest` if its endpoint is not a valid url.
+    ///
+    /// # Example:
+    ///
+    /// ```no_run
+    /// use tokio;
+    /// use valnk::config::ValnkConfig;
+    /// use valnk::data::api::client::*;
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let config = ValnkConfig::load().unwrap();
+    ///     let client = Client::from_config(config).await.unwrap();
+    ///     let store = client.content_store();
+    /// }
+    /// ```
+    pub async fn from_config(config: ValnkConfig) -> Result<Client<'c>> {
+        let aws_cli = config.dynamodb_client().await?;
+
+        return Ok(Client {
+            ddb_cli: DdbClient::OwnedClient(aws_cli),
+            config,
+        });
+    }
+
     /// Creates a new client from a shared config.
     ///
     /// # Example:
@@ -64,34 +98,71 @@ impl<'c> Client<'c> {
     /// }
     /// ```
     pub fn from_aws_conf(aws_config: &aws_config::SdkConfig) -> Client<'c> {
-        let aws_cli = AwsDdbClient::new(&aws_config);
+        let aws_cli = AwsDdbClient::from_conf(dynamodb_config(aws_config).build());
 
         return Client {
             ddb_cli: DdbClient::OwnedClient(aws_cli),
+            config: ValnkConfig::default(),
         };
     }
 
-    /// Creates a new client from a shared aws client.
+    /// Creates a new client from a shared aws client, whose own retries should be disabled,
+    /// see [`DynamoStore`].
     ///
     /// # Example:
     ///
     /// ```
     /// use tokio;
     /// use aws_config;
-    /// use aws_sdk_dynamodb;
     /// use valnk::data::api::client::*;
+    /// use valnk::data::api::store::dynamodb_client;
     ///
     /// #[tokio::main]
     /// async fn main() {
     ///     let shared_config = aws_config::load_from_env().await;
-    ///     let aws_cli = aws_sdk_dynamodb::Client::new(&shared_config);
+    ///     let aws_cli = dynamodb_client(&shared_config, None).unwrap();
     ///     let client = Client::from_aws_cli(&aws_cli);
     /// }
     /// ```
     pub fn from_aws_cli(aws_ddb_cli: &'c AwsDdbClient) -> Client<'c> {
         return Client {
             ddb_cli: DdbClient::SharedClient(aws_ddb_cli),
+            config: ValnkConfig::default(),
         };
     }
-}
 
+    /// Creates a [`DynamoStore`] on the table, sharing the underlying aws client.
+    ///
+    /// # Example:
+    ///
+    /// ```
+    /// use tokio;
+    /// use valnk::data::api::client::*;
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let client = Client::new().await;
+    ///     let store = client.store("valnk-content");
+    /// }
+    /// ```
+    pub fn store(&self, table_name: impl Into<String>) -> DynamoStore {
+        let aws_cli = AwsDdbClient::clone(&self.ddb_cli);
+
+        return DynamoStore::new(aws_cli, table_name)
+            .with_retry_policy(self.config.retry_policy());
+    }
+
+    /// Creates a [`DynamoStore`] on the content table of the config.
+    pub fn content_store(&self) -> DynamoStore {
+        return self.store(self.config.table());
+    }
+
+    pub fn config(&self) -> &ValnkConfig {
+        return &self.config;
+    }
+
+    /// A [`Valnk`] on the content table, sharing the underlying aws client.
+    pub fn valnk(&self) -> Valnk {
+        return Valnk::new(self.content_store(), self.config.clone());
+    }
+}
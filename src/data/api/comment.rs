@@ -0,0 +1,397 @@
+use chrono::{DateTime, Utc};
+use serde_dynamo;
+
+use crate::data::model::entity::EntityType;
+use crate::data::model::idempotency::request_digest;
+use crate::data::model::submission::{self as subm_model, SubmissionId};
+use crate::data::model::comment::{
+    Comment,
+    CommentId,
+    PrimaryKey,
+    SubmissionIndexKey,
+};
+
+use super::result::{Error, Result};
+use super::cursor::Cursor;
+use super::page::{query_page, paginate, ItemStream, PageLimits};
+use super::lookup::{get_entity, batch_get_entities};
+use super::idempotency::{create_once, IdempotentCreate};
+use super::edit::{edit_entity, Edit};
+use super::tombstone::{soft_delete, undelete, DELETED_AT_ATTR};
+use super::store::{
+    ContentStore,
+    Index,
+    Key,
+    SkCondition,
+    Condition,
+    UpdateAction,
+    TransactWriteItem,
+    PutItemInput,
+    UpdateItemInput,
+    QueryInput,
+    condition_failed,
+};
+
+/// The counter of the comments and replies of a submission.
+const N_COMMENTS_ATTR: &str = "n_comments";
+
+
+#[derive(Clone, Debug)]
+pub struct GetItemInput {
+    pub id: CommentId,
+    pub consistent_read: Option<bool>,
+}
+
+impl GetItemInput {
+    pub fn new(id: CommentId) -> Self {
+        Self {
+            id,
+            consistent_read: None,
+        }
+    }
+}
+
+/// An edit of the text of a comment.
+#[derive(Clone, Debug)]
+pub struct EditItemInput {
+    pub id: CommentId,
+    /// The user who edits the comment, the author or a moderator.
+    pub editor_id: String,
+    /// The `updated_at` of the comment the edit is based on.
+    pub expected_updated_at: DateTime<Utc>,
+    pub text: String,
+}
+
+impl EditItemInput {
+    pub fn new(id: CommentId, editor_id: impl Into<String>, expected_updated_at: DateTime<Utc>, text: impl Into<String>) -> Self {
+        Self {
+            id,
+            editor_id: editor_id.into(),
+            expected_updated_at,
+            text: text.into(),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct DeleteItemInput {
+    pub id: CommentId,
+    /// The user who deletes the comment, the author or a moderator.
+    pub deleted_by: String,
+}
+
+impl DeleteItemInput {
+    pub fn new(id: CommentId, deleted_by: impl Into<String>) -> Self {
+        Self {
+            id,
+            deleted_by: deleted_by.into(),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct UndeleteItemInput {
+    pub id: CommentId,
+}
+
+impl UndeleteItemInput {
+    pub fn new(id: CommentId) -> Self {
+        Self {
+            id,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct BatchGetItemsInput {
+    pub ids: Vec<CommentId>,
+    pub consistent_read: Option<bool>,
+}
+
+impl BatchGetItemsInput {
+    pub fn new(ids: Vec<CommentId>) -> Self {
+        Self {
+            ids,
+            consistent_read: None,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct ListItemsBySubmissionInput {
+    pub submission_id: SubmissionId,
+    pub limit: Option<i32>,
+    pub reverse: Option<bool>,
+    pub start_cursor: Option<Cursor>,
+}
+
+impl ListItemsBySubmissionInput {
+    pub fn new(submission_id: SubmissionId) -> Self {
+        Self {
+            submission_id,
+            limit: None,
+            reverse: None,
+            start_cursor: None,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct ListItemsBySubmissionOutput {
+    pub items: Vec<Comment>,
+    pub next_cursor: Option<Cursor>,
+    /// The cursor of the page before this one, `None` on the first page.
+    pub prev_cursor: Option<Cursor>,
+}
+
+impl ListItemsBySubmissionOutput {
+    pub fn new(items: Vec<Comment>) -> Self {
+        Self {
+            items,
+            next_cursor: None,
+            prev_cursor: None,
+        }
+    }
+}
+
+
+fn key_of(id: &CommentId) -> Key {
+    let pk = PrimaryKey::new(id);
+    return Key::new(pk.pk, pk.sk);
+}
+
+/// The writes of a new comment: the comment, that never replaces an existing one,
+/// and the counter of its submission, that must exist and not be deleted.
+fn create_writes(comm: &Comment) -> Result<Vec<TransactWriteItem>> {
+    let subm_pk = subm_model::PrimaryKey::new(&comm.submission_id);
+    let item = serde_dynamo::to_item(comm)
+        .map_err(Error::InvalidInputData)?;
+
+    let mut put = PutItemInput::new(item);
+    put.condition = Some(Condition::AttributeNotExists(Index::Primary.pk_attr().to_string()));
+
+    let mut update = UpdateItemInput::new(Key::new(&subm_pk.pk, subm_pk.sk), vec![
+        UpdateAction::Add(N_COMMENTS_ATTR.to_string(), 1),
+    ]);
+    update.condition = Some(Condition::And(vec![
+        Condition::AttributeExists(Index::Primary.pk_attr().to_string()),
+        Condition::AttributeNotExists(DELETED_AT_ATTR.to_string()),
+    ]));
+
+    return Ok(vec![
+        TransactWriteItem::Put(put),
+        TransactWriteItem::Update(update),
+    ]);
+}
+
+fn create_error(err: Error, comm: &Comment) -> Error {
+    return match err {
+        Error::TransactionCancelled(reasons, _) if condition_failed(&reasons, 0) => {
+            Error::Conflict(format!("the comment `{}` already exists", comm.primary_key.pk))
+        }
+        Error::TransactionCancelled(reasons, _) if condition_failed(&reasons, 1) => {
+            let subm_pk = subm_model::PrimaryKey::new(&comm.submission_id);
+            Error::Conflict(format!("the submission `{}` does not exist or is deleted", subm_pk.pk))
+        }
+        e => e,
+    };
+}
+
+#[derive(Debug)]
+pub struct Client<'c> {
+    store: &'c dyn ContentStore,
+    page_limits: PageLimits,
+}
+
+impl<'c> Client<'c> {
+    pub fn new(store: &'c dyn ContentStore) -> Self {
+        return Self {
+            store,
+            page_limits: PageLimits::default(),
+        };
+    }
+
+    pub fn with_page_limits(mut self, page_limits: PageLimits) -> Self {
+        self.page_limits = page_limits;
+        self
+    }
+
+    /// Creates a comment and bumps the `n_comments` of its submission in one transaction,
+    /// fails with `Error::Conflict` if the submission does not exist or is deleted, or if a comment
+    /// with the same id exists.
+    ///
+    /// # Example:
+    ///
+    /// ```no_run
+    /// use tokio;
+    /// use valnk::data::api::comment::*;
+    /// use valnk::data::api::store::{dynamodb_client, DynamoStore};
+    /// use valnk::data::model::submission::SubmissionId;
+    /// use valnk::data::model::comment as comm_model;
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let shared_config = aws_config::load_from_env().await;
+    ///     let aws_cli = dynamodb_client(&shared_config, None).unwrap();
+    ///     let store = DynamoStore::new(aws_cli, "valnk-content");
+    ///     let cli = Client::new(&store);
+    ///     let comm = comm_model::CommentBuilder::new()
+    ///         .with_submission_id(SubmissionId::from("subm-id").unwrap())
+    ///         .with_author_id("py0x")
+    ///         .with_text("hello create_item")
+    ///         .with_ranking_score(10)
+    ///         .build()
+    ///         .unwrap();
+    ///
+    ///     cli.create_item(comm).await.unwrap();
+    /// }
+    /// ```
+    pub async fn create_item(&self, comm: Comment) -> Result<()> {
+        let writes = create_writes(&comm)?;
+
+        return self.store.transact_write_items(writes).await
+            .map_err(|e| create_error(e, &comm));
+    }
+
+    /// Creates a comment unless one has been created with the same `idempotency_key`
+    /// by its author, returns the comment created with the key, now or earlier.
+    pub async fn create_item_idempotent(&self, comm: Comment, idempotency_key: &str) -> Result<Comment> {
+        let create = IdempotentCreate {
+            author_id: comm.author_id.clone(),
+            key: idempotency_key.to_string(),
+            target_type: EntityType::Comment,
+            target_id: comm.id.clone(),
+            target: key_of(&comm.id),
+            request_digest: request_digest(&[
+                ("submission_id", comm.submission_id.as_ref()),
+                ("text", &comm.text),
+            ]),
+        };
+
+        return match create_once(self.store, create, create_writes(&comm)?).await {
+            Ok(Some(original)) => Ok(original),
+            Ok(None) => Ok(comm),
+            Err(e) => Err(create_error(e, &comm)),
+        };
+    }
+
+    /// Edits the text of a comment and returns it edited, fails with `Error::Conflict`
+    /// if it has been edited since `input.expected_updated_at`, and with
+    /// `Error::NotFound` if it does not exist. The replaced text is kept in a `Revision`.
+    pub async fn edit_item(&self, input: EditItemInput) -> Result<Comment> {
+        let edit = Edit {
+            target_type: EntityType::Comment,
+            target_id: input.id.clone(),
+            target: key_of(&input.id),
+            editor_id: input.editor_id,
+            expected_updated_at: input.expected_updated_at,
+            fields: vec![("text", input.text)],
+            derived: vec![],
+        };
+
+        return edit_entity(self.store, edit).await;
+    }
+
+    /// Deletes a comment: it is kept, with its counters, as a tombstone whose content
+    /// is blank in the reads, so its replies stay in place. Deleting it again is a no-op,
+    /// fails with `Error::NotFound` if it does not exist.
+    pub async fn delete_item(&self, input: DeleteItemInput) -> Result<()> {
+        return soft_delete(self.store, key_of(&input.id), &input.deleted_by).await;
+    }
+
+    /// Restores a deleted comment, for the moderators, fails with `Error::NotFound`
+    /// if it does not exist and with `Error::Conflict` if it has been purged.
+    pub async fn undelete_item(&self, input: UndeleteItemInput) -> Result<()> {
+        return undelete(self.store, key_of(&input.id)).await;
+    }
+
+    /// Returns the comment with the id, fails with `Error::NotFound` if there is none.
+    ///
+    /// # Example:
+    ///
+    /// ```no_run
+    /// use tokio;
+    /// use valnk::data::api::comment::*;
+    /// use valnk::data::api::store::{dynamodb_client, DynamoStore};
+    /// use valnk::data::model::comment::CommentId;
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let shared_config = aws_config::load_from_env().await;
+    ///     let aws_cli = dynamodb_client(&shared_config, None).unwrap();
+    ///     let store = DynamoStore::new(aws_cli, "valnk-content");
+    ///     let cli = Client::new(&store);
+    ///
+    ///     let mut input = GetItemInput::new(CommentId::from("comm-id").unwrap());
+    ///     input.consistent_read = Some(true);
+    ///
+    ///     let comment = cli.get_item(input).await.unwrap();
+    /// }
+    /// ```
+    pub async fn get_item(&self, input: GetItemInput) -> Result<Comment> {
+        let consistent_read = input.consistent_read.unwrap_or(false);
+
+        return get_entity(self.store, key_of(&input.id), consistent_read).await;
+    }
+
+    /// Returns the comments with the ids, in the order of the ids,
+    /// fails with `Error::NotFound` if any of them is missing.
+    pub async fn batch_get_items(&self, input: BatchGetItemsInput) -> Result<Vec<Comment>> {
+        let consistent_read = input.consistent_read.unwrap_or(false);
+        let keys = input.ids.iter().map(key_of).collect();
+
+        return batch_get_entities(self.store, keys, consistent_read).await;
+    }
+
+    /// Lists the comments of a submission, the highest ranked first
+    /// (or the lowest first when `reverse` is set).
+    ///
+    /// # Example:
+    ///
+    /// ```no_run
+    /// use tokio;
+    /// use valnk::data::api::comment::*;
+    /// use valnk::data::api::store::{dynamodb_client, DynamoStore};
+    /// use valnk::data::model::submission::SubmissionId;
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let shared_config = aws_config::load_from_env().await;
+    ///     let aws_cli = dynamodb_client(&shared_config, None).unwrap();
+    ///     let store = DynamoStore::new(aws_cli, "valnk-content");
+    ///     let cli = Client::new(&store);
+    ///
+    ///     let mut input = ListItemsBySubmissionInput::new(SubmissionId::from("subm-id").unwrap());
+    ///     input.limit = Some(10);
+    ///
+    ///     let output = cli.list_items_by_submission(input).await.unwrap();
+    /// }
+    /// ```
+    pub async fn list_items_by_submission(&self, input: ListItemsBySubmissionInput) -> Result<ListItemsBySubmissionOutput> {
+        let mut query = QueryInput::new(Index::Gsi1, SubmissionIndexKey::pk(&input.submission_id));
+        query.sk = Some(SkCondition::BeginsWith(SubmissionIndexKey::sk_prefix()));
+        query.scan_index_forward = input.reverse.unwrap_or(false);
+        query.limit = Some(self.page_limits.resolve(input.limit)?);
+
+        let page = query_page(self.store, query, input.start_cursor).await?;
+
+        let mut output = ListItemsBySubmissionOutput::new(page.items);
+        output.next_cursor = page.next_cursor;
+        output.prev_cursor = page.prev_cursor;
+
+        Ok(output)
+    }
+
+    /// Streams the comments of a submission, following the cursors from `input.start_cursor`,
+    /// `input.limit` is the size of the pages and `max_items` caps the number of items.
+    pub fn stream_items_by_submission(&self, input: ListItemsBySubmissionInput, max_items: Option<usize>) -> ItemStream<'_, Comment> {
+        return paginate(input.start_cursor.clone(), max_items, move |cursor| {
+            let mut input = input.clone();
+            input.start_cursor = cursor;
+            async move {
+                let output = self.list_items_by_submission(input).await?;
+                Ok((output.items, output.next_cursor))
+            }
+        });
+    }
+}
@@ -1,45 +1,243 @@
-use std::fmt;
-use std::fmt::Formatter;
-use std::str::FromStr;
 use std::collections::HashMap;
-use std::convert::TryFrom;
 
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use chrono::{Duration, Utc};
+use figment::Figment;
+use hmac::{Hmac, Mac};
 use serde::{Serialize, Deserialize};
+use sha2::{Digest, Sha256};
 
 use aws_sdk_dynamodb::model::AttributeValue;
 
-#[derive(Clone, Serialize, Deserialize, PartialEq, Debug)]
-pub struct Cursor(HashMap<String, String>);
+use super::result::{Error, Result};
+use super::store::QueryInput;
+
+type HmacSha256 = Hmac<Sha256>;
 
-impl TryFrom<HashMap<String, AttributeValue>> for Cursor {
-    type Error = serde_dynamo::Error;
+/// The way a cursor pages from its position, in the order of its list.
+#[derive(Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default, Debug)]
+pub enum Direction {
+    /// The items after the position, e.g. the older ones of a feed.
+    #[default]
+    #[serde(rename = "n")]
+    Next,
+    /// The items before the position, e.g. the newer ones of a feed.
+    #[serde(rename = "p")]
+    Prev,
+}
 
-    fn try_from(value: HashMap<String, AttributeValue>) -> std::result::Result<Self, Self::Error> {
-        serde_dynamo::from_item(value)
+impl Direction {
+    pub fn reversed(&self) -> Self {
+        return match self {
+            Direction::Next => Direction::Prev,
+            Direction::Prev => Direction::Next,
+        };
     }
 }
 
-impl TryFrom<Cursor> for HashMap<String, AttributeValue> {
-    type Error = serde_dynamo::Error;
+/// The position of a list query, e.g. the `LastEvaluatedKey` of its last page,
+/// and the direction to page from it.
+///
+/// A cursor is bound to the query it comes from (its index, partition, sort-key
+/// condition and direction), the list APIs reject a cursor of another query with
+/// `Error::InvalidCursor`. It leaves the server as an opaque token, see [`CursorCodec`].
+#[derive(Clone, Serialize, Deserialize, PartialEq, Debug)]
+pub struct Cursor {
+    #[serde(rename = "k")]
+    key: HashMap<String, String>,
+    #[serde(rename = "s")]
+    scope: String,
+    #[serde(rename = "d", default)]
+    direction: Direction,
+}
+
+impl Cursor {
+    /// An empty `key` is the end of the list the cursor pages towards from,
+    /// e.g. a `Prev` cursor with no key pages from the last item backwards.
+    pub(crate) fn new(key: HashMap<String, AttributeValue>, scope: impl Into<String>, direction: Direction) -> Result<Self> {
+        let key = serde_dynamo::from_item(key)
+            .map_err(Error::InvalidOutputData)?;
+
+        return Ok(Self {
+            key,
+            scope: scope.into(),
+            direction,
+        });
+    }
+
+    pub fn direction(&self) -> Direction {
+        return self.direction;
+    }
+
+    /// The key of the cursor, if it belongs to the query of `scope`.
+    pub(crate) fn into_key(self, scope: &str) -> Result<HashMap<String, AttributeValue>> {
+        if self.scope != scope {
+            return Err(Error::InvalidCursor("the cursor belongs to another query".to_string()));
+        }
 
-    fn try_from(value: Cursor) -> std::result::Result<Self, Self::Error> {
-        serde_dynamo::to_item(value.0)
+        return serde_dynamo::to_item(self.key)
+            .map_err(Error::InvalidInputData);
     }
 }
 
+/// A short digest that tells a query from the others, e.g. of its index, partition and direction.
+pub(crate) fn scope_of(description: &str) -> String {
+    let digest = Sha256::digest(description.as_bytes());
+
+    return digest[..8].iter().map(|b| format!("{b:02x}")).collect();
+}
+
+/// The scope of the cursors of a store query: everything but its position and limit,
+/// the direction is the one of the list, whichever way a cursor pages.
+pub(crate) fn query_scope(query: &QueryInput) -> String {
+    let description = format!(
+        "query|{:?}|{}|{:?}|{}",
+        query.index, query.pk, query.sk, query.scan_index_forward,
+    );
+
+    return scope_of(&description);
+}
+
+
+/// What a token carries: the cursor and the time it was issued at.
+#[derive(Serialize, Deserialize, Debug)]
+struct Token {
+    #[serde(rename = "c")]
+    cursor: Cursor,
+    #[serde(rename = "t")]
+    issued_at: i64,
+}
+
+/// The `secret_key` of a Rocket config, as it is configured: a base64 or hex
+/// string, or the bytes of the key.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum SecretKey {
+    Encoded(String),
+    Bytes(Vec<u8>),
+}
 
-impl fmt::Display for Cursor {
-    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        let s = serde_json::to_string(&self.0).map_err(|e| fmt::Error)?;
+/// Turns cursors into URL-safe tokens and back.
+///
+/// A token is `<payload>.<signature>`, both in unpadded base64url, the signature
+/// is the HMAC-SHA256 of the payload, so the clients cannot forge or alter a cursor.
+///
+/// # Examples
+///
+/// ```
+/// use chrono::Duration;
+/// use valnk::data::api::cursor::CursorCodec;
+///
+/// // e.g. the `secret_key` of the Rocket config
+/// let codec = CursorCodec::new(b"a secret of at least 32 bytes....")
+///     .with_max_age(Duration::hours(1));
+///
+/// assert!(codec.decode("eyJjIjp7fX0.forged").is_err());
+/// ```
+#[derive(Clone)]
+pub struct CursorCodec {
+    secret: Vec<u8>,
+    max_age: Option<Duration>,
+}
 
-        write!(f, "{}", s)
+impl std::fmt::Debug for CursorCodec {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CursorCodec")
+            .field("max_age", &self.max_age)
+            .finish_non_exhaustive()
     }
 }
 
-impl FromStr for Cursor {
-    type Err = serde_json::Error;
+impl CursorCodec {
+    pub fn new(secret: &[u8]) -> Self {
+        return Self {
+            secret: secret.to_vec(),
+            max_age: None,
+        };
+    }
+
+    /// The codec on the `secret_key` of a Rocket config, the one of its private cookies.
+    ///
+    /// Fails if the config has no `secret_key`, or the zero key Rocket defaults to:
+    /// the cursors would be forged by anyone, or not be valid across restarts.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use figment::Figment;
+    /// use valnk::data::api::cursor::CursorCodec;
+    ///
+    /// let figment = Figment::from(rocket::Config::default());
+    /// assert!(CursorCodec::from_figment(&figment).is_err());
+    ///
+    /// let figment = figment.merge(("secret_key", "hPRYyVRiMyxpw5sBB1XeCMN1kFsDCqKvBi2QJxBVHQk="));
+    /// assert!(CursorCodec::from_figment(&figment).is_ok());
+    /// ```
+    #[allow(clippy::result_large_err)]
+    pub fn from_figment(figment: &Figment) -> std::result::Result<Self, figment::Error> {
+        let secret = match figment.extract_inner::<SecretKey>("secret_key")? {
+            SecretKey::Encoded(key) => key.into_bytes(),
+            SecretKey::Bytes(key) => key,
+        };
+        if secret.iter().all(|b| *b == 0) {
+            return Err(figment::Error::from("the `secret_key` is not configured".to_string()));
+        }
+
+        return Ok(Self::new(&secret));
+    }
+
+    /// Tokens older than `max_age` are rejected, they never expire by default.
+    pub fn with_max_age(mut self, max_age: Duration) -> Self {
+        self.max_age = Some(max_age);
+        self
+    }
+
+    fn mac(&self) -> HmacSha256 {
+        return HmacSha256::new_from_slice(&self.secret)
+            .expect("HMAC accepts keys of any length");
+    }
+
+    pub fn encode(&self, cursor: &Cursor) -> String {
+        let token = Token {
+            cursor: cursor.clone(),
+            issued_at: Utc::now().timestamp(),
+        };
+        let payload = serde_json::to_vec(&token)
+            .expect("a cursor is always serializable");
+
+        let mut mac = self.mac();
+        mac.update(&payload);
+        let signature = mac.finalize().into_bytes();
+
+        return format!("{}.{}", URL_SAFE_NO_PAD.encode(payload), URL_SAFE_NO_PAD.encode(signature));
+    }
+
+    /// Fails with `Error::InvalidCursor` if the token is malformed, forged or expired.
+    pub fn decode(&self, token: &str) -> Result<Cursor> {
+        let invalid = |reason: &str| Error::InvalidCursor(reason.to_string());
 
-    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
-        serde_json::from_str(s)
+        let (payload, signature) = token.split_once('.')
+            .ok_or(invalid("malformed token"))?;
+        let payload = URL_SAFE_NO_PAD.decode(payload)
+            .map_err(|_| invalid("malformed token"))?;
+        let signature = URL_SAFE_NO_PAD.decode(signature)
+            .map_err(|_| invalid("malformed token"))?;
+
+        let mut mac = self.mac();
+        mac.update(&payload);
+        mac.verify_slice(&signature)
+            .map_err(|_| invalid("bad signature"))?;
+
+        let token: Token = serde_json::from_slice(&payload)
+            .map_err(|_| invalid("malformed token"))?;
+
+        if let Some(max_age) = self.max_age {
+            if Utc::now().timestamp() - token.issued_at > max_age.num_seconds() {
+                return Err(invalid("expired"));
+            }
+        }
+
+        return Ok(token.cursor);
     }
-}
\ No newline at end of file
+}
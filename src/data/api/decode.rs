@@ -0,0 +1,105 @@
+use serde_dynamo;
+use thiserror::Error;
+
+use crate::data::model::entity::{Entity, EntityType};
+
+use super::store::{Index, Item};
+
+const ENTITY_TYPE_ATTR: &str = "entity_type";
+
+#[derive(Error, Clone, Debug)]
+pub enum DecodeError {
+    #[error("item `{0}` has no `entity_type`")]
+    MissingEntityType(String),
+
+    #[error("item `{0}` has an unknown entity type: `{1}`")]
+    UnknownEntityType(String, String),
+
+    #[error("item `{0}` is a `{1:?}`, not a content entity")]
+    NotContent(String, EntityType),
+
+    #[error("item `{0}` is not a valid `{1:?}`")]
+    CorruptItem(String, EntityType, #[source] serde_dynamo::Error),
+}
+
+/// The entities decoded from a list of raw items,
+/// with the items that could not be decoded.
+#[derive(Clone, Debug)]
+pub struct DecodedItems {
+    pub entities: Vec<Entity>,
+    pub errors: Vec<DecodeError>,
+}
+
+/// `<PK>/<SK>` of an item, to tell which one could not be decoded.
+fn item_id(item: &Item) -> String {
+    let attr = |name: &str| item.get(name)
+        .and_then(|v| v.as_s().ok())
+        .map_or("?", |s| s.as_str());
+
+    return format!("{}/{}", attr(Index::Primary.pk_attr()), attr(Index::Primary.sk_attr()));
+}
+
+/// Decodes a raw item into the entity named by its `entity_type` attribute.
+///
+/// # Examples
+///
+/// ```
+/// use valnk::data::api::decode::decode_entity;
+/// use valnk::data::model::entity::{Entity, EntityType};
+/// use valnk::data::model::submission::SubmissionBuilder;
+///
+/// let subm = SubmissionBuilder::new()
+///     .with_author_id("py0x")
+///     .with_topic("news")
+///     .with_ranking_score(0)
+///     .with_title("title")
+///     .with_url("")
+///     .with_text("")
+///     .build()
+///     .unwrap();
+///
+/// let item = serde_dynamo::to_item(subm.clone()).unwrap();
+/// let entity = decode_entity(item).unwrap();
+///
+/// assert_eq!(entity.entity_type(), EntityType::Submission);
+/// assert_eq!(entity, Entity::Submission(subm));
+/// ```
+pub fn decode_entity(item: Item) -> Result<Entity, DecodeError> {
+    let id = item_id(&item);
+
+    let type_attr = item.get(ENTITY_TYPE_ATTR)
+        .ok_or_else(|| DecodeError::MissingEntityType(id.clone()))?;
+    let entity_type: EntityType = serde_dynamo::from_attribute_value(type_attr.clone())
+        .map_err(|_| {
+            let type_name = type_attr.as_s().map_or("?".to_string(), String::clone);
+            DecodeError::UnknownEntityType(id.clone(), type_name)
+        })?;
+
+    let entity = match entity_type {
+        EntityType::Submission => serde_dynamo::from_item(item).map(Entity::Submission),
+        EntityType::Comment => serde_dynamo::from_item(item).map(Entity::Comment),
+        EntityType::Reply => serde_dynamo::from_item(item).map(Entity::Reply),
+        EntityType::Vote | EntityType::IdempotencyKey | EntityType::Revision => return Err(DecodeError::NotContent(id, entity_type)),
+    };
+
+    return entity.map_err(|e| DecodeError::CorruptItem(id, entity_type, e));
+}
+
+/// Decodes a list of raw items of any entity types, in order.
+///
+/// Unknown or corrupt items are reported in `errors` instead of failing the whole list.
+pub fn decode_items(items: Vec<Item>) -> DecodedItems {
+    let mut decoded = DecodedItems {
+        entities: vec![],
+        errors: vec![],
+    };
+
+    for item in items {
+        match decode_entity(item) {
+            Ok(entity) => decoded.entities.push(entity),
+            Err(e) => decoded.errors.push(e),
+        }
+    }
+
+    return decoded;
+}
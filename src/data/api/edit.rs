@@ -0,0 +1,153 @@
+use std::collections::BTreeMap;
+
+use aws_sdk_dynamodb::model::AttributeValue;
+use chrono::{DateTime, Duration, Utc};
+use serde::de::DeserializeOwned;
+use serde_dynamo;
+
+use crate::data::model::entity::{EntityType, EntityId};
+use crate::data::model::revision::Revision;
+
+use super::result::{Error, Result};
+use super::tombstone::DELETED_AT_ATTR;
+use super::store::{
+    ContentStore,
+    Index,
+    Item,
+    Key,
+    Condition,
+    UpdateAction,
+    TransactWriteItem,
+    GetItemInput,
+    PutItemInput,
+    UpdateItemInput,
+    apply_update,
+    condition_failed,
+};
+
+/// The attribute that versions the editable fields of an entity.
+pub(crate) const UPDATED_AT_ATTR: &str = "updated_at";
+
+pub(crate) fn time_value(dt: &DateTime<Utc>) -> Result<AttributeValue> {
+    return serde_dynamo::to_attribute_value(dt)
+        .map_err(Error::InvalidInputData);
+}
+
+/// An edit of the content fields of an entity, based on the version of `expected_updated_at`.
+#[derive(Clone, Debug)]
+pub(crate) struct Edit {
+    pub target_type: EntityType,
+    pub target_id: EntityId,
+    pub target: Key,
+    pub editor_id: String,
+    pub expected_updated_at: DateTime<Utc>,
+    /// The new values of the edited fields, by attribute.
+    pub fields: Vec<(&'static str, String)>,
+    /// The other attributes derived from the fields, e.g. an index key.
+    pub derived: Vec<UpdateAction>,
+}
+
+async fn get_raw(store: &dyn ContentStore, key: &Key) -> Result<Option<Item>> {
+    let mut input = GetItemInput::new(key.clone());
+    input.consistent_read = true;
+
+    return store.get_item(input).await;
+}
+
+fn edited_since(edit: &Edit) -> Error {
+    return Error::Conflict(format!(
+        "`{}` has been edited since {}", edit.target.pk, edit.expected_updated_at.to_rfc3339(),
+    ));
+}
+
+fn is_deleted(edit: &Edit) -> Error {
+    return Error::Conflict(format!("`{}` is deleted", edit.target.pk));
+}
+
+/// Applies an edit to an entity, if it has not been edited since `expected_updated_at`,
+/// together with the put of its [`Revision`], and returns the edited entity: the version
+/// read with the values written, not a later read that could be another editor's.
+///
+/// The `updated_at` of the entity is refreshed, it always moves forward, so the
+/// edits based on the same version conflict even if the clocks of the writers drift.
+/// Fails with `Error::NotFound` if the entity does not exist, and with
+/// `Error::Conflict` if it has been edited since `expected_updated_at` or deleted.
+pub(crate) async fn edit_entity<T: DeserializeOwned>(store: &dyn ContentStore, edit: Edit) -> Result<T> {
+    if edit.fields.is_empty() {
+        return Err(Error::BadRequest(format!("nothing to edit on `{}`", edit.target.pk)));
+    }
+
+    // the version read here is the one the update is conditioned on,
+    // so the previous values of the revision are the ones replaced
+    let current = get_raw(store, &edit.target).await?
+        .ok_or(Error::NotFound(edit.target.pk.clone()))?;
+    let expected = time_value(&edit.expected_updated_at)?;
+    if current.get(UPDATED_AT_ATTR) != Some(&expected) {
+        return Err(edited_since(&edit));
+    }
+    if current.contains_key(DELETED_AT_ATTR) {
+        return Err(is_deleted(&edit));
+    }
+
+    let updated_at = Utc::now().max(edit.expected_updated_at + Duration::microseconds(1));
+
+    let mut previous = BTreeMap::new();
+    let mut revised = BTreeMap::new();
+    let mut actions = edit.derived.clone();
+    for (attr, value) in edit.fields.iter() {
+        let old = current.get(*attr)
+            .and_then(|v| v.as_s().ok())
+            .cloned()
+            .unwrap_or_default();
+        previous.insert(attr.to_string(), old);
+        revised.insert(attr.to_string(), value.clone());
+        actions.push(UpdateAction::Set(attr.to_string(), AttributeValue::S(value.clone())));
+    }
+    let updated_value = time_value(&updated_at)?;
+    actions.push(UpdateAction::Set(UPDATED_AT_ATTR.to_string(), updated_value.clone()));
+
+    let mut update = UpdateItemInput::new(edit.target.clone(), actions.clone());
+    update.condition = Some(Condition::And(vec![
+        Condition::AttributeExists(Index::Primary.pk_attr().to_string()),
+        Condition::Equals(UPDATED_AT_ATTR.to_string(), expected),
+        Condition::AttributeNotExists(DELETED_AT_ATTR.to_string()),
+    ]));
+
+    let revision = Revision::new(
+        edit.target_type.clone(),
+        edit.target_id.clone(),
+        &edit.editor_id,
+        edit.expected_updated_at,
+        updated_at,
+        previous,
+        revised,
+    );
+    let item = serde_dynamo::to_item(revision)
+        .map_err(Error::InvalidInputData)?;
+    let mut put = PutItemInput::new(item);
+    put.condition = Some(Condition::AttributeNotExists(Index::Primary.pk_attr().to_string()));
+
+    let writes = vec![
+        TransactWriteItem::Update(update),
+        TransactWriteItem::Put(put),
+    ];
+    match store.transact_write_items(writes).await {
+        Ok(()) => {}
+        Err(Error::TransactionCancelled(reasons, _)) if condition_failed(&reasons, 0) || condition_failed(&reasons, 1) => {
+            match get_raw(store, &edit.target).await? {
+                None => return Err(Error::NotFound(edit.target.pk.clone())),
+                // an attempt of this edit that has been applied already
+                Some(item) if item.get(UPDATED_AT_ATTR) == Some(&updated_value) => {}
+                Some(item) if item.contains_key(DELETED_AT_ATTR) => return Err(is_deleted(&edit)),
+                Some(_) => return Err(edited_since(&edit)),
+            }
+        }
+        Err(e) => return Err(e),
+    }
+
+    let mut edited = current;
+    apply_update(&mut edited, &actions)?;
+
+    return serde_dynamo::from_item(edited)
+        .map_err(Error::InvalidOutputData);
+}
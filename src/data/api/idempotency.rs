@@ -0,0 +1,122 @@
+use aws_sdk_dynamodb::model::AttributeValue;
+use chrono::Utc;
+use serde::de::DeserializeOwned;
+use serde_dynamo;
+
+use crate::data::model::entity::{EntityType, EntityId};
+use crate::data::model::idempotency::{IdempotencyRecord, PrimaryKey};
+
+use super::result::{Error, Result};
+use super::lookup::get_entity;
+use super::store::{
+    ContentStore,
+    Index,
+    Key,
+    Condition,
+    TransactWriteItem,
+    GetItemInput,
+    PutItemInput,
+    condition_failed,
+};
+
+/// The TTL attribute of the idempotency records.
+const EXPIRES_AT_ATTR: &str = "expires_at";
+
+/// The creation of an entity with an idempotency key.
+#[derive(Clone, Debug)]
+pub(crate) struct IdempotentCreate {
+    pub author_id: String,
+    pub key: String,
+    pub target_type: EntityType,
+    pub target_id: EntityId,
+    pub target: Key,
+    /// The [`request_digest`](crate::data::model::idempotency::request_digest) of the create.
+    pub request_digest: String,
+}
+
+/// What the record of an idempotency key holds.
+enum Recorded<T> {
+    /// The entity created earlier with the key.
+    Entity(T),
+    /// Nothing, the key is free. An expired record that DynamoDB has not deleted
+    /// yet, its TTL deletion may lag, is replaced if its `expires_at` is unchanged.
+    Free(Option<i64>),
+}
+
+/// The entity created earlier with the key of `create`, if any,
+/// fails with `Error::Conflict` if the key was used for another request.
+async fn replay<T: DeserializeOwned>(store: &dyn ContentStore, create: &IdempotentCreate) -> Result<Recorded<T>> {
+    let pk = PrimaryKey::new(&create.author_id, &create.key);
+    let mut input = GetItemInput::new(Key::new(pk.pk, pk.sk));
+    input.consistent_read = true;
+
+    let Some(item) = store.get_item(input).await? else { return Ok(Recorded::Free(None)); };
+    let record: IdempotencyRecord = serde_dynamo::from_item(item)
+        .map_err(Error::InvalidOutputData)?;
+    if record.expires_at < Utc::now().timestamp() {
+        return Ok(Recorded::Free(Some(record.expires_at)));
+    }
+
+    if record.target_type != create.target_type {
+        return Err(Error::Conflict(format!(
+            "the idempotency key `{}` was used to create a `{:?}`", create.key, record.target_type,
+        )));
+    }
+    if record.request_digest.as_ref().is_some_and(|digest| *digest != create.request_digest) {
+        return Err(Error::Conflict(format!(
+            "the idempotency key `{}` was used for another request", create.key,
+        )));
+    }
+
+    let target = Key::new(record.target_pk, record.target_sk);
+    return get_entity(store, target, true).await.map(Recorded::Entity);
+}
+
+/// Runs the transaction that creates an entity together with the put of its idempotency record.
+///
+/// Returns the entity created earlier with the same key instead, if any, and `None`
+/// if the entity has been created now. The record is the last write of the transaction,
+/// so the reasons of the other writes keep their indexes for the callers.
+pub(crate) async fn create_once<T: DeserializeOwned>(
+    store: &dyn ContentStore,
+    create: IdempotentCreate,
+    mut writes: Vec<TransactWriteItem>,
+) -> Result<Option<T>> {
+    let expired_at = match replay(store, &create).await? {
+        Recorded::Entity(original) => return Ok(Some(original)),
+        Recorded::Free(expired_at) => expired_at,
+    };
+
+    let record = IdempotencyRecord::new(
+        &create.author_id,
+        &create.key,
+        create.target_type.clone(),
+        create.target_id.clone(),
+        &create.target.pk,
+        &create.target.sk,
+        Utc::now(),
+    ).with_request_digest(&create.request_digest);
+    let item = serde_dynamo::to_item(record)
+        .map_err(Error::InvalidInputData)?;
+
+    let mut put = PutItemInput::new(item);
+    put.condition = Some(match expired_at {
+        Some(expires_at) => Condition::Equals(EXPIRES_AT_ATTR.to_string(), AttributeValue::N(expires_at.to_string())),
+        None => Condition::AttributeNotExists(Index::Primary.pk_attr().to_string()),
+    });
+
+    let record_index = writes.len();
+    writes.push(TransactWriteItem::Put(put));
+
+    return match store.transact_write_items(writes).await {
+        Ok(()) => Ok(None),
+        // a concurrent request with the same key has won the race
+        Err(Error::TransactionCancelled(reasons, _)) if condition_failed(&reasons, record_index) => {
+            match replay(store, &create).await? {
+                Recorded::Entity(original) => Ok(Some(original)),
+                Recorded::Free(_) => Err(Error::Conflict(format!("the idempotency key `{}` is in use", create.key))),
+            }
+        }
+        Err(e) => Err(e),
+    };
+}
@@ -0,0 +1,69 @@
+use std::collections::HashMap;
+
+use serde::de::DeserializeOwned;
+use serde_dynamo;
+
+use super::result::{Error, Result};
+use super::tombstone::blank_deleted;
+use super::store::{ContentStore, Index, Item, Key, GetItemInput, BatchGetItemsInput};
+
+/// Loads the item with the key, fails with `Error::NotFound` if there is none.
+///
+/// The content of a deleted entity is left blank, like in every read.
+pub(crate) async fn get_entity<T: DeserializeOwned>(
+    store: &dyn ContentStore,
+    key: Key,
+    consistent_read: bool,
+) -> Result<T> {
+    let mut input = GetItemInput::new(key.clone());
+    input.consistent_read = consistent_read;
+
+    let mut item = store.get_item(input).await?
+        .ok_or(Error::NotFound(key.pk))?;
+    blank_deleted(&mut item);
+
+    return serde_dynamo::from_item(item)
+        .map_err(Error::InvalidOutputData);
+}
+
+/// Loads the items with the keys, in the order of the keys,
+/// fails with `Error::NotFound` if any of them is missing.
+pub(crate) async fn batch_get_entities<T: DeserializeOwned>(
+    store: &dyn ContentStore,
+    keys: Vec<Key>,
+    consistent_read: bool,
+) -> Result<Vec<T>> {
+    let mut input = BatchGetItemsInput::new(keys.clone());
+    input.consistent_read = consistent_read;
+
+    let found: HashMap<Key, Item> = store.batch_get_items(input).await?
+        .into_iter()
+        .filter_map(|item| item_key(&item).map(|k| (k, item)))
+        .collect();
+
+    let missing: Vec<&str> = keys.iter()
+        .filter(|k| !found.contains_key(k))
+        .map(|k| k.pk.as_str())
+        .collect();
+    if !missing.is_empty() {
+        return Err(Error::NotFound(missing.join(", ")));
+    }
+
+    let items: Vec<Item> = keys.iter()
+        .map(|k| {
+            let mut item = found[k].clone();
+            blank_deleted(&mut item);
+            item
+        })
+        .collect();
+
+    return serde_dynamo::from_items(items)
+        .map_err(Error::InvalidOutputData);
+}
+
+pub(crate) fn item_key(item: &Item) -> Option<Key> {
+    let pk = item.get(Index::Primary.pk_attr())?.as_s().ok()?;
+    let sk = item.get(Index::Primary.sk_attr())?.as_s().ok()?;
+
+    return Some(Key::new(pk, sk));
+}
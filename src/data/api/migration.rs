@@ -0,0 +1,236 @@
+use serde_dynamo;
+
+use crate::data::model::entity::Entity;
+use crate::data::model::reply::Reply;
+use crate::data::model::{comment as comm_model, reply as reply_model};
+
+use super::result::{Error, Result};
+use super::cursor::{Cursor, Direction};
+use super::decode::{decode_entity, DecodeError};
+use super::store::{ContentStore, Index, Item, Key, Condition, UpdateAction, GetItemInput, ScanInput, UpdateItemInput};
+
+/// The page size of the migration scans.
+const MIGRATION_PAGE_LIMIT: i32 = 100;
+
+/// The indexes whose keys are rewritten by the migration, the primary keys never change.
+const MIGRATED_INDEXES: [Index; 4] = [Index::Gsi1, Index::Gsi2, Index::Gsi3, Index::Gsi4];
+
+/// The materialised path of the comment trees, written with the `GSI3` keys.
+const PATH_ATTR: &str = "path";
+
+/// The scope of the migration cursors, there is one scan of the whole table.
+const MIGRATION_SCOPE: &str = "migration";
+
+#[derive(Clone, Debug)]
+pub struct MigrateKeysInput {
+    pub limit: Option<i32>,
+    pub start_cursor: Option<Cursor>,
+}
+
+impl MigrateKeysInput {
+    pub fn new() -> Self {
+        Self {
+            limit: None,
+            start_cursor: None,
+        }
+    }
+}
+
+impl Default for MigrateKeysInput {
+    fn default() -> Self {
+        return MigrateKeysInput::new();
+    }
+}
+
+#[derive(Clone, Default, Debug)]
+pub struct MigrateKeysOutput {
+    pub n_scanned: usize,
+    /// The number of items whose index keys have been rewritten.
+    pub n_migrated: usize,
+    /// The items that could not be decoded, they are left as they are.
+    pub invalid_items: Vec<DecodeError>,
+    pub next_cursor: Option<Cursor>,
+}
+
+
+fn entity_item(entity: Entity) -> Result<Item> {
+    let item = match entity {
+        Entity::Submission(subm) => serde_dynamo::to_item(subm),
+        Entity::Comment(comm) => serde_dynamo::to_item(comm),
+        Entity::Reply(reply) => serde_dynamo::to_item(reply),
+    };
+
+    return item.map_err(Error::InvalidInputData);
+}
+
+fn item_key(item: &Item) -> Option<Key> {
+    let attr = |name: &str| item.get(name).and_then(|v| v.as_s().ok()).cloned();
+
+    return attr(Index::Primary.pk_attr())
+        .zip(attr(Index::Primary.sk_attr()))
+        .map(|(pk, sk)| Key::new(pk, sk));
+}
+
+/// The update that moves the index keys of `old` to the ones of `new`, if they differ.
+///
+/// It only applies if the stored keys are still the old ones, an item
+/// rewritten in the meantime has got the new key format already.
+fn migration_update(old: &Item, new: &Item) -> Option<UpdateItemInput> {
+    let mut actions = vec![];
+    let mut conditions = vec![];
+    let index_attrs = MIGRATED_INDEXES.iter().flat_map(|index| [index.pk_attr(), index.sk_attr()]);
+    for attr in index_attrs.chain([PATH_ATTR]) {
+        let (old_value, new_value) = (old.get(attr), new.get(attr));
+        if old_value == new_value {
+            continue;
+        }
+
+        match new_value {
+            Some(v) => actions.push(UpdateAction::Set(attr.to_string(), v.clone())),
+            None => actions.push(UpdateAction::Remove(attr.to_string())),
+        }
+        match old_value {
+            Some(v) => conditions.push(Condition::Equals(attr.to_string(), v.clone())),
+            None => conditions.push(Condition::AttributeNotExists(attr.to_string())),
+        }
+    }
+
+    if actions.is_empty() {
+        return None;
+    }
+
+    let mut update = UpdateItemInput::new(item_key(old)?, actions);
+    update.condition = Some(Condition::And(conditions));
+
+    return Some(update);
+}
+
+/// Rewrites the index keys of the items written with an older key format,
+/// e.g. the `{score:010}` sort keys, with the ones of [`key_codec`](crate::data::model::key_codec),
+/// places the comments and replies written before the threads in their trees,
+/// indexes the submissions of the topics by creation time for the re-ranking,
+/// and the tombstones by deletion time for the purge.
+///
+/// Each page is independent, so a migration can be stopped and resumed from its cursor,
+/// and running it again on migrated items writes nothing.
+#[derive(Debug)]
+pub struct Client<'c> {
+    store: &'c dyn ContentStore,
+}
+
+impl<'c> Client<'c> {
+    pub fn new(store: &'c dyn ContentStore) -> Self {
+        return Self {
+            store,
+        };
+    }
+
+    /// Migrates one page of the table.
+    pub async fn migrate_keys(&self, input: MigrateKeysInput) -> Result<MigrateKeysOutput> {
+        let mut scan = ScanInput::new();
+        scan.limit = Some(input.limit.unwrap_or(MIGRATION_PAGE_LIMIT));
+        if let Some(cur) = input.start_cursor {
+            scan.exclusive_start_key = Some(cur.into_key(MIGRATION_SCOPE)?);
+        }
+
+        let results = self.store.scan(scan).await?;
+
+        let mut output = MigrateKeysOutput {
+            n_scanned: results.items.len(),
+            ..MigrateKeysOutput::default()
+        };
+        for item in results.items {
+            let mut entity = match decode_entity(item.clone()) {
+                Ok(entity) => entity,
+                // votes, idempotency records and revisions have no index keys
+                Err(DecodeError::NotContent(_, _)) => continue,
+                Err(e) => {
+                    output.invalid_items.push(e);
+                    continue;
+                }
+            };
+
+            // a reply written before the threads is placed under its parent
+            if let Entity::Reply(reply) = &mut entity {
+                if reply.path.is_empty() {
+                    let Some(parent_path) = self.parent_path(reply).await? else { continue; };
+                    reply.place_under(&parent_path);
+                }
+            }
+
+            entity.rebuild_keys();
+            let Some(update) = migration_update(&item, &entity_item(entity)?) else { continue; };
+
+            match self.store.update_item(update).await {
+                Ok(_) => output.n_migrated += 1,
+                Err(Error::ConditionFailed(_)) => continue,
+                Err(e) => return Err(e),
+            }
+        }
+
+        if let Some(lk) = results.last_evaluated_key {
+            output.next_cursor = Some(Cursor::new(lk, MIGRATION_SCOPE, Direction::Next)?);
+        }
+
+        Ok(output)
+    }
+
+    /// The path of the parent of a reply, `None` if the parent is gone or has no path yet.
+    async fn parent_path(&self, reply: &Reply) -> Result<Option<String>> {
+        let parent_key = match &reply.parent_id {
+            Some(parent_id) => {
+                let pk = reply_model::PrimaryKey::new(parent_id);
+                Key::new(pk.pk, pk.sk)
+            }
+            None => {
+                let pk = comm_model::PrimaryKey::new(&reply.comment_id);
+                Key::new(pk.pk, pk.sk)
+            }
+        };
+        let Some(item) = self.store.get_item(GetItemInput::new(parent_key)).await? else {
+            return Ok(None);
+        };
+
+        let path = match decode_entity(item) {
+            // the path of a comment derives from its own fields
+            Ok(Entity::Comment(mut comm)) => {
+                comm.rebuild_keys();
+                comm.path
+            }
+            Ok(Entity::Reply(parent)) => parent.path,
+            _ => String::new(),
+        };
+
+        return Ok(Some(path).filter(|p| !p.is_empty()));
+    }
+
+    /// Migrates the whole table, page by page.
+    ///
+    /// # Example:
+    ///
+    /// ```no_run
+    /// use valnk::data::api::migration::*;
+    ///
+    /// async fn migrate(cli: &Client<'_>) {
+    ///     let output = cli.migrate_all_keys().await.unwrap();
+    ///     println!("{} of {} items migrated", output.n_migrated, output.n_scanned);
+    /// }
+    /// ```
+    pub async fn migrate_all_keys(&self) -> Result<MigrateKeysOutput> {
+        let mut total = MigrateKeysOutput::default();
+        let mut input = MigrateKeysInput::new();
+        loop {
+            let output = self.migrate_keys(input.clone()).await?;
+            total.n_scanned += output.n_scanned;
+            total.n_migrated += output.n_migrated;
+            total.invalid_items.extend(output.invalid_items);
+
+            match output.next_cursor {
+                Some(cursor) => input.start_cursor = Some(cursor),
+                None => break,
+            }
+        }
+
+        Ok(total)
+    }
+}
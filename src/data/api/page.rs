@@ -0,0 +1,184 @@
+use std::future::Future;
+
+use futures::stream::{self, BoxStream, StreamExt};
+use serde::de::DeserializeOwned;
+use serde_dynamo;
+
+use super::result::{Error, Result};
+use super::cursor::{Cursor, Direction, query_scope};
+use super::tombstone::blank_deleted;
+use super::store::{ContentStore, Item, QueryInput};
+
+/// The page size of the list APIs when no `limit` is given.
+pub const DEFAULT_LIMIT: i32 = 30;
+
+/// The largest page size of the list APIs, a larger `limit` is lowered to it.
+pub const MAX_LIMIT: i32 = 100;
+
+/// The page sizes of the list APIs.
+///
+/// # Examples
+///
+/// ```
+/// use valnk::data::api::page::PageLimits;
+///
+/// let limits = PageLimits::new(20, 50);
+///
+/// assert_eq!(limits.resolve(None).unwrap(), 20);
+/// assert_eq!(limits.resolve(Some(10)).unwrap(), 10);
+/// assert_eq!(limits.resolve(Some(500)).unwrap(), 50);
+/// assert!(limits.resolve(Some(0)).is_err());
+/// ```
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct PageLimits {
+    pub default_limit: i32,
+    pub max_limit: i32,
+}
+
+impl PageLimits {
+    pub fn new(default_limit: i32, max_limit: i32) -> Self {
+        return Self {
+            default_limit: default_limit.min(max_limit),
+            max_limit,
+        };
+    }
+
+    /// The page size of a list with the `limit` of its input,
+    /// fails with `Error::BadRequest` if it is not positive.
+    pub fn resolve(&self, limit: Option<i32>) -> Result<i32> {
+        let limit = limit.unwrap_or(self.default_limit);
+        if limit < 1 {
+            return Err(Error::BadRequest(format!("invalid limit: {limit}")));
+        }
+
+        return Ok(limit.min(self.max_limit));
+    }
+}
+
+impl Default for PageLimits {
+    fn default() -> Self {
+        return PageLimits::new(DEFAULT_LIMIT, MAX_LIMIT);
+    }
+}
+
+/// The items of a listing, page after page, see [`paginate`].
+pub type ItemStream<'a, T> = BoxStream<'a, Result<T>>;
+
+/// A page of items of a list query.
+#[derive(Clone, Debug)]
+pub(crate) struct Page<T> {
+    pub items: Vec<T>,
+    pub next_cursor: Option<Cursor>,
+    pub prev_cursor: Option<Cursor>,
+}
+
+/// Runs `query` from `start_cursor` and decodes the items of the page, the deleted ones blank,
+/// fails with `Error::InvalidCursor` if the cursor comes from another query.
+pub(crate) async fn query_page<T: DeserializeOwned>(
+    store: &dyn ContentStore,
+    query: QueryInput,
+    start_cursor: Option<Cursor>,
+) -> Result<Page<T>> {
+    let mut page = query_items(store, query, start_cursor).await?;
+    page.items.iter_mut().for_each(blank_deleted);
+
+    let items: Vec<T> = serde_dynamo::from_items(page.items)
+        .map_err(Error::InvalidOutputData)?;
+
+    Ok(Page {
+        items,
+        next_cursor: page.next_cursor,
+        prev_cursor: page.prev_cursor,
+    })
+}
+
+/// Runs `query` from `start_cursor`, the items of the page are left undecoded.
+///
+/// A `Prev` cursor runs the query the other way round from its position, and the items
+/// are reversed back into the order of the list, the first page may then be shorter.
+pub(crate) async fn query_items(
+    store: &dyn ContentStore,
+    mut query: QueryInput,
+    start_cursor: Option<Cursor>,
+) -> Result<Page<Item>> {
+    let scope = query_scope(&query);
+    let index = query.index;
+
+    let mut direction = Direction::Next;
+    if let Some(cur) = start_cursor {
+        direction = cur.direction();
+        let key = cur.into_key(&scope)?;
+        if !key.is_empty() {
+            query.exclusive_start_key = Some(key);
+        }
+    }
+    if direction == Direction::Prev {
+        query.scan_index_forward = !query.scan_index_forward;
+    }
+    // there is nothing behind a page queried from the end of the list
+    let resumed = query.exclusive_start_key.is_some();
+
+    let results = store.query(query).await?;
+
+    let mut ahead = None;
+    if let Some(lk) = results.last_evaluated_key {
+        ahead = Some(Cursor::new(lk, &scope, direction)?);
+    }
+    let mut behind = None;
+    if resumed {
+        // with no items, everything left is behind the position
+        let key = results.items.first()
+            .map(|it| index.start_key(it))
+            .unwrap_or_default();
+        behind = Some(Cursor::new(key, &scope, direction.reversed())?);
+    }
+
+    let mut items = results.items;
+    let (next_cursor, prev_cursor) = match direction {
+        Direction::Next => (ahead, behind),
+        Direction::Prev => {
+            items.reverse();
+            (behind, ahead)
+        }
+    };
+
+    Ok(Page {
+        items,
+        next_cursor,
+        prev_cursor,
+    })
+}
+
+/// Streams the items of the pages returned by `fetch`, following their next cursors
+/// from `start_cursor` until the last page, or until `max_items` items.
+///
+/// A page that fails is yielded as an error and ends the stream,
+/// the items of the pages before it have been yielded already.
+pub(crate) fn paginate<'a, T, F, Fut>(
+    start_cursor: Option<Cursor>,
+    max_items: Option<usize>,
+    mut fetch: F,
+) -> ItemStream<'a, T>
+where
+    T: Send + 'a,
+    F: FnMut(Option<Cursor>) -> Fut + Send + 'a,
+    Fut: Future<Output = Result<(Vec<T>, Option<Cursor>)>> + Send + 'a,
+{
+    // `None` once the last page has been fetched
+    let pages = stream::unfold(Some(start_cursor), move |cursor| {
+        let page = cursor.map(&mut fetch);
+        async move {
+            return match page?.await {
+                Ok((items, next_cursor)) => Some((Ok(items), next_cursor.map(Some))),
+                Err(e) => Some((Err(e), None)),
+            };
+        }
+    });
+
+    let items = pages.flat_map(|page| match page {
+        Ok(items) => stream::iter(items.into_iter().map(Ok)).left_stream(),
+        Err(e) => stream::once(async { Err(e) }).right_stream(),
+    });
+
+    return items.take(max_items.unwrap_or(usize::MAX)).boxed();
+}
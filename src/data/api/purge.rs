@@ -0,0 +1,369 @@
+use std::time::Duration as StdDuration;
+
+use aws_sdk_dynamodb::model::AttributeValue;
+use chrono::{DateTime, Duration, Utc};
+
+use crate::data::model::entity::Entity;
+use crate::data::model::revision;
+use crate::data::model::tombstone::{DeletedIndexKey, TargetIndexKey};
+use crate::data::model::{submission as subm_model, comment as comm_model, reply as reply_model};
+
+use super::result::{Error, Result};
+use super::cursor::{Cursor, Direction};
+use super::decode::decode_entity;
+use super::edit::time_value;
+use super::lookup::item_key;
+use super::tombstone::{DELETED_AT_ATTR, PURGED_AT_ATTR, CONTENT_ATTRS};
+use super::store::{
+    ContentStore,
+    Index,
+    Item,
+    Key,
+    SkCondition,
+    Condition,
+    UpdateAction,
+    TransactWriteItem,
+    DeleteItemInput,
+    GetItemInput,
+    QueryInput,
+    UpdateItemInput,
+    condition_failed,
+};
+
+/// The page size of the queries of the tombstones, of the revisions and of the references.
+const PURGE_PAGE_LIMIT: i32 = 100;
+
+/// How long a deleted entity can be restored before it is purged.
+const DEFAULT_RETENTION_DAYS: i64 = 30;
+
+/// The scope of the purge cursors, there is one query of the whole tombstone index.
+const PURGE_SCOPE: &str = "purge";
+
+const N_COMMENTS_ATTR: &str = "n_comments";
+const N_REPLIES_ATTR: &str = "n_replies";
+
+#[derive(Clone, Debug)]
+pub struct PurgeDeletedInput {
+    /// Only purges the entities deleted more than `retention` ago.
+    pub retention: Option<Duration>,
+    /// The time the retention is counted from, `Utc::now()` by default.
+    pub now: Option<DateTime<Utc>>,
+    pub limit: Option<i32>,
+    pub start_cursor: Option<Cursor>,
+}
+
+impl PurgeDeletedInput {
+    pub fn new() -> Self {
+        Self {
+            retention: None,
+            now: None,
+            limit: None,
+            start_cursor: None,
+        }
+    }
+}
+
+impl Default for PurgeDeletedInput {
+    fn default() -> Self {
+        return PurgeDeletedInput::new();
+    }
+}
+
+#[derive(Clone, Default, Debug)]
+pub struct PurgeDeletedOutput {
+    /// The number of tombstones read.
+    pub n_scanned: usize,
+    /// The number of tombstones whose content has been erased, they still have children.
+    pub n_erased: usize,
+    /// The number of tombstones that have been removed from the table.
+    pub n_removed: usize,
+    /// The number of revisions removed with them.
+    pub n_revisions: usize,
+    /// The number of votes and idempotency records removed with them.
+    pub n_references: usize,
+    pub next_cursor: Option<Cursor>,
+}
+
+/// Whether other entities hang off the entity in its thread, by its counters.
+fn has_children(entity: &Entity) -> bool {
+    return match entity {
+        Entity::Submission(subm) => subm.n_comments > 0,
+        Entity::Comment(comm) => comm.n_replies > 0,
+        Entity::Reply(reply) => reply.n_replies > 0,
+    };
+}
+
+/// The updates that take a removed comment or reply off the counters of its parents.
+///
+/// They only apply to the parents that still exist, the missing ones are skipped by the purge.
+fn parent_updates(entity: &Entity) -> Vec<UpdateItemInput> {
+    let counter = |key: Key, attr: &str| {
+        let mut update = UpdateItemInput::new(key, vec![UpdateAction::Add(attr.to_string(), -1)]);
+        update.condition = Some(Condition::AttributeExists(Index::Primary.pk_attr().to_string()));
+        update
+    };
+    let subm_key = |id| {
+        let pk = subm_model::PrimaryKey::new(id);
+        Key::new(pk.pk, pk.sk)
+    };
+
+    return match entity {
+        Entity::Submission(_) => vec![],
+        Entity::Comment(comm) => vec![counter(subm_key(&comm.submission_id), N_COMMENTS_ATTR)],
+        Entity::Reply(reply) => {
+            let parent_key = match &reply.parent_id {
+                Some(parent_id) => {
+                    let pk = reply_model::PrimaryKey::new(parent_id);
+                    Key::new(pk.pk, pk.sk)
+                }
+                None => {
+                    let pk = comm_model::PrimaryKey::new(&reply.comment_id);
+                    Key::new(pk.pk, pk.sk)
+                }
+            };
+            vec![
+                counter(parent_key, N_REPLIES_ATTR),
+                counter(subm_key(&reply.submission_id), N_COMMENTS_ATTR),
+            ]
+        }
+    };
+}
+
+/// Purges the entities deleted before their retention period, for good.
+///
+/// The tombstones are read from the sparse `GSI4` index, by deletion time, so
+/// the purge never reads the live entities.
+///
+/// The revisions of a purged entity, that hold its past content, are removed. The entity
+/// itself is removed too, with its votes and idempotency records, unless its thread
+/// still has comments or replies under it: its content is then erased and it stays
+/// as a tombstone that cannot be restored. It is moved forward in the index to
+/// its erasure time, so it is only read again one retention period later, until
+/// a purge finds its last child removed. Each page is independent, so a purge
+/// can be stopped and resumed from its cursor.
+#[derive(Debug)]
+pub struct Client<'c> {
+    store: &'c dyn ContentStore,
+}
+
+impl<'c> Client<'c> {
+    pub fn new(store: &'c dyn ContentStore) -> Self {
+        return Self {
+            store,
+        };
+    }
+
+    /// Purges one page of the expired tombstones, the oldest first.
+    pub async fn purge_deleted(&self, input: PurgeDeletedInput) -> Result<PurgeDeletedOutput> {
+        let now = input.now.unwrap_or_else(Utc::now);
+        let deleted_before = now - input.retention.unwrap_or_else(|| Duration::days(DEFAULT_RETENTION_DAYS));
+
+        let mut query = QueryInput::new(Index::Gsi4, DeletedIndexKey::pk());
+        query.sk = Some(SkCondition::Lt(DeletedIndexKey::sk_end(&deleted_before)));
+        query.limit = Some(input.limit.unwrap_or(PURGE_PAGE_LIMIT));
+        if let Some(cur) = input.start_cursor {
+            query.exclusive_start_key = Some(cur.into_key(PURGE_SCOPE)?);
+        }
+
+        let results = self.store.query(query).await?;
+
+        let mut output = PurgeDeletedOutput {
+            n_scanned: results.items.len(),
+            ..PurgeDeletedOutput::default()
+        };
+        for item in results.items {
+            let Ok(entity) = decode_entity(item.clone()) else { continue; };
+            let Some(deleted_at) = entity.deleted_at() else { continue; };
+            if *entity.purged_at().unwrap_or(deleted_at) > deleted_before {
+                continue;
+            }
+            let Some(key) = item_key(&item) else { continue; };
+
+            // a tombstone restored in the meantime is left alone
+            let still_deleted = Condition::Equals(DELETED_AT_ATTR.to_string(), item[DELETED_AT_ATTR].clone());
+            // erased again if it was erased already, it waits for its last child to be removed
+            let erased = has_children(&entity);
+            let purged = match erased {
+                true => self.erase(key.clone(), &item, still_deleted, now).await,
+                false => self.remove(key.clone(), &entity, still_deleted).await,
+            };
+            match purged {
+                Ok(()) if erased => output.n_erased += 1,
+                Ok(()) => output.n_removed += 1,
+                Err(Error::ConditionFailed(_)) => continue,
+                Err(Error::TransactionCancelled(reasons, _)) if (0..reasons.len()).any(|i| condition_failed(&reasons, i)) => continue,
+                Err(e) => return Err(e),
+            }
+
+            // only once the tombstone cannot be restored anymore
+            output.n_revisions += self.remove_revisions(&key).await?;
+            if !erased {
+                output.n_references += self.remove_references(&key).await?;
+            }
+        }
+
+        if let Some(lk) = results.last_evaluated_key {
+            output.next_cursor = Some(Cursor::new(lk, PURGE_SCOPE, Direction::Next)?);
+        }
+
+        Ok(output)
+    }
+
+    /// Purges all the expired tombstones, page by page.
+    ///
+    /// # Example:
+    ///
+    /// ```no_run
+    /// use chrono::Duration;
+    /// use valnk::data::api::purge::*;
+    ///
+    /// async fn purge(cli: &Client<'_>) {
+    ///     let output = cli.purge_all_deleted(Some(Duration::days(7))).await.unwrap();
+    ///     println!("{} tombstones removed, {} erased", output.n_removed, output.n_erased);
+    /// }
+    /// ```
+    pub async fn purge_all_deleted(&self, retention: Option<Duration>) -> Result<PurgeDeletedOutput> {
+        let mut total = PurgeDeletedOutput::default();
+        let mut input = PurgeDeletedInput::new();
+        input.retention = retention;
+        input.now = Some(Utc::now());
+        loop {
+            let output = self.purge_deleted(input.clone()).await?;
+            total.n_scanned += output.n_scanned;
+            total.n_erased += output.n_erased;
+            total.n_removed += output.n_removed;
+            total.n_revisions += output.n_revisions;
+            total.n_references += output.n_references;
+
+            match output.next_cursor {
+                Some(cursor) => input.start_cursor = Some(cursor),
+                None => break,
+            }
+        }
+
+        Ok(total)
+    }
+
+    /// Removes the revisions in the partition of the entity, returns how many.
+    async fn remove_revisions(&self, key: &Key) -> Result<usize> {
+        let mut n_removed = 0;
+        loop {
+            let mut query = QueryInput::new(Index::Primary, &key.pk);
+            query.sk = Some(SkCondition::BeginsWith(revision::PrimaryKey::sk_prefix()));
+            query.limit = Some(PURGE_PAGE_LIMIT);
+
+            // the removed revisions are gone from the next query
+            let results = self.store.query(query).await?;
+            if results.items.is_empty() {
+                return Ok(n_removed);
+            }
+
+            for rev in results.items.iter().filter_map(item_key) {
+                self.store.delete_item(DeleteItemInput::new(rev)).await?;
+                n_removed += 1;
+            }
+        }
+    }
+
+    /// Removes the votes and the idempotency records of the entity, returns how many.
+    async fn remove_references(&self, key: &Key) -> Result<usize> {
+        let mut n_removed = 0;
+        let mut query = QueryInput::new(Index::Gsi1, TargetIndexKey::pk(&key.pk));
+        query.limit = Some(PURGE_PAGE_LIMIT);
+        loop {
+            // the index may still hold the removed items for a while, so it is paged through
+            let results = self.store.query(query.clone()).await?;
+            for reference in results.items.iter().filter_map(item_key) {
+                self.store.delete_item(DeleteItemInput::new(reference)).await?;
+                n_removed += 1;
+            }
+
+            match results.last_evaluated_key {
+                Some(lk) => query.exclusive_start_key = Some(lk),
+                None => return Ok(n_removed),
+            }
+        }
+    }
+
+    /// Erases the content of the tombstone and moves it forward in the index to `now`.
+    async fn erase(&self, key: Key, item: &Item, condition: Condition, now: DateTime<Utc>) -> Result<()> {
+        let mut actions: Vec<UpdateAction> = CONTENT_ATTRS.iter()
+            .filter(|attr| item.contains_key(**attr))
+            .map(|attr| UpdateAction::Set(attr.to_string(), AttributeValue::S(String::new())))
+            .collect();
+        actions.push(UpdateAction::Set(PURGED_AT_ATTR.to_string(), time_value(&now)?));
+        let deleted_sk = DeletedIndexKey::sk(&now, &key.pk);
+        actions.push(UpdateAction::Set(Index::Gsi4.sk_attr().to_string(), AttributeValue::S(deleted_sk)));
+
+        let mut update = UpdateItemInput::new(key, actions);
+        update.condition = Some(condition);
+
+        return self.store.update_item(update).await.map(|_| ());
+    }
+
+    /// Removes the tombstone and takes it off the counters of its parents, at once.
+    async fn remove(&self, key: Key, entity: &Entity, condition: Condition) -> Result<()> {
+        let mut delete = DeleteItemInput::new(key);
+        delete.condition = Some(condition);
+
+        let mut writes = vec![TransactWriteItem::Delete(delete)];
+        for update in parent_updates(entity) {
+            // a missing parent has no counter to take the tombstone off
+            if self.store.get_item(GetItemInput::new(update.key.clone())).await?.is_none() {
+                continue;
+            }
+            writes.push(TransactWriteItem::Update(update));
+        }
+
+        return self.store.transact_write_items(writes).await;
+    }
+}
+
+
+/// A background job that purges the expired tombstones periodically.
+///
+/// # Example:
+///
+/// ```no_run
+/// use std::time::Duration;
+/// use valnk::data::api::purge::PurgeJob;
+/// use valnk::data::api::store::ContentStore;
+///
+/// async fn purge_daily(store: &dyn ContentStore) {
+///     let job = PurgeJob::new(Duration::from_secs(24 * 3600));
+///     job.run(store).await;
+/// }
+/// ```
+#[derive(Clone, Debug)]
+pub struct PurgeJob {
+    pub interval: StdDuration,
+    pub retention: Option<Duration>,
+}
+
+impl PurgeJob {
+    pub fn new(interval: StdDuration) -> Self {
+        return Self {
+            interval,
+            retention: None,
+        };
+    }
+
+    pub async fn run_once(&self, store: &dyn ContentStore) -> Result<PurgeDeletedOutput> {
+        return Client::new(store).purge_all_deleted(self.retention).await;
+    }
+
+    /// Purges the expired tombstones each `interval`, forever.
+    ///
+    /// The failures are logged with the [`log`] facade, e.g. by the logger of Rocket,
+    /// the purge starts over on the next run.
+    pub async fn run(&self, store: &dyn ContentStore) {
+        let mut ticker = tokio::time::interval(self.interval);
+        loop {
+            ticker.tick().await;
+
+            if let Err(e) = self.run_once(store).await {
+                log::error!("failed to purge the deleted items: {e}");
+            }
+        }
+    }
+}
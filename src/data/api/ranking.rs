@@ -0,0 +1,257 @@
+use std::time::Duration as StdDuration;
+
+use aws_sdk_dynamodb::model::AttributeValue;
+use chrono::{DateTime, Duration, Utc};
+
+use crate::data::model::ranking::HotRanking;
+use crate::data::model::submission::{Submission, PrimaryKey, RankingScore, TopicIndexKey, TopicTimeIndexKey};
+
+use super::result::{Error, Result};
+use super::page::query_page;
+use super::store::{
+    ContentStore,
+    Index,
+    Key,
+    SkCondition,
+    Condition,
+    UpdateAction,
+    QueryInput,
+    UpdateItemInput,
+};
+
+/// The page size of the reads of the recent submissions of a topic.
+const RERANK_PAGE_LIMIT: i32 = 100;
+
+/// Submissions older than this are no longer re-ranked, their score has decayed close to `0` by then.
+const DEFAULT_MAX_AGE_HOURS: i64 = 72;
+
+/// The score of the submissions older than the `max_age` of the re-ranking,
+/// below the one of any recent submission.
+pub const AGED_OUT_SCORE: RankingScore = 0;
+
+#[derive(Clone, Debug)]
+pub struct RerankTopicInput {
+    pub topic: String,
+    /// Only re-ranks the submissions created in the last `max_age`, the older ones get the [`AGED_OUT_SCORE`].
+    pub max_age: Option<Duration>,
+    /// The time the scores are computed at, `Utc::now()` by default.
+    pub now: Option<DateTime<Utc>>,
+}
+
+impl RerankTopicInput {
+    pub fn new(topic: impl Into<String>) -> Self {
+        Self {
+            topic: topic.into(),
+            max_age: None,
+            now: None,
+        }
+    }
+}
+
+#[derive(Clone, Default, Debug)]
+pub struct RerankTopicOutput {
+    /// The number of recent submissions of the topic.
+    pub n_ranked: usize,
+    /// The number of submissions whose score has changed.
+    pub n_updated: usize,
+    /// The number of submissions that have left the `max_age` and got the [`AGED_OUT_SCORE`].
+    pub n_aged_out: usize,
+}
+
+
+/// Rewrites the `ranking_score` and the `TopicIndexKey` of the submissions,
+/// so the topic listings follow the [`HotRanking`] of their votes, comments and age.
+#[derive(Debug)]
+pub struct Client<'c> {
+    store: &'c dyn ContentStore,
+    ranking: HotRanking,
+}
+
+impl<'c> Client<'c> {
+    pub fn new(store: &'c dyn ContentStore) -> Self {
+        return Self {
+            store,
+            ranking: HotRanking::default(),
+        };
+    }
+
+    pub fn with_ranking(store: &'c dyn ContentStore, ranking: HotRanking) -> Self {
+        return Self {
+            store,
+            ranking,
+        };
+    }
+
+    /// Re-ranks the recent submissions of a topic.
+    ///
+    /// They are read by creation time, from the `TopicTimeIndexKey`, so a run
+    /// only costs the submissions of the last `max_age`, however large the topic.
+    ///
+    /// The submissions older than `max_age` drop to the [`AGED_OUT_SCORE`], so a score
+    /// frozen at their last re-ranking does not keep them above the recent ones. They are
+    /// read by creation time too, back from `max_age` ago to the first one at the floor,
+    /// so each one is read about once after it ages out.
+    ///
+    /// # Example:
+    ///
+    /// ```no_run
+    /// use valnk::data::api::ranking::*;
+    ///
+    /// async fn rerank_news(cli: &Client<'_>) {
+    ///     let output = cli.rerank_topic(RerankTopicInput::new("news")).await.unwrap();
+    /// }
+    /// ```
+    pub async fn rerank_topic(&self, input: RerankTopicInput) -> Result<RerankTopicOutput> {
+        let now = input.now.unwrap_or_else(Utc::now);
+        let since = now - input.max_age.unwrap_or_else(|| Duration::hours(DEFAULT_MAX_AGE_HOURS));
+
+        // the scores move the sort keys of the topic index, not the ones of the
+        // creation times, so the pages are re-ranked as they are read
+        let mut output = RerankTopicOutput::default();
+        let mut cursor = None;
+        loop {
+            let mut query = QueryInput::new(Index::Gsi3, TopicTimeIndexKey::pk(&input.topic));
+            query.sk = Some(SkCondition::Ge(TopicTimeIndexKey::sk_start(&since)));
+            query.limit = Some(RERANK_PAGE_LIMIT);
+
+            let page = query_page(self.store, query, cursor).await?;
+            for subm in page.items {
+                output.n_ranked += 1;
+                if self.rerank(&subm, &now).await? {
+                    output.n_updated += 1;
+                }
+            }
+
+            cursor = page.next_cursor;
+            if cursor.is_none() {
+                break;
+            }
+        }
+
+        // the submissions that have left the window, the newest first: the first one
+        // at the floor is where an earlier run got to, the older ones are there already
+        let mut aged_out = vec![];
+        let mut cursor = None;
+        'read: loop {
+            let mut query = QueryInput::new(Index::Gsi3, TopicTimeIndexKey::pk(&input.topic));
+            query.sk = Some(SkCondition::Lt(TopicTimeIndexKey::sk_start(&since)));
+            query.scan_index_forward = false;
+            query.limit = Some(RERANK_PAGE_LIMIT);
+
+            let page = query_page::<Submission>(self.store, query, cursor).await?;
+            for subm in page.items {
+                if subm.ranking_score <= AGED_OUT_SCORE {
+                    break 'read;
+                }
+                aged_out.push(subm);
+            }
+
+            cursor = page.next_cursor;
+            if cursor.is_none() {
+                break;
+            }
+        }
+
+        // the oldest first, so a run that fails leaves the newer ones for the next run
+        for subm in aged_out.iter().rev() {
+            if self.set_score(subm, AGED_OUT_SCORE).await? {
+                output.n_aged_out += 1;
+            }
+        }
+
+        Ok(output)
+    }
+
+    /// Writes the score of a submission at `now`, returns whether it has changed.
+    async fn rerank(&self, subm: &Submission, now: &DateTime<Utc>) -> Result<bool> {
+        return self.set_score(subm, self.ranking.score_submission(subm, now)).await;
+    }
+
+    /// Writes the score of a submission, returns whether it has changed.
+    async fn set_score(&self, subm: &Submission, score: RankingScore) -> Result<bool> {
+        if score == subm.ranking_score {
+            return Ok(false);
+        }
+
+        let pk = PrimaryKey::new(&subm.id);
+        let mut update = UpdateItemInput::new(Key::new(pk.pk, pk.sk), vec![
+            UpdateAction::Set("ranking_score".to_string(), AttributeValue::N(score.to_string())),
+            UpdateAction::Set(Index::Gsi1.sk_attr().to_string(), AttributeValue::S(TopicIndexKey::sk(&score, &subm.id))),
+        ]);
+        // a submission deleted in the meantime must not be created again
+        update.condition = Some(Condition::AttributeExists(Index::Primary.pk_attr().to_string()));
+
+        return match self.store.update_item(update).await {
+            Ok(_) => Ok(true),
+            Err(Error::ConditionFailed(_)) => Ok(false),
+            Err(e) => Err(e),
+        };
+    }
+}
+
+
+/// A background job that re-ranks the recent submissions of some topics periodically,
+/// and drops the older ones to the [`AGED_OUT_SCORE`].
+///
+/// # Example:
+///
+/// ```no_run
+/// use std::time::Duration;
+/// use valnk::data::api::ranking::RerankJob;
+/// use valnk::data::api::store::ContentStore;
+///
+/// async fn rerank_news(store: &dyn ContentStore) {
+///     let job = RerankJob::new(vec!["news".to_string()], Duration::from_secs(300));
+///     job.run(store).await;
+/// }
+/// ```
+#[derive(Clone, Debug)]
+pub struct RerankJob {
+    pub topics: Vec<String>,
+    pub interval: StdDuration,
+    pub max_age: Option<Duration>,
+    pub ranking: HotRanking,
+}
+
+impl RerankJob {
+    pub fn new(topics: Vec<String>, interval: StdDuration) -> Self {
+        return Self {
+            topics,
+            interval,
+            max_age: None,
+            ranking: HotRanking::default(),
+        };
+    }
+
+    /// Re-ranks every topic once, a failed topic does not stop the others.
+    pub async fn run_once(&self, store: &dyn ContentStore) -> Vec<(String, Result<RerankTopicOutput>)> {
+        let cli = Client::with_ranking(store, self.ranking.clone());
+
+        let mut results = vec![];
+        for topic in self.topics.iter() {
+            let mut input = RerankTopicInput::new(topic);
+            input.max_age = self.max_age;
+
+            results.push((topic.clone(), cli.rerank_topic(input).await));
+        }
+
+        return results;
+    }
+
+    /// Re-ranks every topic each `interval`, forever.
+    ///
+    /// The failures are logged with the [`log`] facade, e.g. by the logger of Rocket,
+    /// the topic is tried again on the next run.
+    pub async fn run(&self, store: &dyn ContentStore) {
+        let mut ticker = tokio::time::interval(self.interval);
+        loop {
+            ticker.tick().await;
+
+            for (topic, result) in self.run_once(store).await {
+                if let Err(e) = result {
+                    log::error!("failed to re-rank topic `{topic}`: {e}");
+                }
+            }
+        }
+    }
+}
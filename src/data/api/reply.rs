@@ -0,0 +1,553 @@
+use chrono::{DateTime, Utc};
+use serde_dynamo;
+
+use aws_sdk_dynamodb::model::AttributeValue;
+
+use crate::data::model::entity::EntityType;
+use crate::data::model::idempotency::request_digest;
+use crate::data::model::submission::{self as subm_model, SubmissionId};
+use crate::data::model::comment::{self as comm_model, Comment, CommentId};
+use crate::data::model::thread::{self, MAX_THREAD_DEPTH};
+use crate::data::model::reply::{
+    Reply,
+    ReplyId,
+    PrimaryKey,
+    SubmissionCommentIndexKey,
+};
+
+use super::result::{Error, Result};
+use super::cursor::Cursor;
+use super::page::{query_page, paginate, ItemStream, PageLimits};
+use super::lookup::{get_entity, batch_get_entities};
+use super::idempotency::{create_once, IdempotentCreate};
+use super::edit::{edit_entity, Edit};
+use super::tombstone::{soft_delete, undelete, DELETED_AT_ATTR};
+use super::store::{
+    ContentStore,
+    Index,
+    Key,
+    SkCondition,
+    Condition,
+    UpdateAction,
+    TransactWriteItem,
+    PutItemInput,
+    UpdateItemInput,
+    QueryInput,
+    condition_failed,
+};
+
+/// The counter of the replies of a comment, or of a reply.
+const N_REPLIES_ATTR: &str = "n_replies";
+/// The counter of the comments and replies of a submission.
+const N_COMMENTS_ATTR: &str = "n_comments";
+
+
+#[derive(Clone, Debug)]
+pub struct GetItemInput {
+    pub id: ReplyId,
+    pub consistent_read: Option<bool>,
+}
+
+impl GetItemInput {
+    pub fn new(id: ReplyId) -> Self {
+        Self {
+            id,
+            consistent_read: None,
+        }
+    }
+}
+
+/// An edit of the text of a reply.
+#[derive(Clone, Debug)]
+pub struct EditItemInput {
+    pub id: ReplyId,
+    /// The user who edits the reply, the author or a moderator.
+    pub editor_id: String,
+    /// The `updated_at` of the reply the edit is based on.
+    pub expected_updated_at: DateTime<Utc>,
+    pub text: String,
+}
+
+impl EditItemInput {
+    pub fn new(id: ReplyId, editor_id: impl Into<String>, expected_updated_at: DateTime<Utc>, text: impl Into<String>) -> Self {
+        Self {
+            id,
+            editor_id: editor_id.into(),
+            expected_updated_at,
+            text: text.into(),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct DeleteItemInput {
+    pub id: ReplyId,
+    /// The user who deletes the reply, the author or a moderator.
+    pub deleted_by: String,
+}
+
+impl DeleteItemInput {
+    pub fn new(id: ReplyId, deleted_by: impl Into<String>) -> Self {
+        Self {
+            id,
+            deleted_by: deleted_by.into(),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct UndeleteItemInput {
+    pub id: ReplyId,
+}
+
+impl UndeleteItemInput {
+    pub fn new(id: ReplyId) -> Self {
+        Self {
+            id,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct BatchGetItemsInput {
+    pub ids: Vec<ReplyId>,
+    pub consistent_read: Option<bool>,
+}
+
+impl BatchGetItemsInput {
+    pub fn new(ids: Vec<ReplyId>) -> Self {
+        Self {
+            ids,
+            consistent_read: None,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct ListRepliesByCommentInput {
+    pub submission_id: SubmissionId,
+    pub comment_id: CommentId,
+    pub limit: Option<i32>,
+    pub reverse: Option<bool>,
+    pub start_cursor: Option<Cursor>,
+}
+
+impl ListRepliesByCommentInput {
+    pub fn new(submission_id: SubmissionId, comment_id: CommentId) -> Self {
+        Self {
+            submission_id,
+            comment_id,
+            limit: None,
+            reverse: None,
+            start_cursor: None,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct ListRepliesBySubmissionInput {
+    pub submission_id: SubmissionId,
+    pub limit: Option<i32>,
+    pub reverse: Option<bool>,
+    pub start_cursor: Option<Cursor>,
+}
+
+impl ListRepliesBySubmissionInput {
+    pub fn new(submission_id: SubmissionId) -> Self {
+        Self {
+            submission_id,
+            limit: None,
+            reverse: None,
+            start_cursor: None,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct ListRepliesOutput {
+    pub items: Vec<Reply>,
+    pub next_cursor: Option<Cursor>,
+    /// The cursor of the page before this one, `None` on the first page.
+    pub prev_cursor: Option<Cursor>,
+}
+
+impl ListRepliesOutput {
+    pub fn new(items: Vec<Reply>) -> Self {
+        Self {
+            items,
+            next_cursor: None,
+            prev_cursor: None,
+        }
+    }
+}
+
+
+fn key_of(id: &ReplyId) -> Key {
+    let pk = PrimaryKey::new(id);
+    return Key::new(pk.pk, pk.sk);
+}
+
+/// The writes of a new reply: the reply, that never replaces an existing one,
+/// the `n_comments` of its submission and the `n_replies` of its parent, the comment
+/// or the parent reply, that must exist. The comment of a nested reply is only checked.
+fn create_writes(reply: &Reply) -> Result<Vec<TransactWriteItem>> {
+    let subm_pk = subm_model::PrimaryKey::new(&reply.submission_id);
+    let comm_pk = comm_model::PrimaryKey::new(&reply.comment_id);
+    let submission_id = AttributeValue::S(reply.submission_id.to_string());
+    let item = serde_dynamo::to_item(reply)
+        .map_err(Error::InvalidInputData)?;
+
+    let mut put = PutItemInput::new(item);
+    put.condition = Some(Condition::AttributeNotExists(Index::Primary.pk_attr().to_string()));
+
+    let comm_key = Key::new(&comm_pk.pk, comm_pk.sk);
+    let comm_condition = Condition::And(vec![
+        Condition::AttributeExists(Index::Primary.pk_attr().to_string()),
+        Condition::AttributeNotExists(DELETED_AT_ATTR.to_string()),
+        Condition::Equals("submission_id".to_string(), submission_id),
+    ]);
+    let comm_write = match &reply.parent_id {
+        Some(_) => TransactWriteItem::ConditionCheck(comm_key, comm_condition),
+        None => {
+            let mut comm_update = UpdateItemInput::new(comm_key, vec![UpdateAction::Add(N_REPLIES_ATTR.to_string(), 1)]);
+            comm_update.condition = Some(comm_condition);
+            TransactWriteItem::Update(comm_update)
+        }
+    };
+
+    let mut subm_update = UpdateItemInput::new(Key::new(&subm_pk.pk, subm_pk.sk), vec![
+        UpdateAction::Add(N_COMMENTS_ATTR.to_string(), 1),
+    ]);
+    subm_update.condition = Some(Condition::And(vec![
+        Condition::AttributeExists(Index::Primary.pk_attr().to_string()),
+        Condition::AttributeNotExists(DELETED_AT_ATTR.to_string()),
+    ]));
+
+    let mut writes = vec![
+        TransactWriteItem::Put(put),
+        comm_write,
+        TransactWriteItem::Update(subm_update),
+    ];
+
+    if let Some(parent_id) = &reply.parent_id {
+        let mut parent_update = UpdateItemInput::new(key_of(parent_id), vec![
+            UpdateAction::Add(N_REPLIES_ATTR.to_string(), 1),
+        ]);
+        parent_update.condition = Some(Condition::And(vec![
+            Condition::AttributeExists(Index::Primary.pk_attr().to_string()),
+            Condition::AttributeNotExists(DELETED_AT_ATTR.to_string()),
+            Condition::Equals("comment_id".to_string(), AttributeValue::S(reply.comment_id.to_string())),
+        ]));
+        writes.push(TransactWriteItem::Update(parent_update));
+    }
+
+    return Ok(writes);
+}
+
+fn create_error(err: Error, reply: &Reply) -> Error {
+    let subm_pk = subm_model::PrimaryKey::new(&reply.submission_id);
+    let comm_pk = comm_model::PrimaryKey::new(&reply.comment_id);
+
+    return match err {
+        Error::TransactionCancelled(reasons, _) if condition_failed(&reasons, 0) => {
+            Error::Conflict(format!("the reply `{}` already exists", reply.primary_key.pk))
+        }
+        Error::TransactionCancelled(reasons, _) if condition_failed(&reasons, 2) => {
+            Error::Conflict(format!("the submission `{}` does not exist or is deleted", subm_pk.pk))
+        }
+        Error::TransactionCancelled(reasons, _) if condition_failed(&reasons, 1) => {
+            Error::Conflict(format!("the comment `{}` does not exist in `{}` or is deleted", comm_pk.pk, subm_pk.pk))
+        }
+        Error::TransactionCancelled(reasons, _) if condition_failed(&reasons, 3) => {
+            Error::Conflict(format!("the parent reply of `{}` does not exist under `{}` or is deleted", reply.primary_key.pk, comm_pk.pk))
+        }
+        e => e,
+    };
+}
+
+fn missing_parent(err: Error, reply: &Reply) -> Error {
+    let comm_pk = comm_model::PrimaryKey::new(&reply.comment_id);
+
+    return match err {
+        Error::NotFound(pk) => Error::Conflict(format!("`{pk}` does not exist under `{}`", comm_pk.pk)),
+        e => e,
+    };
+}
+
+#[derive(Debug)]
+pub struct Client<'c> {
+    store: &'c dyn ContentStore,
+    page_limits: PageLimits,
+}
+
+impl<'c> Client<'c> {
+    pub fn new(store: &'c dyn ContentStore) -> Self {
+        return Self {
+            store,
+            page_limits: PageLimits::default(),
+        };
+    }
+
+    pub fn with_page_limits(mut self, page_limits: PageLimits) -> Self {
+        self.page_limits = page_limits;
+        self
+    }
+
+    /// Places a new reply in its tree: under its parent reply if it has one,
+    /// under its comment otherwise.
+    ///
+    /// Fails with `Error::Conflict` if the parent is not in the tree of the comment,
+    /// and with `Error::BadRequest` if the reply would be nested deeper than `MAX_THREAD_DEPTH`.
+    async fn place(&self, reply: &mut Reply) -> Result<()> {
+        let comm_pk = comm_model::PrimaryKey::new(&reply.comment_id);
+        let mut comm: Comment = get_entity(self.store, Key::new(comm_pk.pk, comm_pk.sk), true).await
+            .map_err(|e| missing_parent(e, reply))?;
+        if comm.submission_id != reply.submission_id {
+            return Err(Error::Conflict(format!("the comment `{}` does not exist in `{}`", comm.primary_key.pk, reply.submission_comment_key.pk)));
+        }
+
+        let parent_path = match &reply.parent_id {
+            Some(parent_id) => {
+                let parent: Reply = get_entity(self.store, key_of(parent_id), true).await
+                    .map_err(|e| missing_parent(e, reply))?;
+                if parent.comment_id != reply.comment_id || parent.path.is_empty() {
+                    return Err(Error::Conflict(format!("the reply `{}` is not in the tree of `{}`", parent.primary_key.pk, comm.primary_key.pk)));
+                }
+                parent.path
+            }
+            // a comment written before the threads derives its path from its own fields
+            None if comm.path.is_empty() => {
+                comm.rebuild_keys();
+                comm.path
+            }
+            None => comm.path,
+        };
+
+        if thread::depth(&parent_path) + 1 > MAX_THREAD_DEPTH {
+            return Err(Error::BadRequest(format!("a reply cannot be nested deeper than {MAX_THREAD_DEPTH}")));
+        }
+
+        reply.place_under(&parent_path);
+        return Ok(());
+    }
+
+    /// Creates a reply and bumps the `n_replies` of its parent, its parent reply if it has one
+    /// and its comment otherwise, and the `n_comments` of its submission in one transaction.
+    ///
+    /// Fails with `Error::Conflict` if the comment does not exist in the submission,
+    /// if the parent reply is not in the tree of the comment, or if a reply with the same
+    /// id exists, and with `Error::BadRequest` if the reply is nested too deep.
+    ///
+    /// # Example:
+    ///
+    /// ```no_run
+    /// use tokio;
+    /// use valnk::data::api::reply::*;
+    /// use valnk::data::api::store::{dynamodb_client, DynamoStore};
+    /// use valnk::data::model::submission::SubmissionId;
+    /// use valnk::data::model::comment::CommentId;
+    /// use valnk::data::model::reply as reply_model;
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let shared_config = aws_config::load_from_env().await;
+    ///     let aws_cli = dynamodb_client(&shared_config, None).unwrap();
+    ///     let store = DynamoStore::new(aws_cli, "valnk-content");
+    ///     let cli = Client::new(&store);
+    ///     let reply = reply_model::ReplyBuilder::new()
+    ///         .with_submission_id(SubmissionId::from("subm-id").unwrap())
+    ///         .with_comment_id(CommentId::from("comm-id").unwrap())
+    ///         .with_author_id("py0x")
+    ///         .with_text("hello create_item")
+    ///         .build()
+    ///         .unwrap();
+    ///
+    ///     cli.create_item(reply).await.unwrap();
+    /// }
+    /// ```
+    pub async fn create_item(&self, mut reply: Reply) -> Result<()> {
+        self.place(&mut reply).await?;
+        let writes = create_writes(&reply)?;
+
+        return self.store.transact_write_items(writes).await
+            .map_err(|e| create_error(e, &reply));
+    }
+
+    /// Creates a reply unless one has been created with the same `idempotency_key`
+    /// by its author, returns the reply created with the key, now or earlier.
+    pub async fn create_item_idempotent(&self, mut reply: Reply, idempotency_key: &str) -> Result<Reply> {
+        self.place(&mut reply).await?;
+        let create = IdempotentCreate {
+            author_id: reply.author_id.clone(),
+            key: idempotency_key.to_string(),
+            target_type: EntityType::Reply,
+            target_id: reply.id.clone(),
+            target: key_of(&reply.id),
+            request_digest: request_digest(&[
+                ("submission_id", reply.submission_id.as_ref()),
+                ("comment_id", reply.comment_id.as_ref()),
+                ("parent_id", reply.parent_id.as_ref().map_or("", |id| id.as_ref())),
+                ("text", &reply.text),
+            ]),
+        };
+
+        return match create_once(self.store, create, create_writes(&reply)?).await {
+            Ok(Some(original)) => Ok(original),
+            Ok(None) => Ok(reply),
+            Err(e) => Err(create_error(e, &reply)),
+        };
+    }
+
+    /// Edits the text of a reply and returns it edited, fails with `Error::Conflict`
+    /// if it has been edited since `input.expected_updated_at`, and with
+    /// `Error::NotFound` if it does not exist. The replaced text is kept in a `Revision`.
+    pub async fn edit_item(&self, input: EditItemInput) -> Result<Reply> {
+        let edit = Edit {
+            target_type: EntityType::Reply,
+            target_id: input.id.clone(),
+            target: key_of(&input.id),
+            editor_id: input.editor_id,
+            expected_updated_at: input.expected_updated_at,
+            fields: vec![("text", input.text)],
+            derived: vec![],
+        };
+
+        return edit_entity(self.store, edit).await;
+    }
+
+    /// Deletes a reply: it is kept, with its counters, as a tombstone whose content
+    /// is blank in the reads, so its thread keeps its shape. Deleting it again is a no-op,
+    /// fails with `Error::NotFound` if it does not exist.
+    pub async fn delete_item(&self, input: DeleteItemInput) -> Result<()> {
+        return soft_delete(self.store, key_of(&input.id), &input.deleted_by).await;
+    }
+
+    /// Restores a deleted reply, for the moderators, fails with `Error::NotFound`
+    /// if it does not exist and with `Error::Conflict` if it has been purged.
+    pub async fn undelete_item(&self, input: UndeleteItemInput) -> Result<()> {
+        return undelete(self.store, key_of(&input.id)).await;
+    }
+
+    /// Returns the reply with the id, fails with `Error::NotFound` if there is none.
+    ///
+    /// # Example:
+    ///
+    /// ```no_run
+    /// use tokio;
+    /// use valnk::data::api::reply::*;
+    /// use valnk::data::api::store::{dynamodb_client, DynamoStore};
+    /// use valnk::data::model::reply::ReplyId;
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let shared_config = aws_config::load_from_env().await;
+    ///     let aws_cli = dynamodb_client(&shared_config, None).unwrap();
+    ///     let store = DynamoStore::new(aws_cli, "valnk-content");
+    ///     let cli = Client::new(&store);
+    ///
+    ///     let mut input = GetItemInput::new(ReplyId::from("reply-id").unwrap());
+    ///     input.consistent_read = Some(true);
+    ///
+    ///     let reply = cli.get_item(input).await.unwrap();
+    /// }
+    /// ```
+    pub async fn get_item(&self, input: GetItemInput) -> Result<Reply> {
+        let consistent_read = input.consistent_read.unwrap_or(false);
+
+        return get_entity(self.store, key_of(&input.id), consistent_read).await;
+    }
+
+    /// Returns the replies with the ids, in the order of the ids,
+    /// fails with `Error::NotFound` if any of them is missing.
+    pub async fn batch_get_items(&self, input: BatchGetItemsInput) -> Result<Vec<Reply>> {
+        let consistent_read = input.consistent_read.unwrap_or(false);
+        let keys = input.ids.iter().map(key_of).collect();
+
+        return batch_get_entities(self.store, keys, consistent_read).await;
+    }
+
+    /// Lists the replies to a comment, the oldest first
+    /// (or the newest first when `reverse` is set).
+    ///
+    /// # Example:
+    ///
+    /// ```no_run
+    /// use tokio;
+    /// use valnk::data::api::reply::*;
+    /// use valnk::data::api::store::{dynamodb_client, DynamoStore};
+    /// use valnk::data::model::submission::SubmissionId;
+    /// use valnk::data::model::comment::CommentId;
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let shared_config = aws_config::load_from_env().await;
+    ///     let aws_cli = dynamodb_client(&shared_config, None).unwrap();
+    ///     let store = DynamoStore::new(aws_cli, "valnk-content");
+    ///     let cli = Client::new(&store);
+    ///
+    ///     let input = ListRepliesByCommentInput::new(
+    ///         SubmissionId::from("subm-id").unwrap(),
+    ///         CommentId::from("comm-id").unwrap(),
+    ///     );
+    ///
+    ///     let output = cli.list_replies_by_comment(input).await.unwrap();
+    /// }
+    /// ```
+    pub async fn list_replies_by_comment(&self, input: ListRepliesByCommentInput) -> Result<ListRepliesOutput> {
+        let mut query = QueryInput::new(Index::Gsi1, SubmissionCommentIndexKey::pk(&input.submission_id));
+        query.sk = Some(SkCondition::BeginsWith(SubmissionCommentIndexKey::comment_sk_prefix(&input.comment_id)));
+        query.scan_index_forward = !input.reverse.unwrap_or(false);
+        query.limit = Some(self.page_limits.resolve(input.limit)?);
+
+        let page = query_page(self.store, query, input.start_cursor).await?;
+
+        let mut output = ListRepliesOutput::new(page.items);
+        output.next_cursor = page.next_cursor;
+        output.prev_cursor = page.prev_cursor;
+
+        Ok(output)
+    }
+
+    /// Lists all the replies of a submission in one partition query,
+    /// grouped by comment and the oldest first within a comment.
+    pub async fn list_replies_by_submission(&self, input: ListRepliesBySubmissionInput) -> Result<ListRepliesOutput> {
+        let mut query = QueryInput::new(Index::Gsi1, SubmissionCommentIndexKey::pk(&input.submission_id));
+        query.sk = Some(SkCondition::BeginsWith(SubmissionCommentIndexKey::sk_prefix()));
+        query.scan_index_forward = !input.reverse.unwrap_or(false);
+        query.limit = Some(self.page_limits.resolve(input.limit)?);
+
+        let page = query_page(self.store, query, input.start_cursor).await?;
+
+        let mut output = ListRepliesOutput::new(page.items);
+        output.next_cursor = page.next_cursor;
+        output.prev_cursor = page.prev_cursor;
+
+        Ok(output)
+    }
+
+    /// Streams the replies of a comment, following the cursors from `input.start_cursor`,
+    /// `input.limit` is the size of the pages and `max_items` caps the number of items.
+    pub fn stream_replies_by_comment(&self, input: ListRepliesByCommentInput, max_items: Option<usize>) -> ItemStream<'_, Reply> {
+        return paginate(input.start_cursor.clone(), max_items, move |cursor| {
+            let mut input = input.clone();
+            input.start_cursor = cursor;
+            async move {
+                let output = self.list_replies_by_comment(input).await?;
+                Ok((output.items, output.next_cursor))
+            }
+        });
+    }
+
+    /// Streams all the replies of a submission, following the cursors from `input.start_cursor`,
+    /// `input.limit` is the size of the pages and `max_items` caps the number of items.
+    pub fn stream_replies_by_submission(&self, input: ListRepliesBySubmissionInput, max_items: Option<usize>) -> ItemStream<'_, Reply> {
+        return paginate(input.start_cursor.clone(), max_items, move |cursor| {
+            let mut input = input.clone();
+            input.start_cursor = cursor;
+            async move {
+                let output = self.list_replies_by_submission(input).await?;
+                Ok((output.items, output.next_cursor))
+            }
+        });
+    }
+}
@@ -1,6 +1,9 @@
 use serde_dynamo;
 use thiserror::Error;
 
+/// The error of the store behind an [`Error`], e.g. the SDK error of a DynamoDB request.
+pub type SourceError = Box<dyn std::error::Error + Send + Sync>;
+
 #[derive(Error, Debug)]
 pub enum Error {
     #[error("invalid request: `{0}`")]
@@ -12,11 +15,91 @@ pub enum Error {
     #[error("invalid output data")]
     InvalidOutputData(#[source] serde_dynamo::Error),
 
-    #[error("failed to make a request, upstream server error: `{0}`")]
-    ServerError(String),
+    #[error("invalid cursor: `{0}`")]
+    InvalidCursor(String),
+
+    #[error("not found: `{0}`")]
+    NotFound(String),
+
+    #[error("the request conflicts with the stored data: `{0}`")]
+    Conflict(String),
+
+    /// The condition of a single write does not hold, e.g. `attribute_exists(PK)`.
+    #[error("the condition of the write does not hold")]
+    ConditionFailed(#[source] SourceError),
+
+    /// A transaction was cancelled as a whole, with the reason code of each of its
+    /// writes, in order, e.g. `Some("ConditionalCheckFailed")`, or `None` if it did not fail.
+    #[error("the transaction was cancelled, reasons: `{0:?}`")]
+    TransactionCancelled(Vec<Option<String>>, #[source] Option<SourceError>),
+
+    /// The request exceeded the provisioned throughput or a request rate limit.
+    #[error("the request was throttled")]
+    Throttled(#[source] SourceError),
+
+    /// The store rejected the request as invalid, e.g. an item over the size limit.
+    #[error("the request was rejected as invalid")]
+    ValidationFailed(#[source] SourceError),
+
+    /// The request timed out or failed on the network, it may have been applied or not.
+    #[error("the request failed on the way to the store")]
+    Transient(#[source] SourceError),
+
+    #[error("failed to make a request, upstream server error")]
+    ServerError(#[source] SourceError),
 
     #[error("unknown error: {0}")]
     Unknown(String),
 }
 
-pub type Result<T, E = Error> = std::result::Result<T, E>;
\ No newline at end of file
+impl Error {
+    /// The HTTP status code of the response to a request that failed with this error.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use valnk::data::api::result::Error;
+    ///
+    /// assert_eq!(Error::NotFound("SUBMS#id1".to_string()).http_status(), 404);
+    /// assert_eq!(Error::Throttled("slow down".into()).http_status(), 503);
+    /// ```
+    pub fn http_status(&self) -> u16 {
+        return match self {
+            Error::BadRequest(_) => 400,
+            Error::InvalidInputData(_) => 400,
+            Error::InvalidCursor(_) => 400,
+            Error::ValidationFailed(_) => 400,
+            Error::NotFound(_) => 404,
+            Error::Conflict(_) => 409,
+            Error::ConditionFailed(_) => 409,
+            Error::TransactionCancelled(reasons, _) => transaction_status(reasons),
+            Error::Throttled(_) => 503,
+            Error::Transient(_) => 503,
+            Error::InvalidOutputData(_) => 500,
+            Error::ServerError(_) => 500,
+            Error::Unknown(_) => 500,
+        };
+    }
+}
+
+/// A cancelled transaction conflicts with the stored data if a condition or a
+/// concurrent transaction cancelled it, the throttled ones may be retried.
+fn transaction_status(reasons: &[Option<String>]) -> u16 {
+    let has_reason = |codes: &[&str]| reasons.iter()
+        .flatten()
+        .any(|r| codes.contains(&r.as_str()));
+
+    if has_reason(&["ConditionalCheckFailed", "TransactionConflict"]) {
+        return 409;
+    }
+    if has_reason(&["ThrottlingError", "ProvisionedThroughputExceeded"]) {
+        return 503;
+    }
+    if has_reason(&["ValidationError", "ItemCollectionSizeLimitExceeded"]) {
+        return 400;
+    }
+
+    return 500;
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
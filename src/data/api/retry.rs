@@ -0,0 +1,176 @@
+//! Retries of the requests to the store that fail with a throttling or transient error.
+
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use rand::Rng;
+
+use super::result::{Error, Result};
+
+/// How many times and how long after a failed request is sent again.
+///
+/// The delay before the `n`-th retry is drawn uniformly from
+/// `0..=min(max_delay, base_delay * 2^(n-1))` ("full jitter"),
+/// so the clients that were throttled together do not retry together.
+///
+/// # Examples
+///
+/// ```
+/// use std::time::Duration;
+/// use valnk::data::api::retry::RetryPolicy;
+///
+/// let policy = RetryPolicy::new()
+///     .with_max_attempts(5)
+///     .with_base_delay(Duration::from_millis(20));
+///
+/// assert!(policy.delay(3) <= Duration::from_millis(80));
+/// ```
+#[derive(Clone, PartialEq, Debug)]
+pub struct RetryPolicy {
+    /// The number of attempts of a request, the first one included.
+    pub max_attempts: u32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+}
+
+impl RetryPolicy {
+    pub fn new() -> Self {
+        return Self {
+            max_attempts: 4,
+            base_delay: Duration::from_millis(50),
+            max_delay: Duration::from_secs(2),
+        };
+    }
+
+    /// A policy that sends every request once.
+    pub fn never() -> Self {
+        return Self::new().with_max_attempts(1);
+    }
+
+    pub fn with_max_attempts(mut self, max_attempts: u32) -> Self {
+        self.max_attempts = max_attempts.max(1);
+        self
+    }
+
+    pub fn with_base_delay(mut self, base_delay: Duration) -> Self {
+        self.base_delay = base_delay;
+        self
+    }
+
+    pub fn with_max_delay(mut self, max_delay: Duration) -> Self {
+        self.max_delay = max_delay;
+        self
+    }
+
+    /// The random delay before the `retry`-th retry, from `1`.
+    pub fn delay(&self, retry: u32) -> Duration {
+        let factor = 2u32.saturating_pow(retry.saturating_sub(1));
+        let cap = self.base_delay.saturating_mul(factor).min(self.max_delay);
+
+        return rand::thread_rng().gen_range(Duration::ZERO..=cap);
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        return RetryPolicy::new();
+    }
+}
+
+/// Whether a request that failed with `err` may be sent again.
+///
+/// A throttled request, or a transaction cancelled by a concurrent one, has not been
+/// applied. A request that timed out or failed on the server may have been applied,
+/// so it is only sent again if applying it twice is the same as once (`idempotent`),
+/// e.g. not an update that adds to a counter, nor a write whose condition it would
+/// then fail itself.
+pub fn is_retryable(err: &Error, idempotent: bool) -> bool {
+    return match err {
+        Error::Throttled(_) => true,
+        Error::TransactionCancelled(reasons, _) => reasons.iter().flatten().any(|r| {
+            matches!(r.as_str(), "TransactionConflict" | "ThrottlingError" | "ProvisionedThroughputExceeded")
+        }),
+        Error::Transient(_) | Error::ServerError(_) => idempotent,
+        _ => false,
+    };
+}
+
+/// Counters of the retries of a store, shared by its clones.
+#[derive(Default, Debug)]
+pub struct RetryMetrics {
+    requests: AtomicU64,
+    retries: AtomicU64,
+    throttled: AtomicU64,
+    exhausted: AtomicU64,
+    unprocessed_keys: AtomicU64,
+}
+
+/// The values of the [`RetryMetrics`] at some point.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub struct RetryStats {
+    /// The number of requests, whatever their number of attempts.
+    pub requests: u64,
+    /// The number of attempts after the first ones.
+    pub retries: u64,
+    /// The number of attempts that were throttled.
+    pub throttled: u64,
+    /// The number of requests that failed after their last attempt with a retryable error.
+    pub exhausted: u64,
+    /// The number of keys of the batch reads that were sent again, unprocessed.
+    pub unprocessed_keys: u64,
+}
+
+impl RetryMetrics {
+    pub fn stats(&self) -> RetryStats {
+        return RetryStats {
+            requests: self.requests.load(Ordering::Relaxed),
+            retries: self.retries.load(Ordering::Relaxed),
+            throttled: self.throttled.load(Ordering::Relaxed),
+            exhausted: self.exhausted.load(Ordering::Relaxed),
+            unprocessed_keys: self.unprocessed_keys.load(Ordering::Relaxed),
+        };
+    }
+
+    pub(crate) fn add_unprocessed_keys(&self, n: usize) {
+        self.unprocessed_keys.fetch_add(n as u64, Ordering::Relaxed);
+    }
+}
+
+/// Sends a request with `send` until it succeeds, fails with an error that is not
+/// retryable, or runs out of attempts, sleeping for the delays of the `policy` between them.
+pub(crate) async fn with_retry<T, F, Fut>(
+    policy: &RetryPolicy,
+    metrics: &RetryMetrics,
+    idempotent: bool,
+    mut send: F,
+) -> Result<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T>>,
+{
+    metrics.requests.fetch_add(1, Ordering::Relaxed);
+
+    let mut attempt = 1;
+    loop {
+        let err = match send().await {
+            Ok(output) => return Ok(output),
+            Err(e) => e,
+        };
+
+        if matches!(err, Error::Throttled(_)) {
+            metrics.throttled.fetch_add(1, Ordering::Relaxed);
+        }
+        if !is_retryable(&err, idempotent) {
+            return Err(err);
+        }
+        if attempt >= policy.max_attempts {
+            metrics.exhausted.fetch_add(1, Ordering::Relaxed);
+            return Err(err);
+        }
+
+        tokio::time::sleep(policy.delay(attempt)).await;
+        metrics.retries.fetch_add(1, Ordering::Relaxed);
+        attempt += 1;
+    }
+}
@@ -0,0 +1,124 @@
+use crate::data::model::entity::{EntityType, EntityId};
+use crate::data::model::revision::{Revision, PrimaryKey};
+
+use super::result::{Error, Result};
+use super::cursor::Cursor;
+use super::page::{query_page, paginate, ItemStream, PageLimits};
+use super::store::{
+    ContentStore,
+    Index,
+    SkCondition,
+    QueryInput,
+};
+
+
+#[derive(Clone, Debug)]
+pub struct ListRevisionsInput {
+    /// `Submission`, `Comment` or `Reply`.
+    pub target_type: EntityType,
+    pub target_id: EntityId,
+    pub limit: Option<i32>,
+    pub reverse: Option<bool>,
+    pub start_cursor: Option<Cursor>,
+}
+
+impl ListRevisionsInput {
+    pub fn new(target_type: EntityType, target_id: EntityId) -> Self {
+        Self {
+            target_type,
+            target_id,
+            limit: None,
+            reverse: None,
+            start_cursor: None,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct ListRevisionsOutput {
+    pub items: Vec<Revision>,
+    pub next_cursor: Option<Cursor>,
+    /// The cursor of the page before this one, `None` on the first page.
+    pub prev_cursor: Option<Cursor>,
+}
+
+impl ListRevisionsOutput {
+    pub fn new(items: Vec<Revision>) -> Self {
+        Self {
+            items,
+            next_cursor: None,
+            prev_cursor: None,
+        }
+    }
+}
+
+
+/// Reads the revisions that the edits of the submissions, comments and replies leave behind.
+#[derive(Debug)]
+pub struct Client<'c> {
+    store: &'c dyn ContentStore,
+    page_limits: PageLimits,
+}
+
+impl<'c> Client<'c> {
+    pub fn new(store: &'c dyn ContentStore) -> Self {
+        return Self {
+            store,
+            page_limits: PageLimits::default(),
+        };
+    }
+
+    pub fn with_page_limits(mut self, page_limits: PageLimits) -> Self {
+        self.page_limits = page_limits;
+        self
+    }
+
+    /// Lists the revisions of an entity, the latest edit first
+    /// (or the earliest first when `reverse` is set).
+    ///
+    /// # Example:
+    ///
+    /// ```no_run
+    /// use valnk::data::api::revision::*;
+    /// use valnk::data::model::entity::{EntityType, EntityId};
+    ///
+    /// async fn print_revisions(cli: &Client<'_>) {
+    ///     let input = ListRevisionsInput::new(EntityType::Comment, EntityId::from("comm-id").unwrap());
+    ///     let output = cli.list_revisions(input).await.unwrap();
+    ///     for rev in output.items {
+    ///         println!("{} at {}: {:?} -> {:?}", rev.editor_id, rev.revised_at, rev.previous, rev.revised);
+    ///     }
+    /// }
+    /// ```
+    pub async fn list_revisions(&self, input: ListRevisionsInput) -> Result<ListRevisionsOutput> {
+        if !matches!(input.target_type, EntityType::Submission | EntityType::Comment | EntityType::Reply) {
+            return Err(Error::BadRequest(format!("`{:?}` has no revisions", input.target_type)));
+        }
+
+        let mut query = QueryInput::new(Index::Primary, PrimaryKey::pk(&input.target_type, &input.target_id));
+        query.sk = Some(SkCondition::BeginsWith(PrimaryKey::sk_prefix()));
+        query.scan_index_forward = input.reverse.unwrap_or(false);
+        query.limit = Some(self.page_limits.resolve(input.limit)?);
+
+        let page = query_page(self.store, query, input.start_cursor).await?;
+
+        let mut output = ListRevisionsOutput::new(page.items);
+        output.next_cursor = page.next_cursor;
+        output.prev_cursor = page.prev_cursor;
+
+        Ok(output)
+    }
+
+    /// Streams the revisions of an entity, following the cursors from `input.start_cursor`,
+    /// `input.limit` is the size of the pages and `max_items` caps the number of items.
+    pub fn stream_revisions(&self, input: ListRevisionsInput, max_items: Option<usize>) -> ItemStream<'_, Revision> {
+        return paginate(input.start_cursor.clone(), max_items, move |cursor| {
+            let mut input = input.clone();
+            input.start_cursor = cursor;
+            async move {
+                let output = self.list_revisions(input).await?;
+                Ok((output.items, output.next_cursor))
+            }
+        });
+    }
+}
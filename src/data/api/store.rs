@@ -0,0 +1,388 @@
+pub mod dynamo;
+pub mod memory;
+pub mod schema;
+
+use std::collections::HashMap;
+use std::fmt::Debug;
+
+use async_trait::async_trait;
+use aws_sdk_dynamodb::model::AttributeValue;
+
+use super::result::{Error, Result};
+
+pub use dynamo::{dynamodb_client, dynamodb_config, DynamoStore};
+pub use memory::MemoryStore;
+
+/// A raw item of the `valnk-content` table.
+pub type Item = HashMap<String, AttributeValue>;
+
+/// The indexes of the `valnk-content` table.
+///
+/// All entities (submissions, comments and replies) live in one table,
+/// the same key attributes are shared by every entity type.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Index {
+    /// The table itself: `PK` / `SK`.
+    Primary,
+    /// `GSI1_PK` / `GSI1_SK`, e.g. submissions by topic, comments by submission.
+    Gsi1,
+    /// `GSI2_PK` / `GSI2_SK`, e.g. entities by author.
+    Gsi2,
+    /// `GSI3_PK` / `GSI3_SK`, the comment trees of the submissions by path,
+    /// and the submissions of the topics by creation time.
+    Gsi3,
+    /// `GSI4_PK` / `GSI4_SK`, the deleted entities by deletion time.
+    Gsi4,
+}
+
+impl Index {
+    pub fn name(&self) -> Option<&'static str> {
+        return match self {
+            Index::Primary => None,
+            Index::Gsi1 => Some("GSI1"),
+            Index::Gsi2 => Some("GSI2"),
+            Index::Gsi3 => Some("GSI3"),
+            Index::Gsi4 => Some("GSI4"),
+        };
+    }
+
+    pub fn pk_attr(&self) -> &'static str {
+        return match self {
+            Index::Primary => "PK",
+            Index::Gsi1 => "GSI1_PK",
+            Index::Gsi2 => "GSI2_PK",
+            Index::Gsi3 => "GSI3_PK",
+            Index::Gsi4 => "GSI4_PK",
+        };
+    }
+
+    pub fn sk_attr(&self) -> &'static str {
+        return match self {
+            Index::Primary => "SK",
+            Index::Gsi1 => "GSI1_SK",
+            Index::Gsi2 => "GSI2_SK",
+            Index::Gsi3 => "GSI3_SK",
+            Index::Gsi4 => "GSI4_SK",
+        };
+    }
+
+    /// The `LastEvaluatedKey` of a query on the index that stops at `item`.
+    pub fn start_key(&self, item: &Item) -> Item {
+        let mut attrs = vec![Index::Primary.pk_attr(), Index::Primary.sk_attr()];
+        if *self != Index::Primary {
+            attrs.push(self.pk_attr());
+            attrs.push(self.sk_attr());
+        }
+
+        return attrs.into_iter()
+            .filter_map(|attr| item.get(attr).map(|v| (attr.to_string(), v.clone())))
+            .collect();
+    }
+}
+
+/// The primary key of an item.
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct Key {
+    pub pk: String,
+    pub sk: String,
+}
+
+impl Key {
+    pub fn new(pk: impl Into<String>, sk: impl Into<String>) -> Self {
+        return Self {
+            pk: pk.into(),
+            sk: sk.into(),
+        };
+    }
+
+    pub fn to_item(&self) -> Item {
+        let pk_attr = Index::Primary.pk_attr();
+        let sk_attr = Index::Primary.sk_attr();
+
+        return HashMap::from([
+            (pk_attr.to_string(), AttributeValue::S(self.pk.clone())),
+            (sk_attr.to_string(), AttributeValue::S(self.sk.clone())),
+        ]);
+    }
+}
+
+/// A condition on the sort key of a query.
+#[derive(Clone, PartialEq, Debug)]
+pub enum SkCondition {
+    Eq(String),
+    Lt(String),
+    Le(String),
+    Gt(String),
+    Ge(String),
+    Between(String, String),
+    BeginsWith(String),
+}
+
+impl SkCondition {
+    pub fn matches(&self, sk: &str) -> bool {
+        return match self {
+            SkCondition::Eq(v) => sk == v,
+            SkCondition::Lt(v) => sk < v.as_str(),
+            SkCondition::Le(v) => sk <= v.as_str(),
+            SkCondition::Gt(v) => sk > v.as_str(),
+            SkCondition::Ge(v) => sk >= v.as_str(),
+            SkCondition::Between(lo, hi) => lo.as_str() <= sk && sk <= hi.as_str(),
+            SkCondition::BeginsWith(pfx) => sk.starts_with(pfx.as_str()),
+        };
+    }
+}
+
+/// A condition that must hold on the stored item for a write to succeed.
+#[derive(Clone, PartialEq, Debug)]
+pub enum Condition {
+    AttributeExists(String),
+    AttributeNotExists(String),
+    Equals(String, AttributeValue),
+    And(Vec<Condition>),
+}
+
+/// A single modification of an attribute in `update_item`.
+#[derive(Clone, PartialEq, Debug)]
+pub enum UpdateAction {
+    /// Sets the attribute to the value.
+    Set(String, AttributeValue),
+    /// Adds the number to a numeric attribute, a missing attribute counts as `0`.
+    Add(String, i64),
+    /// Removes the attribute.
+    Remove(String),
+}
+
+#[derive(Clone, Debug)]
+pub struct PutItemInput {
+    pub item: Item,
+    pub condition: Option<Condition>,
+}
+
+impl PutItemInput {
+    pub fn new(item: Item) -> Self {
+        Self {
+            item,
+            condition: None,
+        }
+    }
+
+    /// Whether the put can be sent again after it may have been applied,
+    /// a conditional one would then fail its own condition, e.g. `attribute_not_exists(PK)`.
+    pub fn is_idempotent(&self) -> bool {
+        return self.condition.is_none();
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct GetItemInput {
+    pub key: Key,
+    pub consistent_read: bool,
+}
+
+impl GetItemInput {
+    pub fn new(key: Key) -> Self {
+        Self {
+            key,
+            consistent_read: false,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct BatchGetItemsInput {
+    pub keys: Vec<Key>,
+    pub consistent_read: bool,
+}
+
+impl BatchGetItemsInput {
+    pub fn new(keys: Vec<Key>) -> Self {
+        Self {
+            keys,
+            consistent_read: false,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct UpdateItemInput {
+    pub key: Key,
+    pub actions: Vec<UpdateAction>,
+    pub condition: Option<Condition>,
+}
+
+impl UpdateItemInput {
+    pub fn new(key: Key, actions: Vec<UpdateAction>) -> Self {
+        Self {
+            key,
+            actions,
+            condition: None,
+        }
+    }
+
+    /// Whether the update can be sent again after it may have been applied:
+    /// it has no condition and adds to no counter.
+    pub fn is_idempotent(&self) -> bool {
+        return self.condition.is_none()
+            && !self.actions.iter().any(|a| matches!(a, UpdateAction::Add(_, _)));
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct DeleteItemInput {
+    pub key: Key,
+    pub condition: Option<Condition>,
+}
+
+impl DeleteItemInput {
+    pub fn new(key: Key) -> Self {
+        Self {
+            key,
+            condition: None,
+        }
+    }
+
+    /// Whether the delete can be sent again after it may have been applied.
+    pub fn is_idempotent(&self) -> bool {
+        return self.condition.is_none();
+    }
+}
+
+/// A single write of `transact_write_items`.
+#[derive(Clone, Debug)]
+pub enum TransactWriteItem {
+    Put(PutItemInput),
+    Update(UpdateItemInput),
+    Delete(DeleteItemInput),
+    /// Only checks the condition on the item with the key, without writing it.
+    ConditionCheck(Key, Condition),
+}
+
+/// Applies the actions of an update to an item, like DynamoDB does.
+pub fn apply_update(item: &mut Item, actions: &[UpdateAction]) -> Result<()> {
+    for action in actions {
+        match action {
+            UpdateAction::Set(attr, value) => {
+                item.insert(attr.clone(), value.clone());
+            }
+            UpdateAction::Add(attr, n) => {
+                let current = match item.get(attr) {
+                    None => 0,
+                    Some(AttributeValue::N(v)) => v.parse::<i64>()
+                        .map_err(|e| Error::BadRequest(format!("`{attr}` is not an integer: {e}")))?,
+                    Some(_) => return Err(Error::BadRequest(format!("`{attr}` is not a number"))),
+                };
+                item.insert(attr.clone(), AttributeValue::N((current + n).to_string()));
+            }
+            UpdateAction::Remove(attr) => {
+                item.remove(attr);
+            }
+        }
+    }
+
+    return Ok(());
+}
+
+/// The reason code of a write of a cancelled transaction whose condition did not hold.
+pub const CONDITIONAL_CHECK_FAILED: &str = "ConditionalCheckFailed";
+
+/// Whether the write at `index` of a cancelled transaction failed its condition.
+pub fn condition_failed(reasons: &[Option<String>], index: usize) -> bool {
+    return reasons.get(index)
+        .is_some_and(|r| r.as_deref() == Some(CONDITIONAL_CHECK_FAILED));
+}
+
+#[derive(Clone, Debug)]
+pub struct QueryInput {
+    pub index: Index,
+    pub pk: String,
+    pub sk: Option<SkCondition>,
+    pub scan_index_forward: bool,
+    pub limit: Option<i32>,
+    pub exclusive_start_key: Option<Item>,
+}
+
+impl QueryInput {
+    pub fn new(index: Index, pk: impl Into<String>) -> Self {
+        Self {
+            index,
+            pk: pk.into(),
+            sk: None,
+            scan_index_forward: true,
+            limit: None,
+            exclusive_start_key: None,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct QueryOutput {
+    pub items: Vec<Item>,
+    pub last_evaluated_key: Option<Item>,
+}
+
+#[derive(Clone, Debug)]
+pub struct ScanInput {
+    pub limit: Option<i32>,
+    pub exclusive_start_key: Option<Item>,
+}
+
+impl ScanInput {
+    pub fn new() -> Self {
+        Self {
+            limit: None,
+            exclusive_start_key: None,
+        }
+    }
+}
+
+impl Default for ScanInput {
+    fn default() -> Self {
+        return ScanInput::new();
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct ScanOutput {
+    pub items: Vec<Item>,
+    pub last_evaluated_key: Option<Item>,
+}
+
+/// The storage backend of the `valnk-content` table.
+///
+/// Every entity is created, read, listed, updated and deleted through these
+/// item-level operations, so the API clients work the same way on top of
+/// DynamoDB ([`DynamoStore`]) or in memory ([`MemoryStore`]).
+#[async_trait]
+pub trait ContentStore: Send + Sync + Debug {
+    /// Creates or replaces an item, fails with `Error::ConditionFailed` if the condition does not hold.
+    async fn put_item(&self, input: PutItemInput) -> Result<()>;
+
+    /// Returns the item with the key, if any.
+    async fn get_item(&self, input: GetItemInput) -> Result<Option<Item>>;
+
+    /// Returns the items with the keys, in no particular order, missing items are left out.
+    async fn batch_get_items(&self, input: BatchGetItemsInput) -> Result<Vec<Item>>;
+
+    /// Queries the items of one partition of an index, ordered by its sort key.
+    ///
+    /// Like DynamoDB, `last_evaluated_key` is set whenever the page is cut by `limit`.
+    async fn query(&self, input: QueryInput) -> Result<QueryOutput>;
+
+    /// Reads a page of all the items of the table, in no particular order,
+    /// for the maintenance jobs only.
+    async fn scan(&self, input: ScanInput) -> Result<ScanOutput>;
+
+    /// Updates an item and returns all of its new attributes,
+    /// fails with `Error::ConditionFailed` if the condition does not hold.
+    async fn update_item(&self, input: UpdateItemInput) -> Result<Item>;
+
+    /// Deletes an item and returns its old attributes, if any.
+    async fn delete_item(&self, input: DeleteItemInput) -> Result<Option<Item>>;
+
+    /// Applies all the writes or none of them, an item can only be written once per transaction.
+    ///
+    /// Fails with `Error::TransactionCancelled` if any condition does not hold.
+    /// The attempts of a transaction share a client request token, so one that is sent
+    /// again after it was applied succeeds without being applied twice.
+    async fn transact_write_items(&self, items: Vec<TransactWriteItem>) -> Result<()>;
+}
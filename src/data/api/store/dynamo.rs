@@ -0,0 +1,538 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use aws_sdk_dynamodb::Client as DynamodbClient;
+use aws_sdk_dynamodb::Error as DynamodbError;
+use aws_sdk_dynamodb::Endpoint;
+use aws_sdk_dynamodb::types::SdkError;
+use aws_smithy_types::retry::{ProvideErrorKind, RetryConfig};
+use aws_sdk_dynamodb::model::{
+    AttributeValue,
+    KeysAndAttributes,
+    ReturnValue,
+    Put,
+    Update,
+    Delete,
+    ConditionCheck,
+};
+use aws_sdk_dynamodb::model::TransactWriteItem as DynamodbTransactWriteItem;
+use uuid::Uuid;
+
+use super::{
+    ContentStore,
+    Item,
+    Condition,
+    SkCondition,
+    UpdateAction,
+    TransactWriteItem,
+    PutItemInput,
+    GetItemInput,
+    BatchGetItemsInput,
+    UpdateItemInput,
+    DeleteItemInput,
+    QueryInput,
+    QueryOutput,
+    ScanInput,
+    ScanOutput,
+};
+use super::super::result::{Error, Result};
+use super::super::retry::{with_retry, RetryMetrics, RetryPolicy, RetryStats};
+
+/// The maximum number of keys of one `BatchGetItem` request.
+const BATCH_GET_LIMIT: usize = 100;
+
+type ExpressionNames = Option<HashMap<String, String>>;
+type ExpressionValues = Option<HashMap<String, AttributeValue>>;
+
+/// Builds the placeholders of a DynamoDB expression,
+/// attribute names become `#nX` and values become `:vX`.
+#[derive(Default, Debug)]
+struct Expression {
+    names: HashMap<String, String>,
+    values: HashMap<String, AttributeValue>,
+}
+
+impl Expression {
+    fn name(&mut self, attr: &str) -> String {
+        let placeholder = format!("#n{}", self.names.len());
+        self.names.insert(placeholder.clone(), attr.to_string());
+        return placeholder;
+    }
+
+    fn value(&mut self, value: AttributeValue) -> String {
+        let placeholder = format!(":v{}", self.values.len());
+        self.values.insert(placeholder.clone(), value);
+        return placeholder;
+    }
+
+    fn condition(&mut self, cond: &Condition) -> String {
+        return match cond {
+            Condition::AttributeExists(attr) => {
+                format!("attribute_exists({})", self.name(attr))
+            }
+            Condition::AttributeNotExists(attr) => {
+                format!("attribute_not_exists({})", self.name(attr))
+            }
+            Condition::Equals(attr, value) => {
+                format!("{} = {}", self.name(attr), self.value(value.clone()))
+            }
+            Condition::And(conds) => {
+                let exprs: Vec<String> = conds.iter()
+                    .map(|c| format!("({})", self.condition(c)))
+                    .collect();
+                exprs.join(" AND ")
+            }
+        };
+    }
+
+    fn sk_condition(&mut self, attr: &str, cond: &SkCondition) -> String {
+        let name = self.name(attr);
+
+        return match cond {
+            SkCondition::Eq(v) => format!("{name} = {}", self.value(AttributeValue::S(v.clone()))),
+            SkCondition::Lt(v) => format!("{name} < {}", self.value(AttributeValue::S(v.clone()))),
+            SkCondition::Le(v) => format!("{name} <= {}", self.value(AttributeValue::S(v.clone()))),
+            SkCondition::Gt(v) => format!("{name} > {}", self.value(AttributeValue::S(v.clone()))),
+            SkCondition::Ge(v) => format!("{name} >= {}", self.value(AttributeValue::S(v.clone()))),
+            SkCondition::Between(lo, hi) => {
+                let lo = self.value(AttributeValue::S(lo.clone()));
+                let hi = self.value(AttributeValue::S(hi.clone()));
+                format!("{name} BETWEEN {lo} AND {hi}")
+            }
+            SkCondition::BeginsWith(pfx) => {
+                format!("begins_with({name}, {})", self.value(AttributeValue::S(pfx.clone())))
+            }
+        };
+    }
+
+    fn update(&mut self, actions: &[UpdateAction]) -> String {
+        let mut sets = vec![];
+        let mut adds = vec![];
+        let mut removes = vec![];
+
+        for action in actions {
+            match action {
+                UpdateAction::Set(attr, value) => {
+                    sets.push(format!("{} = {}", self.name(attr), self.value(value.clone())));
+                }
+                UpdateAction::Add(attr, n) => {
+                    adds.push(format!("{} {}", self.name(attr), self.value(AttributeValue::N(n.to_string()))));
+                }
+                UpdateAction::Remove(attr) => {
+                    removes.push(self.name(attr));
+                }
+            }
+        }
+
+        let mut clauses = vec![];
+        if !sets.is_empty() {
+            clauses.push(format!("SET {}", sets.join(", ")));
+        }
+        if !adds.is_empty() {
+            clauses.push(format!("ADD {}", adds.join(", ")));
+        }
+        if !removes.is_empty() {
+            clauses.push(format!("REMOVE {}", removes.join(", ")));
+        }
+
+        return clauses.join(" ");
+    }
+
+    /// DynamoDB rejects empty placeholder maps.
+    fn into_parts(self) -> (ExpressionNames, ExpressionValues) {
+        let names = if self.names.is_empty() { None } else { Some(self.names) };
+        let values = if self.values.is_empty() { None } else { Some(self.values) };
+
+        return (names, values);
+    }
+}
+
+/// Classifies the error of a request by its kind and service error code,
+/// the SDK error is kept as the source.
+pub(super) fn map_sdk_err<E>(err: SdkError<E>) -> Error
+    where E: ProvideErrorKind + std::error::Error + Send + Sync + 'static,
+          DynamodbError: From<SdkError<E>>
+{
+    let code = match &err {
+        SdkError::ServiceError { err, .. } => err.code().map(String::from),
+        SdkError::TimeoutError(_) | SdkError::DispatchFailure(_) | SdkError::ResponseError { .. } => {
+            return Error::Transient(Box::new(err));
+        }
+        SdkError::ConstructionFailure(_) => return Error::ValidationFailed(Box::new(err)),
+    };
+
+    return match code.as_deref() {
+        Some("ConditionalCheckFailedException") => Error::ConditionFailed(Box::new(err)),
+        Some("TransactionCanceledException") => match DynamodbError::from(err) {
+            DynamodbError::TransactionCanceledException(e) => {
+                // the reason of a write that did not fail is `None`
+                let reasons = e.cancellation_reasons()
+                    .unwrap_or_default()
+                    .iter()
+                    .map(|r| r.code().filter(|c| *c != "None").map(String::from))
+                    .collect();
+                Error::TransactionCancelled(reasons, Some(Box::new(e)))
+            }
+            e => Error::ServerError(Box::new(e)),
+        },
+        Some(
+            "ProvisionedThroughputExceededException"
+            | "RequestLimitExceeded"
+            | "ThrottlingException"
+            | "LimitExceededException"
+        ) => Error::Throttled(Box::new(err)),
+        Some(
+            "ValidationException"
+            | "SerializationException"
+            | "ItemCollectionSizeLimitExceededException"
+        ) => Error::ValidationFailed(Box::new(err)),
+        Some("ServiceUnavailable" | "TransactionInProgressException") => Error::Transient(Box::new(err)),
+        _ => Error::ServerError(Box::new(err)),
+    };
+}
+
+/// The config of a DynamoDB client for the shared config, with the retries of the SDK disabled,
+/// so the requests are only sent again by a [`DynamoStore`], following its [`RetryPolicy`].
+pub fn dynamodb_config(shared_config: &aws_config::SdkConfig) -> aws_sdk_dynamodb::config::Builder {
+    return aws_sdk_dynamodb::config::Builder::from(shared_config)
+        .retry_config(RetryConfig::disabled());
+}
+
+/// A DynamoDB client for the shared config, sending its requests to `endpoint_url` if set,
+/// e.g. to a DynamoDB Local or another DynamoDB-compatible store in the tests.
+///
+/// The client does not retry on its own, see [`dynamodb_config`].
+///
+/// # Example:
+///
+/// ```no_run
+/// use tokio;
+/// use valnk::data::api::store::{dynamodb_client, DynamoStore};
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let shared_config = aws_config::load_from_env().await;
+///     let aws_cli = dynamodb_client(&shared_config, Some("http://localhost:8000")).unwrap();
+///     let store = DynamoStore::new(aws_cli, "valnk-content");
+/// }
+/// ```
+pub fn dynamodb_client(shared_config: &aws_config::SdkConfig, endpoint_url: Option<&str>) -> Result<DynamodbClient> {
+    let mut config = dynamodb_config(shared_config);
+    if let Some(url) = endpoint_url {
+        let uri = url.parse()
+            .map_err(|e| Error::BadRequest(format!("invalid endpoint url `{url}`: {e}")))?;
+        config = config.endpoint_resolver(Endpoint::immutable(uri));
+    }
+
+    return Ok(DynamodbClient::from_conf(config.build()));
+}
+
+/// A [`ContentStore`] backed by a DynamoDB table.
+///
+/// The requests that are throttled or fail on the way are sent again
+/// following its [`RetryPolicy`], and so are the unprocessed keys of the batch reads.
+///
+/// The store needs a client whose own retries are disabled, as the ones of [`dynamodb_client`]:
+/// the SDK would otherwise send again the conditional writes and the counter updates
+/// that the policy never retries, and multiply the attempts of the others.
+#[derive(Clone, Debug)]
+pub struct DynamoStore {
+    ddb_cli: DynamodbClient,
+    table_name: String,
+    retry_policy: RetryPolicy,
+    retry_metrics: Arc<RetryMetrics>,
+}
+
+impl DynamoStore {
+    /// # Example:
+    ///
+    /// ```no_run
+    /// use tokio;
+    /// use valnk::data::api::store::{dynamodb_client, DynamoStore};
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let shared_config = aws_config::load_from_env().await;
+    ///     let aws_cli = dynamodb_client(&shared_config, None).unwrap();
+    ///     let store = DynamoStore::new(aws_cli, "valnk-content");
+    /// }
+    /// ```
+    pub fn new(ddb_cli: DynamodbClient, table_name: impl Into<String>) -> Self {
+        return Self {
+            ddb_cli,
+            table_name: table_name.into(),
+            retry_policy: RetryPolicy::default(),
+            retry_metrics: Arc::default(),
+        };
+    }
+
+    pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    pub fn table_name(&self) -> &str {
+        return &self.table_name;
+    }
+
+    /// The retries of the store and of its clones so far.
+    pub fn retry_stats(&self) -> RetryStats {
+        return self.retry_metrics.stats();
+    }
+
+    /// Sends a request with `send`, again if it fails with a retryable error.
+    async fn send<T, E, F, Fut>(&self, idempotent: bool, mut send: F) -> Result<T>
+        where F: FnMut() -> Fut,
+              Fut: Future<Output = std::result::Result<T, SdkError<E>>>,
+              E: ProvideErrorKind + std::error::Error + Send + Sync + 'static,
+              DynamodbError: From<SdkError<E>>
+    {
+        return with_retry(&self.retry_policy, &self.retry_metrics, idempotent, || {
+            let response = send();
+            async move { response.await.map_err(map_sdk_err) }
+        }).await;
+    }
+
+    fn transact_write_item(&self, write: TransactWriteItem) -> DynamodbTransactWriteItem {
+        let mut expr = Expression::default();
+
+        return match write {
+            TransactWriteItem::Put(input) => {
+                let condition = input.condition.map(|c| expr.condition(&c));
+                let (names, values) = expr.into_parts();
+                let put = Put::builder()
+                    .table_name(&self.table_name)
+                    .set_item(Some(input.item))
+                    .set_condition_expression(condition)
+                    .set_expression_attribute_names(names)
+                    .set_expression_attribute_values(values)
+                    .build();
+
+                DynamodbTransactWriteItem::builder().put(put).build()
+            }
+            TransactWriteItem::Update(input) => {
+                let update_expr = expr.update(&input.actions);
+                let condition = input.condition.map(|c| expr.condition(&c));
+                let (names, values) = expr.into_parts();
+                let update = Update::builder()
+                    .table_name(&self.table_name)
+                    .set_key(Some(input.key.to_item()))
+                    .update_expression(update_expr)
+                    .set_condition_expression(condition)
+                    .set_expression_attribute_names(names)
+                    .set_expression_attribute_values(values)
+                    .build();
+
+                DynamodbTransactWriteItem::builder().update(update).build()
+            }
+            TransactWriteItem::Delete(input) => {
+                let condition = input.condition.map(|c| expr.condition(&c));
+                let (names, values) = expr.into_parts();
+                let delete = Delete::builder()
+                    .table_name(&self.table_name)
+                    .set_key(Some(input.key.to_item()))
+                    .set_condition_expression(condition)
+                    .set_expression_attribute_names(names)
+                    .set_expression_attribute_values(values)
+                    .build();
+
+                DynamodbTransactWriteItem::builder().delete(delete).build()
+            }
+            TransactWriteItem::ConditionCheck(key, cond) => {
+                let condition = expr.condition(&cond);
+                let (names, values) = expr.into_parts();
+                let check = ConditionCheck::builder()
+                    .table_name(&self.table_name)
+                    .set_key(Some(key.to_item()))
+                    .condition_expression(condition)
+                    .set_expression_attribute_names(names)
+                    .set_expression_attribute_values(values)
+                    .build();
+
+                DynamodbTransactWriteItem::builder().condition_check(check).build()
+            }
+        };
+    }
+}
+
+#[async_trait]
+impl ContentStore for DynamoStore {
+    async fn put_item(&self, input: PutItemInput) -> Result<()> {
+        let idempotent = input.is_idempotent();
+        let mut expr = Expression::default();
+        let condition = input.condition.map(|c| expr.condition(&c));
+        let (names, values) = expr.into_parts();
+
+        let request = self.ddb_cli
+            .put_item()
+            .table_name(&self.table_name)
+            .set_item(Some(input.item))
+            .set_condition_expression(condition)
+            .set_expression_attribute_names(names)
+            .set_expression_attribute_values(values);
+        self.send(idempotent, || request.clone().send()).await?;
+
+        Ok(())
+    }
+
+    async fn get_item(&self, input: GetItemInput) -> Result<Option<Item>> {
+        let request = self.ddb_cli
+            .get_item()
+            .table_name(&self.table_name)
+            .set_key(Some(input.key.to_item()))
+            .consistent_read(input.consistent_read);
+        let result = self.send(true, || request.clone().send()).await?;
+
+        Ok(result.item)
+    }
+
+    async fn batch_get_items(&self, input: BatchGetItemsInput) -> Result<Vec<Item>> {
+        // `BatchGetItem` rejects duplicated keys
+        let mut keys = input.keys;
+        keys.sort_by(|a, b| (&a.pk, &a.sk).cmp(&(&b.pk, &b.sk)));
+        keys.dedup();
+
+        let mut items = vec![];
+        for chunk in keys.chunks(BATCH_GET_LIMIT) {
+            let mut keys_and_attrs = KeysAndAttributes::builder()
+                .set_keys(Some(chunk.iter().map(|k| k.to_item()).collect()))
+                .consistent_read(input.consistent_read)
+                .build();
+
+            let mut round = 0;
+            loop {
+                let request = self.ddb_cli
+                    .batch_get_item()
+                    .request_items(&self.table_name, keys_and_attrs);
+                let result = self.send(true, || request.clone().send()).await?;
+
+                if let Some(mut responses) = result.responses {
+                    items.extend(responses.remove(&self.table_name).unwrap_or_default());
+                }
+
+                // keys left out by DynamoDB (e.g. for the throughput or the response size)
+                // are asked again, after a delay as for a throttled request
+                let unprocessed = result.unprocessed_keys
+                    .and_then(|mut uk| uk.remove(&self.table_name))
+                    .filter(|u| u.keys().is_some_and(|k| !k.is_empty()));
+                let Some(unprocessed) = unprocessed else { break; };
+                let n_unprocessed = unprocessed.keys().map_or(0, |k| k.len());
+
+                round += 1;
+                if round >= self.retry_policy.max_attempts {
+                    return Err(Error::Throttled(format!("{n_unprocessed} keys left unprocessed").into()));
+                }
+                self.retry_metrics.add_unprocessed_keys(n_unprocessed);
+                tokio::time::sleep(self.retry_policy.delay(round)).await;
+
+                keys_and_attrs = unprocessed;
+            }
+        }
+
+        Ok(items)
+    }
+
+    async fn query(&self, input: QueryInput) -> Result<QueryOutput> {
+        let mut expr = Expression::default();
+        let pk_name = expr.name(input.index.pk_attr());
+        let pk_value = expr.value(AttributeValue::S(input.pk));
+
+        let mut key_condition = format!("{pk_name} = {pk_value}");
+        if let Some(sk) = &input.sk {
+            let sk_condition = expr.sk_condition(input.index.sk_attr(), sk);
+            key_condition = format!("{key_condition} AND {sk_condition}");
+        }
+        let (names, values) = expr.into_parts();
+
+        // more about `ddb_cli.query`:
+        // https://docs.rs/aws-sdk-dynamodb/0.21.0/aws_sdk_dynamodb/client/struct.Client.html#method.query
+        let request = self.ddb_cli
+            .query()
+            .table_name(&self.table_name)
+            .set_index_name(input.index.name().map(String::from))
+            .key_condition_expression(key_condition)
+            .set_expression_attribute_names(names)
+            .set_expression_attribute_values(values)
+            .scan_index_forward(input.scan_index_forward)
+            .set_limit(input.limit)
+            .set_exclusive_start_key(input.exclusive_start_key);
+        let result = self.send(true, || request.clone().send()).await?;
+
+        Ok(QueryOutput {
+            items: result.items.unwrap_or_default(),
+            last_evaluated_key: result.last_evaluated_key,
+        })
+    }
+
+    async fn scan(&self, input: ScanInput) -> Result<ScanOutput> {
+        let request = self.ddb_cli
+            .scan()
+            .table_name(&self.table_name)
+            .set_limit(input.limit)
+            .set_exclusive_start_key(input.exclusive_start_key);
+        let result = self.send(true, || request.clone().send()).await?;
+
+        Ok(ScanOutput {
+            items: result.items.unwrap_or_default(),
+            last_evaluated_key: result.last_evaluated_key,
+        })
+    }
+
+    async fn update_item(&self, input: UpdateItemInput) -> Result<Item> {
+        let idempotent = input.is_idempotent();
+        let mut expr = Expression::default();
+        let update = expr.update(&input.actions);
+        let condition = input.condition.map(|c| expr.condition(&c));
+        let (names, values) = expr.into_parts();
+
+        let request = self.ddb_cli
+            .update_item()
+            .table_name(&self.table_name)
+            .set_key(Some(input.key.to_item()))
+            .update_expression(update)
+            .set_condition_expression(condition)
+            .set_expression_attribute_names(names)
+            .set_expression_attribute_values(values)
+            .return_values(ReturnValue::AllNew);
+        let result = self.send(idempotent, || request.clone().send()).await?;
+
+        Ok(result.attributes.unwrap_or_default())
+    }
+
+    async fn delete_item(&self, input: DeleteItemInput) -> Result<Option<Item>> {
+        let idempotent = input.is_idempotent();
+        let mut expr = Expression::default();
+        let condition = input.condition.map(|c| expr.condition(&c));
+        let (names, values) = expr.into_parts();
+
+        let request = self.ddb_cli
+            .delete_item()
+            .table_name(&self.table_name)
+            .set_key(Some(input.key.to_item()))
+            .set_condition_expression(condition)
+            .set_expression_attribute_names(names)
+            .set_expression_attribute_values(values)
+            .return_values(ReturnValue::AllOld);
+        let result = self.send(idempotent, || request.clone().send()).await?;
+
+        Ok(result.attributes)
+    }
+
+    async fn transact_write_items(&self, items: Vec<TransactWriteItem>) -> Result<()> {
+        let items = items.into_iter()
+            .map(|write| self.transact_write_item(write))
+            .collect();
+
+        // every attempt carries the same token: DynamoDB does not apply a transaction
+        // twice for a token, and answers the retry of an applied one with a success
+        let request = self.ddb_cli
+            .transact_write_items()
+            .set_transact_items(Some(items))
+            .client_request_token(Uuid::new_v4().to_string());
+        self.send(true, || request.clone().send()).await?;
+
+        Ok(())
+    }
+}
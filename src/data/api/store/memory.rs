@@ -0,0 +1,378 @@
+use std::collections::{BTreeMap, HashSet};
+use std::sync::{Mutex, RwLock};
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use async_trait::async_trait;
+use aws_sdk_dynamodb::model::AttributeValue;
+use uuid::Uuid;
+
+use super::{
+    ContentStore,
+    Item,
+    Index,
+    Condition,
+    TransactWriteItem,
+    CONDITIONAL_CHECK_FAILED,
+    apply_update,
+    PutItemInput,
+    GetItemInput,
+    BatchGetItemsInput,
+    UpdateItemInput,
+    DeleteItemInput,
+    QueryInput,
+    QueryOutput,
+    ScanInput,
+    ScanOutput,
+};
+use super::super::result::{Error, Result};
+use super::super::retry::{with_retry, RetryMetrics, RetryPolicy, RetryStats};
+
+/// A [`ContentStore`] that keeps the whole table in memory.
+///
+/// It follows the DynamoDB semantics the API clients rely on: sparse GSIs ordered by
+/// their sort key, `Limit`/`LastEvaluatedKey` pagination and conditional writes,
+/// so the forum can run and be tested without AWS.
+///
+/// The writes are retried like the ones of a [`DynamoStore`](super::DynamoStore),
+/// see [`fail_after_writes`](MemoryStore::fail_after_writes) to test a lost response.
+///
+/// # Example:
+///
+/// ```
+/// use tokio;
+/// use valnk::data::api::store::{ContentStore, MemoryStore, Key, GetItemInput};
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let store = MemoryStore::new();
+///     let item = store.get_item(GetItemInput::new(Key::new("SUBMS#id1", "A"))).await.unwrap();
+///
+///     assert_eq!(item, None);
+/// }
+/// ```
+#[derive(Default, Debug)]
+pub struct MemoryStore {
+    items: RwLock<BTreeMap<(String, String), Item>>,
+    retry_policy: RetryPolicy,
+    retry_metrics: RetryMetrics,
+    /// The number of the next writes whose response is lost.
+    lost_responses: AtomicU32,
+    /// The client request tokens of the transactions applied so far.
+    transactions: Mutex<HashSet<String>>,
+}
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        return MemoryStore::default();
+    }
+
+    pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    /// The retries of the store so far.
+    pub fn retry_stats(&self) -> RetryStats {
+        return self.retry_metrics.stats();
+    }
+
+    /// Makes the next `n` writes fail with `Error::Transient` after they are applied,
+    /// like a request to DynamoDB that times out once it has been applied.
+    pub fn fail_after_writes(&self, n: u32) {
+        self.lost_responses.store(n, Ordering::SeqCst);
+    }
+
+    /// Applies a write with `write`, again if its response is lost and it can be sent again.
+    async fn write<T, F>(&self, idempotent: bool, mut write: F) -> Result<T>
+        where F: FnMut() -> Result<T>,
+              T: Send
+    {
+        return with_retry(&self.retry_policy, &self.retry_metrics, idempotent, || {
+            let result = write().and_then(|output| self.lose_response(output));
+            async move { result }
+        }).await;
+    }
+
+    fn lose_response<T>(&self, output: T) -> Result<T> {
+        let lost = self.lost_responses
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1))
+            .is_ok();
+        if lost {
+            return Err(Error::Transient("the response of an applied write was lost".into()));
+        }
+
+        return Ok(output);
+    }
+
+    fn put_item_once(&self, input: &PutItemInput) -> Result<()> {
+        let key = item_key(&input.item)?;
+        let mut items = self.items.write().unwrap();
+
+        ensure_condition(items.get(&key), &input.condition)?;
+        items.insert(key, input.item.clone());
+
+        Ok(())
+    }
+
+    fn update_item_once(&self, input: &UpdateItemInput) -> Result<Item> {
+        let key = (input.key.pk.clone(), input.key.sk.clone());
+        let mut items = self.items.write().unwrap();
+
+        let current = items.get(&key);
+        ensure_condition(current, &input.condition)?;
+
+        // like DynamoDB, updating a missing item creates it
+        let mut item = current.cloned().unwrap_or_else(|| input.key.to_item());
+        apply_update(&mut item, &input.actions)?;
+        items.insert(key, item.clone());
+
+        Ok(item)
+    }
+
+    fn delete_item_once(&self, input: &DeleteItemInput) -> Result<Option<Item>> {
+        let key = (input.key.pk.clone(), input.key.sk.clone());
+        let mut items = self.items.write().unwrap();
+
+        ensure_condition(items.get(&key), &input.condition)?;
+
+        Ok(items.remove(&key))
+    }
+
+    fn transact_write_items_once(&self, token: &str, writes: &[TransactWriteItem]) -> Result<()> {
+        let mut items = self.items.write().unwrap();
+        let mut transactions = self.transactions.lock().unwrap();
+        // like DynamoDB, a transaction is applied once per client request token
+        if transactions.contains(token) {
+            return Ok(());
+        }
+
+        let mut keys = vec![];
+        let mut reasons = vec![];
+        for write in writes {
+            let key = write_key(write)?;
+            if keys.contains(&key) {
+                return Err(Error::BadRequest(format!("`{}/{}` is written twice in the transaction", key.0, key.1)));
+            }
+
+            let holds = match write_condition(write) {
+                Some(cond) => check_condition(items.get(&key), cond),
+                None => true,
+            };
+            reasons.push(if holds { None } else { Some(CONDITIONAL_CHECK_FAILED.to_string()) });
+            keys.push(key);
+        }
+
+        if reasons.iter().any(Option::is_some) {
+            return Err(Error::TransactionCancelled(reasons, None));
+        }
+
+        // every new item is built before the first one is stored, so a failed update writes nothing
+        let mut changes = vec![];
+        for (key, write) in keys.into_iter().zip(writes) {
+            match write {
+                TransactWriteItem::Put(input) => changes.push((key, Some(input.item.clone()))),
+                TransactWriteItem::Update(input) => {
+                    let mut item = items.get(&key).cloned().unwrap_or_else(|| input.key.to_item());
+                    apply_update(&mut item, &input.actions)?;
+                    changes.push((key, Some(item)));
+                }
+                TransactWriteItem::Delete(_) => changes.push((key, None)),
+                TransactWriteItem::ConditionCheck(_, _) => {}
+            }
+        }
+
+        for (key, item) in changes {
+            match item {
+                Some(item) => items.insert(key, item),
+                None => items.remove(&key),
+            };
+        }
+        transactions.insert(token.to_string());
+
+        Ok(())
+    }
+}
+
+fn get_s<'i>(item: &'i Item, attr: &str) -> Option<&'i str> {
+    return match item.get(attr) {
+        Some(AttributeValue::S(s)) => Some(s.as_str()),
+        _ => None,
+    };
+}
+
+fn item_key(item: &Item) -> Result<(String, String)> {
+    let pk = get_s(item, Index::Primary.pk_attr());
+    let sk = get_s(item, Index::Primary.sk_attr());
+
+    return match (pk, sk) {
+        (Some(pk), Some(sk)) => Ok((pk.to_string(), sk.to_string())),
+        _ => Err(Error::BadRequest("the item has no string `PK`/`SK`".to_string())),
+    };
+}
+
+/// The position of an item in an index: the index sort key,
+/// ties are broken by the primary key.
+fn index_position(item: &Item, index: Index) -> Option<(String, String, String)> {
+    let sk = get_s(item, index.sk_attr())?;
+    let table_pk = get_s(item, Index::Primary.pk_attr())?;
+    let table_sk = get_s(item, Index::Primary.sk_attr())?;
+
+    return Some((sk.to_string(), table_pk.to_string(), table_sk.to_string()));
+}
+
+fn check_condition(item: Option<&Item>, cond: &Condition) -> bool {
+    return match cond {
+        Condition::AttributeExists(attr) => item.is_some_and(|it| it.contains_key(attr)),
+        Condition::AttributeNotExists(attr) => !item.is_some_and(|it| it.contains_key(attr)),
+        Condition::Equals(attr, value) => item.and_then(|it| it.get(attr)) == Some(value),
+        Condition::And(conds) => conds.iter().all(|c| check_condition(item, c)),
+    };
+}
+
+fn write_key(write: &TransactWriteItem) -> Result<(String, String)> {
+    return match write {
+        TransactWriteItem::Put(input) => item_key(&input.item),
+        TransactWriteItem::Update(input) => Ok((input.key.pk.clone(), input.key.sk.clone())),
+        TransactWriteItem::Delete(input) => Ok((input.key.pk.clone(), input.key.sk.clone())),
+        TransactWriteItem::ConditionCheck(key, _) => Ok((key.pk.clone(), key.sk.clone())),
+    };
+}
+
+fn write_condition(write: &TransactWriteItem) -> Option<&Condition> {
+    return match write {
+        TransactWriteItem::Put(input) => input.condition.as_ref(),
+        TransactWriteItem::Update(input) => input.condition.as_ref(),
+        TransactWriteItem::Delete(input) => input.condition.as_ref(),
+        TransactWriteItem::ConditionCheck(_, cond) => Some(cond),
+    };
+}
+
+fn ensure_condition(item: Option<&Item>, cond: &Option<Condition>) -> Result<()> {
+    if let Some(c) = cond {
+        if !check_condition(item, c) {
+            return Err(Error::ConditionFailed(format!("the conditional request failed: {c:?}").into()));
+        }
+    }
+
+    return Ok(());
+}
+
+#[async_trait]
+impl ContentStore for MemoryStore {
+    async fn put_item(&self, input: PutItemInput) -> Result<()> {
+        return self.write(input.is_idempotent(), || self.put_item_once(&input)).await;
+    }
+
+    async fn get_item(&self, input: GetItemInput) -> Result<Option<Item>> {
+        let items = self.items.read().unwrap();
+
+        Ok(items.get(&(input.key.pk, input.key.sk)).cloned())
+    }
+
+    async fn batch_get_items(&self, input: BatchGetItemsInput) -> Result<Vec<Item>> {
+        let items = self.items.read().unwrap();
+
+        let mut keys: Vec<(String, String)> = input.keys.into_iter()
+            .map(|k| (k.pk, k.sk))
+            .collect();
+        keys.sort();
+        keys.dedup();
+
+        Ok(keys.iter().filter_map(|k| items.get(k).cloned()).collect())
+    }
+
+    async fn query(&self, input: QueryInput) -> Result<QueryOutput> {
+        let index = input.index;
+        let items = self.items.read().unwrap();
+
+        // like a sparse GSI, items without the index keys are not in the index
+        let mut matched: Vec<((String, String, String), &Item)> = items.values()
+            .filter(|it| get_s(it, index.pk_attr()) == Some(input.pk.as_str()))
+            .filter(|it| match (&input.sk, get_s(it, index.sk_attr())) {
+                (_, None) => false,
+                (None, Some(_)) => true,
+                (Some(cond), Some(sk)) => cond.matches(sk),
+            })
+            .filter_map(|it| index_position(it, index).map(|pos| (pos, it)))
+            .collect();
+
+        matched.sort_by(|a, b| a.0.cmp(&b.0));
+        if !input.scan_index_forward {
+            matched.reverse();
+        }
+
+        if let Some(start_key) = &input.exclusive_start_key {
+            let start = index_position(start_key, index).ok_or(
+                Error::BadRequest("invalid exclusive start key".to_string())
+            )?;
+
+            matched.retain(|(pos, _)| match input.scan_index_forward {
+                true => *pos > start,
+                false => *pos < start,
+            });
+        }
+
+        let mut last_key = None;
+        if let Some(limit) = input.limit {
+            let limit = usize::try_from(limit)
+                .map_err(|_| Error::BadRequest(format!("invalid limit: {limit}")))?;
+
+            if matched.len() >= limit {
+                matched.truncate(limit);
+                last_key = matched.last().map(|(_, it)| index.start_key(it));
+            }
+        }
+
+        Ok(QueryOutput {
+            items: matched.into_iter().map(|(_, it)| it.clone()).collect(),
+            last_evaluated_key: last_key,
+        })
+    }
+
+    async fn scan(&self, input: ScanInput) -> Result<ScanOutput> {
+        let items = self.items.read().unwrap();
+
+        let mut start = None;
+        if let Some(start_key) = &input.exclusive_start_key {
+            start = Some(item_key(start_key)?);
+        }
+
+        let mut scanned: Vec<&Item> = items.iter()
+            .filter(|(key, _)| match &start {
+                Some(s) => *key > s,
+                None => true,
+            })
+            .map(|(_, it)| it)
+            .collect();
+
+        let mut last_key = None;
+        if let Some(limit) = input.limit {
+            let limit = usize::try_from(limit)
+                .map_err(|_| Error::BadRequest(format!("invalid limit: {limit}")))?;
+
+            if scanned.len() >= limit {
+                scanned.truncate(limit);
+                last_key = scanned.last().map(|it| Index::Primary.start_key(it));
+            }
+        }
+
+        Ok(ScanOutput {
+            items: scanned.into_iter().cloned().collect(),
+            last_evaluated_key: last_key,
+        })
+    }
+
+    async fn update_item(&self, input: UpdateItemInput) -> Result<Item> {
+        return self.write(input.is_idempotent(), || self.update_item_once(&input)).await;
+    }
+
+    async fn delete_item(&self, input: DeleteItemInput) -> Result<Option<Item>> {
+        return self.write(input.is_idempotent(), || self.delete_item_once(&input)).await;
+    }
+
+    async fn transact_write_items(&self, writes: Vec<TransactWriteItem>) -> Result<()> {
+        let token = Uuid::new_v4().to_string();
+
+        return self.write(true, || self.transact_write_items_once(&token, &writes)).await;
+    }
+}
@@ -0,0 +1,389 @@
+//! The schema of the content table: its keys, global secondary indexes and TTL attribute,
+//! declared in code so a table can be created, or brought up to date, from it.
+//!
+//! The changes are additive only: a missing table or index is created and a missing TTL
+//! is enabled, while a table whose keys or indexes differ from the schema is reported
+//! as a conflict and left as it is, since fixing it would drop data.
+
+use std::time::Duration;
+
+use aws_sdk_dynamodb::Client as DynamodbClient;
+use aws_sdk_dynamodb::types::SdkError;
+use aws_sdk_dynamodb::model::{
+    AttributeDefinition,
+    BillingMode,
+    CreateGlobalSecondaryIndexAction,
+    GlobalSecondaryIndex,
+    GlobalSecondaryIndexUpdate,
+    IndexStatus,
+    KeySchemaElement,
+    KeyType,
+    Projection as DynamodbProjection,
+    ProjectionType,
+    ScalarAttributeType,
+    TableDescription,
+    TableStatus,
+    TimeToLiveDescription,
+    TimeToLiveSpecification,
+    TimeToLiveStatus,
+};
+
+use super::Index;
+use super::dynamo::map_sdk_err;
+use super::super::result::{Error, Result};
+
+/// The TTL attribute of the table, the unix timestamp an item expires at,
+/// e.g. the `expires_at` of an [`IdempotencyRecord`](crate::data::model::idempotency::IdempotencyRecord).
+pub const TTL_ATTR: &str = "expires_at";
+
+/// How often a table that is being created or updated is described, until it is active.
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// How long a table is waited for, the creation of an index on a large table takes longer.
+const DEFAULT_WAIT_TIMEOUT: Duration = Duration::from_secs(600);
+
+/// The attributes of the items copied into an index.
+#[derive(Clone, PartialEq, Debug)]
+pub enum Projection {
+    All,
+    KeysOnly,
+    /// The keys and these attributes.
+    Include(Vec<String>),
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct IndexSchema {
+    pub name: String,
+    pub pk_attr: String,
+    pub sk_attr: String,
+    pub projection: Projection,
+}
+
+impl IndexSchema {
+    /// The schema of one of the global secondary indexes of the store.
+    pub fn of(index: Index, projection: Projection) -> Option<Self> {
+        let name = index.name()?;
+
+        return Some(Self {
+            name: name.to_string(),
+            pk_attr: index.pk_attr().to_string(),
+            sk_attr: index.sk_attr().to_string(),
+            projection,
+        });
+    }
+}
+
+/// A change to bring a table in line with its schema.
+#[derive(Clone, PartialEq, Debug)]
+pub enum SchemaChange {
+    CreateTable,
+    CreateIndex(String),
+    EnableTtl(String),
+}
+
+/// The declaration of a table, see [`TableSchema::content`].
+#[derive(Clone, PartialEq, Debug)]
+pub struct TableSchema {
+    pub table_name: String,
+    pub pk_attr: String,
+    pub sk_attr: String,
+    pub indexes: Vec<IndexSchema>,
+    pub ttl_attr: Option<String>,
+    /// How long [`apply`](TableSchema::apply) waits for the table to be active.
+    pub wait_timeout: Duration,
+}
+
+impl TableSchema {
+    /// The schema of the content table: the `PK`/`SK` primary key, the `GSI1`, `GSI2`,
+    /// `GSI3` and `GSI4` indexes that project the whole items, since the lists and the purge
+    /// read the entities from them, and the `expires_at` TTL.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use valnk::data::api::store::schema::TableSchema;
+    ///
+    /// let schema = TableSchema::content("valnk-content");
+    ///
+    /// let names: Vec<&str> = schema.indexes.iter().map(|i| i.name.as_str()).collect();
+    /// assert_eq!(names, vec!["GSI1", "GSI2", "GSI3", "GSI4"]);
+    /// assert_eq!(schema.ttl_attr.as_deref(), Some("expires_at"));
+    /// ```
+    pub fn content(table_name: impl Into<String>) -> Self {
+        let indexes = [Index::Gsi1, Index::Gsi2, Index::Gsi3, Index::Gsi4].into_iter()
+            .filter_map(|index| IndexSchema::of(index, Projection::All))
+            .collect();
+
+        return Self {
+            table_name: table_name.into(),
+            pk_attr: Index::Primary.pk_attr().to_string(),
+            sk_attr: Index::Primary.sk_attr().to_string(),
+            indexes,
+            ttl_attr: Some(TTL_ATTR.to_string()),
+            wait_timeout: DEFAULT_WAIT_TIMEOUT,
+        };
+    }
+
+    pub fn with_wait_timeout(mut self, wait_timeout: Duration) -> Self {
+        self.wait_timeout = wait_timeout;
+        self
+    }
+
+    /// The changes that bring the described table in line with the schema, in order,
+    /// none if it is up to date. `table` is `None` if there is no such table.
+    ///
+    /// Fails with `Error::Conflict` if the keys or the indexes of the table differ
+    /// from the schema, or if another attribute is its TTL.
+    pub fn plan(&self, table: Option<&TableDescription>, ttl: Option<&TimeToLiveDescription>) -> Result<Vec<SchemaChange>> {
+        let mut changes = vec![];
+        let ttl_change = self.ttl_attr.clone().map(SchemaChange::EnableTtl);
+
+        let Some(table) = table else {
+            changes.push(SchemaChange::CreateTable);
+            changes.extend(ttl_change);
+            return Ok(changes);
+        };
+
+        let key_schema = key_schema_of(table.key_schema());
+        if key_schema != (Some(self.pk_attr.as_str()), Some(self.sk_attr.as_str())) {
+            return Err(Error::Conflict(format!(
+                "the table `{}` is keyed by `{key_schema:?}`, not by `{}`/`{}`",
+                self.table_name, self.pk_attr, self.sk_attr,
+            )));
+        }
+
+        let existing = table.global_secondary_indexes().unwrap_or_default();
+        for index in self.indexes.iter() {
+            let Some(current) = existing.iter().find(|i| i.index_name() == Some(index.name.as_str())) else {
+                changes.push(SchemaChange::CreateIndex(index.name.clone()));
+                continue;
+            };
+
+            let key_schema = key_schema_of(current.key_schema());
+            let projection = current.projection().and_then(projection_of);
+            if key_schema != (Some(index.pk_attr.as_str()), Some(index.sk_attr.as_str()))
+                || projection.as_ref() != Some(&index.projection)
+            {
+                return Err(Error::Conflict(format!(
+                    "the index `{}` of `{}` is `{key_schema:?}` projecting `{projection:?}`, not the one of the schema",
+                    index.name, self.table_name,
+                )));
+            }
+        }
+
+        let enabled = ttl
+            .filter(|d| matches!(d.time_to_live_status(), Some(TimeToLiveStatus::Enabled | TimeToLiveStatus::Enabling)))
+            .and_then(|d| d.attribute_name());
+        match (&self.ttl_attr, enabled) {
+            (Some(attr), Some(current)) if attr != current => {
+                return Err(Error::Conflict(format!(
+                    "the TTL attribute of `{}` is `{current}`, not `{attr}`", self.table_name,
+                )));
+            }
+            (Some(_), None) => changes.extend(ttl_change),
+            _ => {}
+        }
+
+        return Ok(changes);
+    }
+
+    /// The changes that [`apply`](TableSchema::apply) would make to the table.
+    pub async fn diff(&self, ddb_cli: &DynamodbClient) -> Result<Vec<SchemaChange>> {
+        let table = self.describe(ddb_cli).await?;
+        let ttl = match table {
+            Some(_) => self.describe_ttl(ddb_cli).await?,
+            None => None,
+        };
+
+        return self.plan(table.as_ref(), ttl.as_ref());
+    }
+
+    /// Creates the table, or the indexes and the TTL it misses, and waits until it is active,
+    /// returns the changes made. Applying the schema again is a no-op.
+    ///
+    /// # Example:
+    ///
+    /// ```no_run
+    /// use tokio;
+    /// use valnk::data::api::store::schema::TableSchema;
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let shared_config = aws_config::load_from_env().await;
+    ///     let aws_cli = aws_sdk_dynamodb::Client::new(&shared_config);
+    ///
+    ///     let changes = TableSchema::content("valnk-content").apply(&aws_cli).await.unwrap();
+    ///     println!("{changes:?}");
+    /// }
+    /// ```
+    pub async fn apply(&self, ddb_cli: &DynamodbClient) -> Result<Vec<SchemaChange>> {
+        let changes = self.diff(ddb_cli).await?;
+
+        for change in changes.iter() {
+            match change {
+                SchemaChange::CreateTable => self.create_table(ddb_cli).await?,
+                SchemaChange::CreateIndex(name) => self.create_index(ddb_cli, name).await?,
+                SchemaChange::EnableTtl(attr) => self.enable_ttl(ddb_cli, attr).await?,
+            }
+            // an index can only be added to an active table, one at a time
+            self.wait_active(ddb_cli).await?;
+        }
+
+        return Ok(changes);
+    }
+
+    async fn describe(&self, ddb_cli: &DynamodbClient) -> Result<Option<TableDescription>> {
+        let response = ddb_cli.describe_table()
+            .table_name(&self.table_name)
+            .send()
+            .await;
+
+        return match response {
+            Ok(output) => Ok(output.table().cloned()),
+            Err(SdkError::ServiceError { ref err, .. }) if err.is_resource_not_found_exception() => Ok(None),
+            Err(e) => Err(map_sdk_err(e)),
+        };
+    }
+
+    async fn describe_ttl(&self, ddb_cli: &DynamodbClient) -> Result<Option<TimeToLiveDescription>> {
+        let output = ddb_cli.describe_time_to_live()
+            .table_name(&self.table_name)
+            .send()
+            .await
+            .map_err(map_sdk_err)?;
+
+        return Ok(output.time_to_live_description().cloned());
+    }
+
+    /// The definitions of the key attributes of the table and of the indexes, all strings.
+    fn attribute_definitions(&self) -> Vec<AttributeDefinition> {
+        let mut attrs = vec![self.pk_attr.as_str(), self.sk_attr.as_str()];
+        for index in self.indexes.iter() {
+            attrs.extend([index.pk_attr.as_str(), index.sk_attr.as_str()]);
+        }
+        attrs.sort();
+        attrs.dedup();
+
+        return attrs.into_iter()
+            .map(|attr| AttributeDefinition::builder()
+                .attribute_name(attr)
+                .attribute_type(ScalarAttributeType::S)
+                .build())
+            .collect();
+    }
+
+    fn index(&self, name: &str) -> Result<&IndexSchema> {
+        return self.indexes.iter()
+            .find(|i| i.name == name)
+            .ok_or_else(|| Error::BadRequest(format!("no index `{name}` in the schema")));
+    }
+
+    async fn create_table(&self, ddb_cli: &DynamodbClient) -> Result<()> {
+        let mut request = ddb_cli.create_table()
+            .table_name(&self.table_name)
+            .billing_mode(BillingMode::PayPerRequest)
+            .set_attribute_definitions(Some(self.attribute_definitions()))
+            .set_key_schema(Some(key_schema(&self.pk_attr, &self.sk_attr)));
+        for index in self.indexes.iter() {
+            request = request.global_secondary_indexes(GlobalSecondaryIndex::builder()
+                .index_name(&index.name)
+                .set_key_schema(Some(key_schema(&index.pk_attr, &index.sk_attr)))
+                .projection(projection(&index.projection))
+                .build());
+        }
+
+        request.send().await.map_err(map_sdk_err)?;
+        Ok(())
+    }
+
+    async fn create_index(&self, ddb_cli: &DynamodbClient, name: &str) -> Result<()> {
+        let index = self.index(name)?;
+        let create = CreateGlobalSecondaryIndexAction::builder()
+            .index_name(&index.name)
+            .set_key_schema(Some(key_schema(&index.pk_attr, &index.sk_attr)))
+            .projection(projection(&index.projection))
+            .build();
+
+        ddb_cli.update_table()
+            .table_name(&self.table_name)
+            .set_attribute_definitions(Some(self.attribute_definitions()))
+            .global_secondary_index_updates(GlobalSecondaryIndexUpdate::builder().create(create).build())
+            .send()
+            .await
+            .map_err(map_sdk_err)?;
+        Ok(())
+    }
+
+    async fn enable_ttl(&self, ddb_cli: &DynamodbClient, attr: &str) -> Result<()> {
+        let spec = TimeToLiveSpecification::builder()
+            .attribute_name(attr)
+            .enabled(true)
+            .build();
+
+        ddb_cli.update_time_to_live()
+            .table_name(&self.table_name)
+            .time_to_live_specification(spec)
+            .send()
+            .await
+            .map_err(map_sdk_err)?;
+        Ok(())
+    }
+
+    /// Waits until the table and all of its indexes are active,
+    /// fails with `Error::Unknown` after `wait_timeout`.
+    async fn wait_active(&self, ddb_cli: &DynamodbClient) -> Result<()> {
+        let deadline = tokio::time::Instant::now() + self.wait_timeout;
+        loop {
+            let table = self.describe(ddb_cli).await?
+                .ok_or_else(|| Error::NotFound(self.table_name.clone()))?;
+            let indexes_active = table.global_secondary_indexes().unwrap_or_default()
+                .iter()
+                .all(|i| i.index_status() == Some(&IndexStatus::Active));
+            if table.table_status() == Some(&TableStatus::Active) && indexes_active {
+                return Ok(());
+            }
+
+            if tokio::time::Instant::now() >= deadline {
+                return Err(Error::Unknown(format!("the table `{}` is not active after {:?}", self.table_name, self.wait_timeout)));
+            }
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+    }
+}
+
+fn key_schema(pk_attr: &str, sk_attr: &str) -> Vec<KeySchemaElement> {
+    return vec![
+        KeySchemaElement::builder().attribute_name(pk_attr).key_type(KeyType::Hash).build(),
+        KeySchemaElement::builder().attribute_name(sk_attr).key_type(KeyType::Range).build(),
+    ];
+}
+
+/// The hash and the range attributes of a described key schema.
+fn key_schema_of(elements: Option<&[KeySchemaElement]>) -> (Option<&str>, Option<&str>) {
+    let attr_of = |key_type: KeyType| elements.unwrap_or_default()
+        .iter()
+        .find(|e| e.key_type() == Some(&key_type))
+        .and_then(|e| e.attribute_name());
+
+    return (attr_of(KeyType::Hash), attr_of(KeyType::Range));
+}
+
+fn projection(proj: &Projection) -> DynamodbProjection {
+    return match proj {
+        Projection::All => DynamodbProjection::builder().projection_type(ProjectionType::All).build(),
+        Projection::KeysOnly => DynamodbProjection::builder().projection_type(ProjectionType::KeysOnly).build(),
+        Projection::Include(attrs) => DynamodbProjection::builder()
+            .projection_type(ProjectionType::Include)
+            .set_non_key_attributes(Some(attrs.clone()))
+            .build(),
+    };
+}
+
+fn projection_of(proj: &DynamodbProjection) -> Option<Projection> {
+    return match proj.projection_type()? {
+        ProjectionType::All => Some(Projection::All),
+        ProjectionType::KeysOnly => Some(Projection::KeysOnly),
+        ProjectionType::Include => Some(Projection::Include(proj.non_key_attributes().unwrap_or_default().to_vec())),
+        _ => None,
+    };
+}
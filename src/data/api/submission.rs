@@ -1,21 +1,125 @@
-use serde::{Serialize, Deserialize};
-use serde_dynamo;
-
-use aws_config;
-use aws_sdk_dynamodb::Client as DynamodbClient;
 use aws_sdk_dynamodb::model::AttributeValue;
-use aws_sdk_dynamodb::types::SdkError;
-
+use chrono::{DateTime, Utc};
+use serde_dynamo;
 
+use crate::data::model::entity::EntityType;
+use crate::data::model::idempotency::request_digest;
 use crate::data::model::submission::{
     Submission,
-    SUBMISSION_TAG,
+    SubmissionId,
+    PrimaryKey,
     TopicIndexKey,
+    TopicTimeIndexKey,
 };
 
 use super::result::{Error, Result};
 use super::cursor::Cursor;
+use super::page::{query_page, paginate, ItemStream, PageLimits};
+use super::lookup::{get_entity, batch_get_entities};
+use super::idempotency::{create_once, IdempotentCreate};
+use super::edit::{edit_entity, Edit};
+use super::tombstone::{soft_delete, undelete};
+use super::store::{
+    ContentStore,
+    Index,
+    Key,
+    SkCondition,
+    Condition,
+    UpdateAction,
+    TransactWriteItem,
+    PutItemInput,
+    QueryInput,
+    condition_failed,
+};
+
+
+#[derive(Clone, Debug)]
+pub struct GetItemInput {
+    pub id: SubmissionId,
+    pub consistent_read: Option<bool>,
+}
+
+impl GetItemInput {
+    pub fn new(id: SubmissionId) -> Self {
+        Self {
+            id,
+            consistent_read: None,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct DeleteItemInput {
+    pub id: SubmissionId,
+    /// The user who deletes the submission, the author or a moderator.
+    pub deleted_by: String,
+}
+
+impl DeleteItemInput {
+    pub fn new(id: SubmissionId, deleted_by: impl Into<String>) -> Self {
+        Self {
+            id,
+            deleted_by: deleted_by.into(),
+        }
+    }
+}
 
+#[derive(Clone, Debug)]
+pub struct UndeleteItemInput {
+    pub id: SubmissionId,
+}
+
+impl UndeleteItemInput {
+    pub fn new(id: SubmissionId) -> Self {
+        Self {
+            id,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct BatchGetItemsInput {
+    pub ids: Vec<SubmissionId>,
+    pub consistent_read: Option<bool>,
+}
+
+impl BatchGetItemsInput {
+    pub fn new(ids: Vec<SubmissionId>) -> Self {
+        Self {
+            ids,
+            consistent_read: None,
+        }
+    }
+}
+
+/// An edit of the fields of a submission, the `None` fields are left as they are.
+#[derive(Clone, Debug)]
+pub struct EditItemInput {
+    pub id: SubmissionId,
+    /// The user who edits the submission, the author or a moderator.
+    pub editor_id: String,
+    /// The `updated_at` of the submission the edit is based on.
+    pub expected_updated_at: DateTime<Utc>,
+    /// Moves the submission to another topic.
+    pub topic: Option<String>,
+    pub title: Option<String>,
+    pub url: Option<String>,
+    pub text: Option<String>,
+}
+
+impl EditItemInput {
+    pub fn new(id: SubmissionId, editor_id: impl Into<String>, expected_updated_at: DateTime<Utc>) -> Self {
+        Self {
+            id,
+            editor_id: editor_id.into(),
+            expected_updated_at,
+            topic: None,
+            title: None,
+            url: None,
+            text: None,
+        }
+    }
+}
 
 #[derive(Clone, Debug)]
 pub struct ListItemsByTopicInput {
@@ -40,6 +144,8 @@ impl ListItemsByTopicInput {
 pub struct ListItemsByTopicOutput {
     pub items: Vec<Submission>,
     pub next_cursor: Option<Cursor>,
+    /// The cursor of the page before this one, `None` on the first page.
+    pub prev_cursor: Option<Cursor>,
 }
 
 impl ListItemsByTopicOutput {
@@ -47,37 +153,67 @@ impl ListItemsByTopicOutput {
         Self {
             items,
             next_cursor: None,
+            prev_cursor: None,
         }
     }
 }
 
 
+fn key_of(id: &SubmissionId) -> Key {
+    let pk = PrimaryKey::new(id);
+    return Key::new(pk.pk, pk.sk);
+}
+
+/// The put of a new submission, it never replaces an existing one.
+fn create_put(subm: &Submission) -> Result<PutItemInput> {
+    let item = serde_dynamo::to_item(subm)
+        .map_err(Error::InvalidInputData)?;
+
+    let mut put = PutItemInput::new(item);
+    put.condition = Some(Condition::AttributeNotExists(Index::Primary.pk_attr().to_string()));
+
+    return Ok(put);
+}
+
+fn already_exists(pk: &str) -> Error {
+    return Error::Conflict(format!("the submission `{pk}` already exists"));
+}
+
 #[derive(Debug)]
 pub struct Client<'c> {
-    ddb_cli: &'c DynamodbClient,
-    table_name: String,
+    store: &'c dyn ContentStore,
+    page_limits: PageLimits,
 }
 
 impl<'c> Client<'c> {
-    pub fn new(ddb_cli: &'c DynamodbClient, table_name: impl Into<String>) -> Self {
+    pub fn new(store: &'c dyn ContentStore) -> Self {
         return Self {
-            ddb_cli,
-            table_name: table_name.into(),
+            store,
+            page_limits: PageLimits::default(),
         };
     }
 
+    pub fn with_page_limits(mut self, page_limits: PageLimits) -> Self {
+        self.page_limits = page_limits;
+        self
+    }
+
+    /// Creates a submission, fails with `Error::Conflict` if another one with the same id exists.
+    ///
     /// # Example:
     ///
     /// ```no_run
     /// use tokio;
     /// use valnk::data::api::submission::*;
+    /// use valnk::data::api::store::{dynamodb_client, DynamoStore};
     /// use valnk::data::model::submission as subm_model;
     ///
     /// #[tokio::main]
     /// async fn main() {
     ///     let shared_config = aws_config::load_from_env().await;
-    ///     let aws_cli = aws_sdk_dynamodb::Client::new(&shared_config);
-    ///     let cli = Client::new(&aws_cli, "valnk-content");
+    ///     let aws_cli = dynamodb_client(&shared_config, None).unwrap();
+    ///     let store = DynamoStore::new(aws_cli, "valnk-content");
+    ///     let cli = Client::new(&store);
     ///     let subm = subm_model::SubmissionBuilder::new()
     ///         .with_author_id("py0x")
     ///         .with_topic("news")
@@ -92,96 +228,266 @@ impl<'c> Client<'c> {
     /// }
     /// ```
     pub async fn create_item(&self, subm: Submission) -> Result<()> {
-        let item = serde_dynamo::to_item(subm)
-            .map_err(Error::InvalidInputData)?;
+        let put = create_put(&subm)?;
 
-        self.ddb_cli
-            .put_item()
-            .table_name(&self.table_name)
-            .set_item(Some(item))
-            .send()
-            .await
-            .map_err(|e| Error::ServerError(e.to_string()))?;
-
-        Ok(())
+        return match self.store.put_item(put).await {
+            Err(Error::ConditionFailed(_)) => Err(already_exists(&subm.primary_key.pk)),
+            result => result,
+        };
     }
 
+    /// Creates a submission unless one has been created with the same `idempotency_key`
+    /// by its author, returns the submission created with the key, now or earlier.
+    ///
     /// # Example:
     ///
     /// ```no_run
     /// use tokio;
     /// use valnk::data::api::submission::*;
+    /// use valnk::data::api::store::{dynamodb_client, DynamoStore};
     /// use valnk::data::model::submission as subm_model;
     ///
     /// #[tokio::main]
     /// async fn main() {
     ///     let shared_config = aws_config::load_from_env().await;
-    ///     let aws_cli = aws_sdk_dynamodb::Client::new(&shared_config);
-    ///     let cli = Client::new(&aws_cli, "valnk-content");
-    ///
-    ///     let mut input = ListItemsByTopicInput::new("my-topic");
-    ///     input.limit = Some(10);
+    ///     let aws_cli = dynamodb_client(&shared_config, None).unwrap();
+    ///     let store = DynamoStore::new(aws_cli, "valnk-content");
+    ///     let cli = Client::new(&store);
+    ///     let subm = subm_model::SubmissionBuilder::new()
+    ///         .with_author_id("py0x")
+    ///         .with_topic("news")
+    ///         .with_title("create_item_idempotent test example")
+    ///         .with_text("hello create_item_idempotent")
+    ///         .with_url("")
+    ///         .with_ranking_score(10)
+    ///         .build()
+    ///         .unwrap();
     ///
-    ///     let output = cli.list_items_by_topic(input).await.unwrap();
+    ///     // e.g. the `Idempotency-Key` header of the request
+    ///     let created = cli.create_item_idempotent(subm, "8e03978e").await.unwrap();
     /// }
     /// ```
-    pub async fn list_items_by_topic(&self, input: ListItemsByTopicInput) -> Result<ListItemsByTopicOutput> {
-        let mut limit = 30;
-        let mut reverse = false;
-        let mut exclusive_start_key = None;
+    pub async fn create_item_idempotent(&self, subm: Submission, idempotency_key: &str) -> Result<Submission> {
+        let put = create_put(&subm)?;
 
-        if let Some(lm) = input.limit {
-            limit = lm;
-        }
+        let create = IdempotentCreate {
+            author_id: subm.author_id.clone(),
+            key: idempotency_key.to_string(),
+            target_type: EntityType::Submission,
+            target_id: subm.id.clone(),
+            target: key_of(&subm.id),
+            request_digest: request_digest(&[
+                ("topic", &subm.topic),
+                ("title", &subm.title),
+                ("url", &subm.url),
+                ("text", &subm.text),
+            ]),
+        };
 
-        if let Some(rv) = input.reverse {
-            reverse = rv;
-        }
+        return match create_once(self.store, create, vec![TransactWriteItem::Put(put)]).await {
+            Ok(Some(original)) => Ok(original),
+            Ok(None) => Ok(subm),
+            Err(Error::TransactionCancelled(reasons, _)) if condition_failed(&reasons, 0) => {
+                Err(already_exists(&subm.primary_key.pk))
+            }
+            Err(e) => Err(e),
+        };
+    }
 
-        if let Some(cur) = input.start_cursor {
-            let lk = cur.try_into()
-                .map_err(Error::InvalidInputData)?;
-            exclusive_start_key = Some(lk);
+    /// Edits the title, url, text or topic of a submission and returns it edited.
+    ///
+    /// The edit only applies if the submission has not been edited since
+    /// `input.expected_updated_at`, so two editors never overwrite each other:
+    /// fails with `Error::Conflict` if it has, and with `Error::NotFound` if
+    /// the submission does not exist. A new topic moves its `TopicIndexKey`
+    /// and `TopicTimeIndexKey` along, the `AuthorIndexKey` does not depend on the editable fields.
+    /// Every edit keeps the replaced values in a `Revision` of the submission.
+    ///
+    /// # Example:
+    ///
+    /// ```no_run
+    /// use tokio;
+    /// use valnk::data::api::submission::*;
+    /// use valnk::data::api::store::{dynamodb_client, DynamoStore};
+    /// use valnk::data::model::submission::SubmissionId;
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let shared_config = aws_config::load_from_env().await;
+    ///     let aws_cli = dynamodb_client(&shared_config, None).unwrap();
+    ///     let store = DynamoStore::new(aws_cli, "valnk-content");
+    ///     let cli = Client::new(&store);
+    ///
+    ///     let subm = cli.get_item(GetItemInput::new(SubmissionId::from("subm-id").unwrap())).await.unwrap();
+    ///
+    ///     let mut input = EditItemInput::new(subm.id, "py0x", subm.updated_at);
+    ///     input.title = Some("a better title".to_string());
+    ///
+    ///     let edited = cli.edit_item(input).await.unwrap();
+    /// }
+    /// ```
+    pub async fn edit_item(&self, input: EditItemInput) -> Result<Submission> {
+        let mut fields = vec![];
+        let mut derived = vec![];
+        if let Some(topic) = input.topic {
+            derived.push(UpdateAction::Set(Index::Gsi1.pk_attr().to_string(), AttributeValue::S(TopicIndexKey::pk(&topic))));
+            derived.push(UpdateAction::Set(Index::Gsi3.pk_attr().to_string(), AttributeValue::S(TopicTimeIndexKey::pk(&topic))));
+            fields.push(("topic", topic));
         }
-
-        // more about `ddb_cli.query`:
-        // https://docs.rs/aws-sdk-dynamodb/0.21.0/aws_sdk_dynamodb/client/struct.Client.html#method.query
-        let results = self.ddb_cli
-            .query()
-            .table_name(&self.table_name)
-            .index_name(TopicIndexKey::INDEX_NAME)
-            .key_condition_expression("GSI1_PK = :topic_pk and begins_with(GSI1_SK, :tag_pfx)")
-            .expression_attribute_values(
-                ":topic_pk", AttributeValue::S(TopicIndexKey::pk(&input.topic)),
-            )
-            .expression_attribute_values(
-                ":tag_pfx", AttributeValue::S(TopicIndexKey::sk_prefix()),
-            )
-            .scan_index_forward(reverse)
-            .limit(limit)
-            .set_exclusive_start_key(exclusive_start_key)
-            .send()
-            .await
-            .map_err(|e| Error::ServerError(e.to_string()))?;
-
-
-        let mut subms: Vec<Submission> = vec![];
-        if let Some(items) = results.items() {
-            subms = serde_dynamo::from_items(items.to_vec())
-                .map_err(Error::InvalidOutputData)?;
+        if let Some(title) = input.title {
+            fields.push(("title", title));
+        }
+        if let Some(url) = input.url {
+            fields.push(("url", url));
+        }
+        if let Some(text) = input.text {
+            fields.push(("text", text));
         }
-        let mut output = ListItemsByTopicOutput::new(subms);
 
+        let edit = Edit {
+            target_type: EntityType::Submission,
+            target_id: input.id.clone(),
+            target: key_of(&input.id),
+            editor_id: input.editor_id,
+            expected_updated_at: input.expected_updated_at,
+            fields,
+            derived,
+        };
 
-        if let Some(lk) = results.last_evaluated_key() {
-            let next_cursor = Cursor::try_from(lk.to_owned())
-                .map_err(Error::InvalidOutputData)?;
+        return edit_entity(self.store, edit).await;
+    }
 
-            output.next_cursor = Some(next_cursor);
-        }
+    /// Deletes a submission: it is kept, with its counters, as a tombstone whose content
+    /// is blank in the reads, so its comments stay in place. Deleting it again is a no-op,
+    /// fails with `Error::NotFound` if it does not exist.
+    ///
+    /// # Example:
+    ///
+    /// ```no_run
+    /// use tokio;
+    /// use valnk::data::api::submission::*;
+    /// use valnk::data::api::store::{dynamodb_client, DynamoStore};
+    /// use valnk::data::model::submission::SubmissionId;
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let shared_config = aws_config::load_from_env().await;
+    ///     let aws_cli = dynamodb_client(&shared_config, None).unwrap();
+    ///     let store = DynamoStore::new(aws_cli, "valnk-content");
+    ///     let cli = Client::new(&store);
+    ///
+    ///     let input = DeleteItemInput::new(SubmissionId::from("subm-id").unwrap(), "py0x");
+    ///     cli.delete_item(input).await.unwrap();
+    /// }
+    /// ```
+    pub async fn delete_item(&self, input: DeleteItemInput) -> Result<()> {
+        return soft_delete(self.store, key_of(&input.id), &input.deleted_by).await;
+    }
+
+    /// Restores a deleted submission, for the moderators, fails with `Error::NotFound`
+    /// if it does not exist and with `Error::Conflict` if it has been purged.
+    pub async fn undelete_item(&self, input: UndeleteItemInput) -> Result<()> {
+        return undelete(self.store, key_of(&input.id)).await;
+    }
+
+    /// Returns the submission with the id, fails with `Error::NotFound` if there is none.
+    ///
+    /// # Example:
+    ///
+    /// ```no_run
+    /// use tokio;
+    /// use valnk::data::api::submission::*;
+    /// use valnk::data::api::store::{dynamodb_client, DynamoStore};
+    /// use valnk::data::model::submission::SubmissionId;
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let shared_config = aws_config::load_from_env().await;
+    ///     let aws_cli = dynamodb_client(&shared_config, None).unwrap();
+    ///     let store = DynamoStore::new(aws_cli, "valnk-content");
+    ///     let cli = Client::new(&store);
+    ///
+    ///     let mut input = GetItemInput::new(SubmissionId::from("subm-id").unwrap());
+    ///     input.consistent_read = Some(true);
+    ///
+    ///     let submission = cli.get_item(input).await.unwrap();
+    /// }
+    /// ```
+    pub async fn get_item(&self, input: GetItemInput) -> Result<Submission> {
+        let consistent_read = input.consistent_read.unwrap_or(false);
+
+        return get_entity(self.store, key_of(&input.id), consistent_read).await;
+    }
+
+    /// Returns the submissions with the ids, in the order of the ids,
+    /// fails with `Error::NotFound` if any of them is missing.
+    pub async fn batch_get_items(&self, input: BatchGetItemsInput) -> Result<Vec<Submission>> {
+        let consistent_read = input.consistent_read.unwrap_or(false);
+        let keys = input.ids.iter().map(key_of).collect();
+
+        return batch_get_entities(self.store, keys, consistent_read).await;
+    }
+
+    /// # Example:
+    ///
+    /// ```no_run
+    /// use tokio;
+    /// use valnk::data::api::submission::*;
+    /// use valnk::data::api::store::{dynamodb_client, DynamoStore};
+    /// use valnk::data::model::submission as subm_model;
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let shared_config = aws_config::load_from_env().await;
+    ///     let aws_cli = dynamodb_client(&shared_config, None).unwrap();
+    ///     let store = DynamoStore::new(aws_cli, "valnk-content");
+    ///     let cli = Client::new(&store);
+    ///
+    ///     let mut input = ListItemsByTopicInput::new("my-topic");
+    ///     input.limit = Some(10);
+    ///
+    ///     let output = cli.list_items_by_topic(input).await.unwrap();
+    /// }
+    /// ```
+    pub async fn list_items_by_topic(&self, input: ListItemsByTopicInput) -> Result<ListItemsByTopicOutput> {
+        let mut query = QueryInput::new(Index::Gsi1, TopicIndexKey::pk(&input.topic));
+        query.sk = Some(SkCondition::BeginsWith(TopicIndexKey::sk_prefix()));
+        query.scan_index_forward = input.reverse.unwrap_or(false);
+        query.limit = Some(self.page_limits.resolve(input.limit)?);
+
+        let page = query_page(self.store, query, input.start_cursor).await?;
+
+        let mut output = ListItemsByTopicOutput::new(page.items);
+        output.next_cursor = page.next_cursor;
+        output.prev_cursor = page.prev_cursor;
 
         Ok(output)
     }
 
-    // pub async fn get_items_by_author_id() -> Result<()> {}
-}
\ No newline at end of file
+    /// Streams the submissions of a topic, following the cursors from `input.start_cursor`,
+    /// `input.limit` is the size of the pages and `max_items` caps the number of items.
+    ///
+    /// # Example:
+    ///
+    /// ```no_run
+    /// use futures::StreamExt;
+    /// use valnk::data::api::submission::*;
+    ///
+    /// async fn print_titles(cli: &Client<'_>) {
+    ///     let mut items = cli.stream_items_by_topic(ListItemsByTopicInput::new("news"), Some(1000));
+    ///     while let Some(subm) = items.next().await {
+    ///         println!("{}", subm.unwrap().title);
+    ///     }
+    /// }
+    /// ```
+    pub fn stream_items_by_topic(&self, input: ListItemsByTopicInput, max_items: Option<usize>) -> ItemStream<'_, Submission> {
+        return paginate(input.start_cursor.clone(), max_items, move |cursor| {
+            let mut input = input.clone();
+            input.start_cursor = cursor;
+            async move {
+                let output = self.list_items_by_topic(input).await?;
+                Ok((output.items, output.next_cursor))
+            }
+        });
+    }
+}
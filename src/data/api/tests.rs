@@ -1,39 +1,1724 @@
 use std::collections::HashMap;
 use super::submission;
-use chrono::{DateTime, TimeZone, NaiveDateTime, Utc};
+use super::comment;
+use super::reply;
+use super::author;
+use super::vote;
+use super::ranking;
+use super::migration;
+use super::revision;
+use super::purge;
+use super::thread;
+use super::store::*;
+use super::store::schema::{self, SchemaChange, TableSchema};
+use super::cursor::CursorCodec;
+use super::page::PageLimits;
+use super::Valnk;
+use crate::config::ValnkConfig;
+use super::result::Error;
+use crate::data::model::submission::{SubmissionBuilder, SubmissionId};
+use crate::data::model::comment::{CommentBuilder, CommentId};
+use crate::data::model::reply::{ReplyBuilder, ReplyId};
+use crate::data::model::entity::{Entity, EntityId, EntityType};
+use crate::data::model::idempotency::{IdempotencyRecord, IDEMPOTENCY_TTL_HOURS};
+use crate::data::model::thread::MAX_THREAD_DEPTH;
+use chrono::{TimeZone, Utc};
 
 use tokio;
+use futures::StreamExt;
 
-use serde_dynamo;
-use aws_sdk_dynamodb;
-use aws_config;
+use aws_sdk_dynamodb::model::AttributeValue;
+
+fn new_submission(topic: &str, score: i64, title: &str) -> crate::data::model::submission::Submission {
+    let created_at = Utc.timestamp_opt(1234, 0).unwrap();
+
+    return SubmissionBuilder::new()
+        .with_author_id("py0x")
+        .with_topic(topic)
+        .with_ranking_score(score)
+        .with_title(title)
+        .with_url("")
+        .with_text("")
+        .with_created_at(created_at)
+        .build()
+        .unwrap();
+}
+
+async fn create_submission(store: &MemoryStore, id: &str) {
+    let subm = SubmissionBuilder::new()
+        .with_id(SubmissionId::from(id).unwrap())
+        .with_author_id("py0x")
+        .with_topic("news")
+        .with_ranking_score(0)
+        .with_title(id)
+        .with_url("")
+        .with_text("")
+        .build()
+        .unwrap();
+
+    submission::Client::new(store).create_item(subm).await.unwrap();
+}
+
+async fn create_comment(store: &MemoryStore, id: &str, submission_id: &str) {
+    let comm = CommentBuilder::new()
+        .with_id(CommentId::from(id).unwrap())
+        .with_submission_id(SubmissionId::from(submission_id).unwrap())
+        .with_author_id("py0x")
+        .with_ranking_score(0)
+        .with_text(id)
+        .build()
+        .unwrap();
+
+    comment::Client::new(store).create_item(comm).await.unwrap();
+}
 
 #[tokio::test]
 async fn test_list_items_by_topic() {
-    let shared_config = aws_config::load_from_env().await;
-    let aws_cli = aws_sdk_dynamodb::Client::new(&shared_config);
+    let store = MemoryStore::new();
+    let cli = submission::Client::new(&store);
 
-    let cli = submission::Client::new(&aws_cli, "valnk-content");
+    cli.create_item(new_submission("news", 10, "ten")).await.unwrap();
+    cli.create_item(new_submission("news", 30, "thirty")).await.unwrap();
+    cli.create_item(new_submission("news", 20, "twenty")).await.unwrap();
+    cli.create_item(new_submission("misc", 40, "other topic")).await.unwrap();
 
 
     let mut input = submission::ListItemsByTopicInput::new("news");
     input.limit = Some(1);
 
+    let output = cli.list_items_by_topic(input).await.unwrap();
+    let titles: Vec<&str> = output.items.iter().map(|s| s.title.as_str()).collect();
+    assert_eq!(titles, vec!["thirty"]);
+
+
+    let mut input2 = submission::ListItemsByTopicInput::new("news");
+    input2.limit = Some(2);
+    input2.start_cursor = output.next_cursor;
+
+    let output2 = cli.list_items_by_topic(input2).await.unwrap();
+    let titles: Vec<&str> = output2.items.iter().map(|s| s.title.as_str()).collect();
+    assert_eq!(titles, vec!["twenty", "ten"]);
+
+
+    let mut input3 = submission::ListItemsByTopicInput::new("news");
+    input3.limit = Some(2);
+    input3.start_cursor = output2.next_cursor;
+
+    let output3 = cli.list_items_by_topic(input3).await.unwrap();
+    assert!(output3.items.is_empty());
+    assert!(output3.next_cursor.is_none());
+}
+
+#[tokio::test]
+async fn test_list_items_by_topic_reverse() {
+    let store = MemoryStore::new();
+    let cli = submission::Client::new(&store);
+
+    cli.create_item(new_submission("news", 10, "ten")).await.unwrap();
+    cli.create_item(new_submission("news", 30, "thirty")).await.unwrap();
+    cli.create_item(new_submission("news", 20, "twenty")).await.unwrap();
+
+    let mut input = submission::ListItemsByTopicInput::new("news");
+    input.reverse = Some(true);
 
     let output = cli.list_items_by_topic(input).await.unwrap();
+    let titles: Vec<&str> = output.items.iter().map(|s| s.title.as_str()).collect();
+    assert_eq!(titles, vec!["ten", "twenty", "thirty"]);
+    assert!(output.next_cursor.is_none());
+}
 
-    println!("output: {:#?}", output);
-    println!("========");
+#[tokio::test]
+async fn test_list_items_by_topic_backward() {
+    let store = MemoryStore::new();
+    let cli = submission::Client::new(&store);
 
+    for score in [10, 20, 30, 40, 50] {
+        cli.create_item(new_submission("news", score, &score.to_string())).await.unwrap();
+    }
 
-    let mut input2 = submission::ListItemsByTopicInput::new("news");
+    let list = |start_cursor| {
+        let mut input = submission::ListItemsByTopicInput::new("news");
+        input.limit = Some(2);
+        input.start_cursor = start_cursor;
+        cli.list_items_by_topic(input)
+    };
+    let titles = |output: &submission::ListItemsByTopicOutput| -> Vec<String> {
+        output.items.iter().map(|s| s.title.clone()).collect()
+    };
+
+    let page1 = list(None).await.unwrap();
+    assert_eq!(titles(&page1), vec!["50", "40"]);
+    assert!(page1.prev_cursor.is_none());
+
+    let page2 = list(page1.next_cursor).await.unwrap();
+    assert_eq!(titles(&page2), vec!["30", "20"]);
+
+    let page3 = list(page2.next_cursor.clone()).await.unwrap();
+    assert_eq!(titles(&page3), vec!["10"]);
+    assert!(page3.next_cursor.is_none());
+
+
+    let back2 = list(page3.prev_cursor).await.unwrap();
+    assert_eq!(titles(&back2), vec!["30", "20"]);
+
+    let back1 = list(back2.prev_cursor).await.unwrap();
+    assert_eq!(titles(&back1), vec!["50", "40"]);
+
+    // like a `LastEvaluatedKey`, a full page may have a cursor to an empty one
+    let back0 = list(back1.prev_cursor.clone()).await.unwrap();
+    assert!(back0.items.is_empty());
+    assert!(back0.prev_cursor.is_none());
+
+    // and forward again from a page reached backwards
+    let again2 = list(back1.next_cursor).await.unwrap();
+    assert_eq!(titles(&again2), vec!["30", "20"]);
+    assert_eq!(again2.next_cursor, page2.next_cursor);
+}
+
+#[tokio::test]
+async fn test_page_limits() {
+    let store = MemoryStore::new();
+    let cli = submission::Client::new(&store).with_page_limits(PageLimits::new(2, 3));
+    for i in 0..5 {
+        cli.create_item(new_submission("news", i, &format!("title{i}"))).await.unwrap();
+    }
+
+    let output = cli.list_items_by_topic(submission::ListItemsByTopicInput::new("news")).await.unwrap();
+    assert_eq!(output.items.len(), 2);
+
+    let mut input = submission::ListItemsByTopicInput::new("news");
+    input.limit = Some(500);
+    let output = cli.list_items_by_topic(input).await.unwrap();
+    assert_eq!(output.items.len(), 3);
+
+    let mut input = submission::ListItemsByTopicInput::new("news");
+    input.limit = Some(0);
+    assert!(matches!(cli.list_items_by_topic(input).await, Err(Error::BadRequest(_))));
+}
+
+#[tokio::test]
+async fn test_valnk_shared_across_tasks() {
+    let config = ValnkConfig {
+        default_page_limit: 2,
+        ..ValnkConfig::default()
+    };
+    let store = MemoryStore::new();
+    create_submission(&store, "s1").await;
+    let valnk = Valnk::new(store, config);
+
+    let tasks: Vec<_> = (0..3).map(|i| {
+        let valnk = valnk.clone();
+        tokio::spawn(async move {
+            let user_id = format!("user{i}");
+            let vote = vote::VoteInput::new(&user_id, EntityType::Submission, SubmissionId::from("s1").unwrap());
+            valnk.votes().vote(vote).await.unwrap();
+
+            let comm = CommentBuilder::new()
+                .with_submission_id(SubmissionId::from("s1").unwrap())
+                .with_author_id(&user_id)
+                .with_ranking_score(0)
+                .with_text("hello")
+                .build()
+                .unwrap();
+            valnk.comments().create_item(comm).await.unwrap();
+        })
+    }).collect();
+    for task in tasks {
+        task.await.unwrap();
+    }
+
+    // the clones share the store, and the page limits of the config
+    let subm = valnk.submissions().get_item(submission::GetItemInput::new(SubmissionId::from("s1").unwrap())).await.unwrap();
+    assert_eq!((subm.n_votes, subm.n_comments), (3, 3));
+    let output = valnk.comments()
+        .list_items_by_submission(comment::ListItemsBySubmissionInput::new(SubmissionId::from("s1").unwrap()))
+        .await
+        .unwrap();
+    assert_eq!(output.items.len(), 2);
+    let output = valnk.users().list_items_by_author(author::ListItemsByAuthorInput::new("user1")).await.unwrap();
+    assert_eq!(output.items.len(), 1);
+}
+
+#[tokio::test]
+async fn test_stream_items_by_topic() {
+    let store = MemoryStore::new();
+    let cli = submission::Client::new(&store);
+
+    for score in [10, 20, 30, 40, 50] {
+        cli.create_item(new_submission("news", score, &score.to_string())).await.unwrap();
+    }
+
+    let mut input = submission::ListItemsByTopicInput::new("news");
+    input.limit = Some(2);
+
+    let titles: Vec<String> = cli.stream_items_by_topic(input.clone(), None)
+        .map(|s| s.unwrap().title)
+        .collect()
+        .await;
+    assert_eq!(titles, vec!["50", "40", "30", "20", "10"]);
+
+    let titles: Vec<String> = cli.stream_items_by_topic(input.clone(), Some(3))
+        .map(|s| s.unwrap().title)
+        .collect()
+        .await;
+    assert_eq!(titles, vec!["50", "40", "30"]);
+
+
+    // the error of a page ends the stream
+    let page = cli.list_items_by_topic(input).await.unwrap();
+    let mut input = submission::ListItemsByTopicInput::new("misc");
+    input.start_cursor = page.next_cursor;
+
+    let results: Vec<_> = cli.stream_items_by_topic(input, None).collect().await;
+    assert_eq!(results.len(), 1);
+    assert!(matches!(results[0], Err(Error::InvalidCursor(_))));
+}
+
+#[tokio::test]
+async fn test_stream_items_by_author() {
+    let store = MemoryStore::new();
+    create_author_activities(&store).await;
+    let cli = author::Client::new(&store);
+
+    let mut input = author::ListItemsByAuthorInput::new("py0x");
+    input.limit = Some(2);
+
+    let ids: Vec<String> = cli.stream_items_by_author(input, None)
+        .map(|a| activity_id(&a.unwrap()).to_string())
+        .collect()
+        .await;
+    assert_eq!(ids, vec!["c2", "s2", "r1", "c1", "s1"]);
+}
+
+#[tokio::test]
+async fn test_cursor_tokens() {
+    let store = MemoryStore::new();
+    let cli = submission::Client::new(&store);
+    let codec = CursorCodec::new(b"a secret of at least 32 bytes....");
+
+    cli.create_item(new_submission("news", 10, "ten")).await.unwrap();
+    cli.create_item(new_submission("news", 20, "twenty")).await.unwrap();
+    cli.create_item(new_submission("misc", 30, "other topic")).await.unwrap();
+
+    let mut input = submission::ListItemsByTopicInput::new("news");
+    input.limit = Some(1);
+    let output = cli.list_items_by_topic(input).await.unwrap();
+    let token = codec.encode(&output.next_cursor.unwrap());
+    assert!(token.chars().all(|c| c.is_ascii_alphanumeric() || "-_.".contains(c)));
+
+
+    // the token is resumed from
+    let mut input = submission::ListItemsByTopicInput::new("news");
+    input.start_cursor = Some(codec.decode(&token).unwrap());
+    let output = cli.list_items_by_topic(input).await.unwrap();
+    let titles: Vec<&str> = output.items.iter().map(|s| s.title.as_str()).collect();
+    assert_eq!(titles, vec!["ten"]);
+
+
+    // a tampered or forged token
+    let (payload, signature) = token.split_once('.').unwrap();
+    let tampered = format!("{}A.{signature}", payload);
+    assert!(matches!(codec.decode(&tampered), Err(Error::InvalidCursor(_))));
+    let other_codec = CursorCodec::new(b"another secret of at least 32 bytes");
+    assert!(matches!(other_codec.decode(&token), Err(Error::InvalidCursor(_))));
+    assert!(matches!(codec.decode("not a token"), Err(Error::InvalidCursor(_))));
+
+
+    // an expired token
+    let expiring = CursorCodec::new(b"a secret of at least 32 bytes....")
+        .with_max_age(chrono::Duration::seconds(-1));
+    assert!(matches!(expiring.decode(&token), Err(Error::InvalidCursor(_))));
+
+
+    // a cursor reused on another topic, or in the other direction
+    let mut input = submission::ListItemsByTopicInput::new("misc");
+    input.start_cursor = Some(codec.decode(&token).unwrap());
+    let result = cli.list_items_by_topic(input).await;
+    assert!(matches!(result, Err(Error::InvalidCursor(_))));
+
+    let mut input = submission::ListItemsByTopicInput::new("news");
+    input.reverse = Some(true);
+    input.start_cursor = Some(codec.decode(&token).unwrap());
+    let result = cli.list_items_by_topic(input).await;
+    assert!(matches!(result, Err(Error::InvalidCursor(_))));
+}
+
+#[tokio::test]
+async fn test_list_comments_by_submission() {
+    let store = MemoryStore::new();
+    let cli = comment::Client::new(&store);
+    create_submission(&store, "subm1").await;
+    create_submission(&store, "subm2").await;
+
+    let subm_id = SubmissionId::from("subm1").unwrap();
+    for (id, subm, score) in [("c1", "subm1", 5), ("c2", "subm1", 50), ("c3", "subm1", 20), ("c4", "subm2", 99)] {
+        let comm = CommentBuilder::new()
+            .with_id(CommentId::from(id).unwrap())
+            .with_submission_id(SubmissionId::from(subm).unwrap())
+            .with_author_id("py0x")
+            .with_ranking_score(score)
+            .with_text(id)
+            .build()
+            .unwrap();
+        cli.create_item(comm).await.unwrap();
+    }
+
+    let mut input = comment::ListItemsBySubmissionInput::new(subm_id.clone());
+    input.limit = Some(2);
+
+    let output = cli.list_items_by_submission(input).await.unwrap();
+    let ids: Vec<&str> = output.items.iter().map(|c| c.id.as_ref()).collect();
+    assert_eq!(ids, vec!["c2", "c3"]);
+
+    let mut input2 = comment::ListItemsBySubmissionInput::new(subm_id.clone());
     input2.limit = Some(2);
     input2.start_cursor = output.next_cursor;
 
+    let output2 = cli.list_items_by_submission(input2).await.unwrap();
+    let ids: Vec<&str> = output2.items.iter().map(|c| c.id.as_ref()).collect();
+    assert_eq!(ids, vec!["c1"]);
+    assert!(output2.next_cursor.is_none());
 
-    let output2 = cli.list_items_by_topic(input2).await.unwrap();
+    let mut input3 = comment::ListItemsBySubmissionInput::new(subm_id);
+    input3.reverse = Some(true);
+
+    let output3 = cli.list_items_by_submission(input3).await.unwrap();
+    let ids: Vec<&str> = output3.items.iter().map(|c| c.id.as_ref()).collect();
+    assert_eq!(ids, vec!["c1", "c3", "c2"]);
+
+    let input = comment::GetItemInput::new(CommentId::from("c4").unwrap());
+    let comm = cli.get_item(input).await.unwrap();
+    assert_eq!(comm.ranking_score, 99);
+}
+
+#[tokio::test]
+async fn test_get_items_by_id() {
+    let store = MemoryStore::new();
+    let cli = submission::Client::new(&store);
+
+    let subms = [new_submission("news", 1, "one"), new_submission("news", 2, "two")];
+    for subm in subms.iter() {
+        cli.create_item(subm.clone()).await.unwrap();
+    }
+
+    let mut input = submission::GetItemInput::new(subms[1].id.clone());
+    input.consistent_read = Some(true);
+    assert_eq!(cli.get_item(input).await.unwrap(), subms[1]);
+
+    let missing = SubmissionId::from("missing").unwrap();
+    let err = cli.get_item(submission::GetItemInput::new(missing.clone())).await.unwrap_err();
+    assert!(matches!(err, super::result::Error::NotFound(_)));
+
+    let ids = vec![subms[1].id.clone(), subms[0].id.clone(), subms[1].id.clone()];
+    let found = cli.batch_get_items(submission::BatchGetItemsInput::new(ids)).await.unwrap();
+    assert_eq!(found, vec![subms[1].clone(), subms[0].clone(), subms[1].clone()]);
+
+    let ids = vec![subms[0].id.clone(), missing];
+    let err = cli.batch_get_items(submission::BatchGetItemsInput::new(ids)).await.unwrap_err();
+    assert!(matches!(err, super::result::Error::NotFound(ref id) if id == "SUBMS#missing"));
+}
+
+#[tokio::test]
+async fn test_list_replies() {
+    let store = MemoryStore::new();
+    let cli = reply::Client::new(&store);
+    create_submission(&store, "subm1").await;
+    for comm in ["c1", "c2", "c10"] {
+        create_comment(&store, comm, "subm1").await;
+    }
+
+    let subm_id = SubmissionId::from("subm1").unwrap();
+    let replies = [("r1", "c1", 300), ("r2", "c2", 100), ("r3", "c1", 200), ("r4", "c10", 50)];
+    for (id, comm, ts) in replies {
+        let reply = ReplyBuilder::new()
+            .with_id(ReplyId::from(id).unwrap())
+            .with_submission_id(subm_id.clone())
+            .with_comment_id(CommentId::from(comm).unwrap())
+            .with_author_id("py0x")
+            .with_text(id)
+            .with_created_at(Utc.timestamp_opt(ts, 0).unwrap())
+            .build()
+            .unwrap();
+        cli.create_item(reply).await.unwrap();
+    }
+
+    // `c10` shares the `c1` prefix, but not the `c1#` one
+    let input = reply::ListRepliesByCommentInput::new(subm_id.clone(), CommentId::from("c1").unwrap());
+    let output = cli.list_replies_by_comment(input).await.unwrap();
+    let ids: Vec<&str> = output.items.iter().map(|r| r.id.as_ref()).collect();
+    assert_eq!(ids, vec!["r3", "r1"]);
+
+    let mut input = reply::ListRepliesBySubmissionInput::new(subm_id);
+    input.limit = Some(3);
+    let output = cli.list_replies_by_submission(input).await.unwrap();
+    let ids: Vec<&str> = output.items.iter().map(|r| r.id.as_ref()).collect();
+    assert_eq!(ids, vec!["r3", "r1", "r4"]);
+    assert!(output.next_cursor.is_some());
+}
+
+#[tokio::test]
+async fn test_reply_trees() {
+    let store = MemoryStore::new();
+    create_submission(&store, "s1").await;
+    let subm_id = SubmissionId::from("s1").unwrap();
+    for (id, ts) in [("c2", 200), ("c1", 100)] {
+        let comm = CommentBuilder::new()
+            .with_id(CommentId::from(id).unwrap())
+            .with_submission_id(subm_id.clone())
+            .with_author_id("py0x")
+            .with_ranking_score(0)
+            .with_text(id)
+            .with_created_at(Utc.timestamp_opt(ts, 0).unwrap())
+            .build()
+            .unwrap();
+        comment::Client::new(&store).create_item(comm).await.unwrap();
+    }
+
+    let cli = reply::Client::new(&store);
+    let new_reply = |id: &str, comm: &str, parent: Option<&str>, ts: i64| {
+        let mut builder = ReplyBuilder::new()
+            .with_id(ReplyId::from(id).unwrap())
+            .with_submission_id(subm_id.clone())
+            .with_comment_id(CommentId::from(comm).unwrap())
+            .with_author_id("py0x")
+            .with_text(id)
+            .with_created_at(Utc.timestamp_opt(ts, 0).unwrap());
+        if let Some(parent) = parent {
+            builder = builder.with_parent_id(ReplyId::from(parent).unwrap());
+        }
+        builder.build().unwrap()
+    };
+    let replies = [("r1", "c1", None, 300), ("r2", "c1", Some("r1"), 400), ("r3", "c1", None, 350), ("r4", "c2", None, 310)];
+    for (id, comm, parent, ts) in replies {
+        cli.create_item(new_reply(id, comm, parent, ts)).await.unwrap();
+    }
+
+    // depth-first, the siblings by time
+    let thread_cli = thread::Client::new(&store);
+    let output = thread_cli.list_thread(thread::ListThreadInput::new(subm_id.clone())).await.unwrap();
+    let ids: Vec<&str> = output.items.iter().map(|e| e.id().as_ref()).collect();
+    assert_eq!(ids, vec!["c1", "r1", "r2", "r3", "c2", "r4"]);
+
+    let r1 = cli.get_item(reply::GetItemInput::new(ReplyId::from("r1").unwrap())).await.unwrap();
+    let mut input = thread::ListThreadInput::new(subm_id.clone());
+    input.subtree_path = Some(r1.path.clone());
+    input.reverse = Some(true);
+    let output = thread_cli.list_thread(input).await.unwrap();
+    let ids: Vec<&str> = output.items.iter().map(|e| e.id().as_ref()).collect();
+    assert_eq!(ids, vec!["r2", "r1"]);
+
+    let mut input = thread::ListThreadInput::new(subm_id.clone());
+    input.limit = Some(4);
+    let ids: Vec<String> = thread_cli.stream_thread(input, None)
+        .map(|e| e.unwrap().id().to_string())
+        .collect()
+        .await;
+    assert_eq!(ids, vec!["c1", "r1", "r2", "r3", "c2", "r4"]);
+
+    // the comment and the replies count their direct replies, the submission all of them
+    let comm = comment::Client::new(&store).get_item(comment::GetItemInput::new(CommentId::from("c1").unwrap())).await.unwrap();
+    assert_eq!((comm.n_replies, r1.n_replies), (2, 1));
+    let subm = submission::Client::new(&store).get_item(submission::GetItemInput::new(subm_id.clone())).await.unwrap();
+    assert_eq!(subm.n_comments, 6);
+
+    // the parent must be in the tree of the comment
+    let err = cli.create_item(new_reply("r5", "c2", Some("r1"), 500)).await.unwrap_err();
+    assert!(matches!(err, Error::Conflict(_)));
+    let err = cli.create_item(new_reply("r5", "c1", Some("missing"), 500)).await.unwrap_err();
+    assert!(matches!(err, Error::Conflict(_)));
+
+    // `r2` is at depth 2
+    let mut parent = String::from("r2");
+    for depth in 3..=MAX_THREAD_DEPTH {
+        let id = format!("d{depth}");
+        cli.create_item(new_reply(&id, "c1", Some(&parent), 1000 + depth as i64)).await.unwrap();
+        parent = id;
+    }
+    let err = cli.create_item(new_reply("too-deep", "c1", Some(&parent), 2000)).await.unwrap_err();
+    assert!(matches!(err, Error::BadRequest(_)));
+
+    // the items written before the threads are placed by the migration
+    let r3_key = Key::new("REPLY#r3", "A");
+    let placed = store.get_item(GetItemInput::new(r3_key.clone())).await.unwrap().unwrap();
+    for key in [Key::new("COMMT#c1", "A"), r3_key.clone()] {
+        let update = UpdateItemInput::new(key, ["path", "GSI3_PK", "GSI3_SK"].map(|a| UpdateAction::Remove(a.to_string())).to_vec());
+        store.update_item(update).await.unwrap();
+    }
+    migration::Client::new(&store).migrate_all_keys().await.unwrap();
+    assert_eq!(store.get_item(GetItemInput::new(r3_key)).await.unwrap().unwrap(), placed);
+}
+
+#[tokio::test]
+async fn test_load_thread() {
+    let store = MemoryStore::new();
+    create_submission(&store, "s1").await;
+    create_submission(&store, "s2").await;
+    let subm_id = SubmissionId::from("s1").unwrap();
+    // created in this order, for the order of their paths
+    let comments = [("c1", "s1", 5, 100), ("c2", "s1", 10, 101), ("c3", "s1", -3, 102), ("other", "s2", 100, 103)];
+    for (id, subm, score, ts) in comments {
+        let comm = CommentBuilder::new()
+            .with_id(CommentId::from(id).unwrap())
+            .with_submission_id(SubmissionId::from(subm).unwrap())
+            .with_author_id("py0x")
+            .with_ranking_score(score)
+            .with_text(id)
+            .with_created_at(Utc.timestamp_opt(ts, 0).unwrap())
+            .build()
+            .unwrap();
+        comment::Client::new(&store).create_item(comm).await.unwrap();
+    }
+
+    let cli = reply::Client::new(&store);
+    let replies = [("r1", "c1", None, 300), ("r2", "c1", Some("r1"), 400), ("r3", "c1", None, 200), ("r4", "c2", None, 310)];
+    for (id, comm, parent, ts) in replies {
+        let mut builder = ReplyBuilder::new()
+            .with_id(ReplyId::from(id).unwrap())
+            .with_submission_id(subm_id.clone())
+            .with_comment_id(CommentId::from(comm).unwrap())
+            .with_author_id("py0x")
+            .with_text(id)
+            .with_created_at(Utc.timestamp_opt(ts, 0).unwrap());
+        if let Some(parent) = parent {
+            builder = builder.with_parent_id(ReplyId::from(parent).unwrap());
+        }
+        cli.create_item(builder.build().unwrap()).await.unwrap();
+    }
+    cli.delete_item(reply::DeleteItemInput::new(ReplyId::from("r1").unwrap(), "py0x")).await.unwrap();
+
+    let mut input = thread::LoadThreadInput::new(subm_id);
+    input.collapse_depth = Some(1);
+    input.collapse_below_score = Some(0);
+    let thread = thread::Client::new(&store).load_thread(input).await.unwrap();
+    assert_eq!(thread.submission.id.as_ref(), "s1");
+    assert_eq!(thread.n_nodes, 7);
+    assert!(thread.invalid_items.is_empty());
+
+    fn shape(node: &thread::ThreadNode) -> String {
+        let children: Vec<String> = node.children.iter().map(shape).collect();
+        return match children.is_empty() {
+            true => node.entity.id().to_string(),
+            false => format!("{}({})", node.entity.id(), children.join(" ")),
+        };
+    }
+    let shapes: Vec<String> = thread.comments.iter().map(shape).collect();
+    assert_eq!(shapes, vec!["c2(r4)", "c1(r3 r1(r2))", "c3"]);
+
+    let c1 = &thread.comments[1];
+    let r1 = &c1.children[1];
+    assert_eq!((c1.depth, c1.n_children, c1.n_descendants, c1.collapsed), (0, 2, 3, false));
+    assert_eq!((r1.depth, r1.n_children, r1.n_descendants, r1.collapsed), (1, 1, 1, true));
+    assert!(!c1.children[0].collapsed);
+    assert!(thread.comments[2].collapsed);
+
+    // the deleted reply stays in its tree, blank
+    match &r1.entity {
+        Entity::Reply(reply) => assert!(reply.is_deleted() && reply.text.is_empty()),
+        e => panic!("unexpected {e:?}"),
+    }
+
+    assert!(!thread.truncated);
+
+    // the nodes are read in path order, the latest trees and subtrees are left out first
+    let mut input = thread::LoadThreadInput::new(SubmissionId::from("s1").unwrap());
+    input.max_nodes = Some(3);
+    let thread = thread::Client::new(&store).with_page_limits(PageLimits::new(2, 2)).load_thread(input).await.unwrap();
+    let shapes: Vec<String> = thread.comments.iter().map(shape).collect();
+    assert_eq!(shapes, vec!["c1(r3 r1)"]);
+    assert_eq!(thread.n_nodes, 3);
+    assert!(thread.truncated);
+
+    // a discussion of exactly `max_nodes` nodes is whole
+    let mut input = thread::LoadThreadInput::new(SubmissionId::from("s1").unwrap());
+    input.max_nodes = Some(7);
+    let thread = thread::Client::new(&store).with_page_limits(PageLimits::new(2, 2)).load_thread(input).await.unwrap();
+    assert_eq!(thread.n_nodes, 7);
+    assert!(!thread.truncated);
+
+    let err = thread::Client::new(&store).load_thread(thread::LoadThreadInput::new(SubmissionId::from("missing").unwrap())).await.unwrap_err();
+    assert!(matches!(err, Error::NotFound(_)));
+}
+
+async fn create_author_activities(store: &MemoryStore) {
+    let subm_cli = submission::Client::new(store);
+    let comm_cli = comment::Client::new(store);
+    let reply_cli = reply::Client::new(store);
+
+    for (id, ts) in [("s1", 100), ("s2", 400)] {
+        let subm = SubmissionBuilder::new()
+            .with_id(SubmissionId::from(id).unwrap())
+            .with_author_id("py0x")
+            .with_topic("news")
+            .with_ranking_score(0)
+            .with_title(id)
+            .with_url("")
+            .with_text("")
+            .with_created_at(Utc.timestamp_opt(ts, 0).unwrap())
+            .build()
+            .unwrap();
+        subm_cli.create_item(subm).await.unwrap();
+    }
+
+    for (id, ts) in [("c1", 200), ("c2", 500)] {
+        let comm = CommentBuilder::new()
+            .with_id(CommentId::from(id).unwrap())
+            .with_submission_id(SubmissionId::from("s1").unwrap())
+            .with_author_id("py0x")
+            .with_ranking_score(0)
+            .with_text(id)
+            .with_created_at(Utc.timestamp_opt(ts, 0).unwrap())
+            .build()
+            .unwrap();
+        comm_cli.create_item(comm).await.unwrap();
+    }
+
+    let reply = ReplyBuilder::new()
+        .with_id(ReplyId::from("r1").unwrap())
+        .with_submission_id(SubmissionId::from("s1").unwrap())
+        .with_comment_id(CommentId::from("c1").unwrap())
+        .with_author_id("py0x")
+        .with_text("r1")
+        .with_created_at(Utc.timestamp_opt(300, 0).unwrap())
+        .build()
+        .unwrap();
+    reply_cli.create_item(reply).await.unwrap();
+}
+
+fn activity_id(entity: &Entity) -> &str {
+    return entity.id().as_ref();
+}
+
+#[tokio::test]
+async fn test_list_items_by_author_interleaved() {
+    let store = MemoryStore::new();
+    create_author_activities(&store).await;
+    let cli = author::Client::new(&store);
+
+    let mut ids = vec![];
+    let mut cursor = None;
+    loop {
+        let mut input = author::ListItemsByAuthorInput::new("py0x");
+        input.limit = Some(2);
+        input.start_cursor = cursor;
+
+        let output = cli.list_items_by_author(input).await.unwrap();
+        assert!(output.items.len() <= 2);
+        ids.extend(output.items.iter().map(|a| activity_id(a).to_string()));
+
+        cursor = output.next_cursor;
+        if cursor.is_none() {
+            break;
+        }
+    }
+    assert_eq!(ids, vec!["c2", "s2", "r1", "c1", "s1"]);
+
+    let mut input = author::ListItemsByAuthorInput::new("py0x");
+    input.reverse = Some(true);
+    input.since = Some(Utc.timestamp_opt(200, 0).unwrap());
+    input.until = Some(Utc.timestamp_opt(400, 0).unwrap());
+
+    let output = cli.list_items_by_author(input).await.unwrap();
+    let ids: Vec<&str> = output.items.iter().map(activity_id).collect();
+    assert_eq!(ids, vec!["c1", "r1", "s2"]);
+    assert!(output.next_cursor.is_none());
+}
+
+#[tokio::test]
+async fn test_list_items_by_author_corrupt_item() {
+    let store = MemoryStore::new();
+    create_author_activities(&store).await;
+    let cli = author::Client::new(&store);
+
+    // the only reply fails to decode, its stream must still move past it
+    let corrupt = UpdateItemInput::new(Key::new("REPLY#r1", "A"), vec![UpdateAction::Remove("text".to_string())]);
+    store.update_item(corrupt).await.unwrap();
+
+    let mut ids = vec![];
+    let mut n_invalid = 0;
+    let mut cursor = None;
+    for _ in 0..10 {
+        let mut input = author::ListItemsByAuthorInput::new("py0x");
+        input.limit = Some(1);
+        input.start_cursor = cursor;
+
+        let output = cli.list_items_by_author(input).await.unwrap();
+        ids.extend(output.items.iter().map(|a| activity_id(a).to_string()));
+        n_invalid += output.invalid_items.len();
+
+        cursor = output.next_cursor;
+        if cursor.is_none() {
+            break;
+        }
+    }
+    assert!(cursor.is_none());
+    assert_eq!(ids, vec!["c2", "s2", "c1", "s1"]);
+    assert_eq!(n_invalid, 1);
+}
+
+#[tokio::test]
+async fn test_list_items_by_author_undecodable_page() {
+    let store = MemoryStore::new();
+    create_author_activities(&store).await;
+    let cli = author::Client::new(&store);
+
+    // the first page of the replies, two of them, fails to decode,
+    // the reply behind it is newer than the submission `s2`
+    let reply_cli = reply::Client::new(&store);
+    for (id, ts) in [("r2", 600), ("r3", 590), ("r4", 450)] {
+        let reply = ReplyBuilder::new()
+            .with_id(ReplyId::from(id).unwrap())
+            .with_submission_id(SubmissionId::from("s1").unwrap())
+            .with_comment_id(CommentId::from("c1").unwrap())
+            .with_author_id("py0x")
+            .with_text(id)
+            .with_created_at(Utc.timestamp_opt(ts, 0).unwrap())
+            .build()
+            .unwrap();
+        reply_cli.create_item(reply).await.unwrap();
+    }
+    for id in ["r2", "r3"] {
+        let corrupt = UpdateItemInput::new(Key::new(format!("REPLY#{id}"), "A"), vec![UpdateAction::Remove("text".to_string())]);
+        store.update_item(corrupt).await.unwrap();
+    }
+
+    let mut pages = vec![];
+    let mut n_invalid = 0;
+    let mut cursor = None;
+    for _ in 0..10 {
+        let mut input = author::ListItemsByAuthorInput::new("py0x");
+        input.limit = Some(2);
+        input.start_cursor = cursor;
+
+        let output = cli.list_items_by_author(input).await.unwrap();
+        pages.push(output.items.iter().map(|a| activity_id(a).to_string()).collect::<Vec<_>>());
+        n_invalid += output.invalid_items.len();
+
+        cursor = output.next_cursor;
+        if cursor.is_none() {
+            break;
+        }
+    }
+    assert_eq!(pages, vec![vec!["c2", "r4"], vec!["s2", "r1"], vec!["c1", "s1"]]);
+    assert_eq!(n_invalid, 2);
+}
+
+#[tokio::test]
+async fn test_list_items_by_author_backward() {
+    let store = MemoryStore::new();
+    create_author_activities(&store).await;
+    let cli = author::Client::new(&store);
+
+    let list = |start_cursor| {
+        let mut input = author::ListItemsByAuthorInput::new("py0x");
+        input.limit = Some(2);
+        input.start_cursor = start_cursor;
+        cli.list_items_by_author(input)
+    };
+    let ids = |output: &author::ListItemsByAuthorOutput| -> Vec<String> {
+        output.items.iter().map(|a| activity_id(a).to_string()).collect()
+    };
+
+    let page1 = list(None).await.unwrap();
+    let page2 = list(page1.next_cursor).await.unwrap();
+    let page3 = list(page2.next_cursor).await.unwrap();
+    assert_eq!(ids(&page3), vec!["s1"]);
+    assert!(page3.next_cursor.is_none());
+
+    let back2 = list(page3.prev_cursor).await.unwrap();
+    assert_eq!(ids(&back2), vec!["r1", "c1"]);
+
+    let back1 = list(back2.prev_cursor).await.unwrap();
+    assert_eq!(ids(&back1), vec!["c2", "s2"]);
+    assert!(back1.prev_cursor.is_none());
+
+    let again2 = list(back1.next_cursor).await.unwrap();
+    assert_eq!(ids(&again2), vec!["r1", "c1"]);
+
+    let again3 = list(again2.next_cursor).await.unwrap();
+    assert_eq!(ids(&again3), vec!["s1"]);
+}
+
+#[tokio::test]
+async fn test_list_items_by_author_per_type() {
+    let store = MemoryStore::new();
+    create_author_activities(&store).await;
+    let cli = author::Client::new(&store);
+
+    let mut input = author::ListItemsByAuthorInput::new("py0x");
+    input.entity_type = Some(EntityType::Submission);
+
+    let output = cli.list_items_by_author(input).await.unwrap();
+    let ids: Vec<&str> = output.items.iter().map(activity_id).collect();
+    assert_eq!(ids, vec!["s2", "s1"]);
+
+    // `since` only must not run into the sort keys of the next entity type
+    let mut input = author::ListItemsByAuthorInput::new("py0x");
+    input.entity_type = Some(EntityType::Reply);
+    input.since = Some(Utc.timestamp_opt(0, 0).unwrap());
+
+    let output = cli.list_items_by_author(input).await.unwrap();
+    let ids: Vec<&str> = output.items.iter().map(activity_id).collect();
+    assert_eq!(ids, vec!["r1"]);
+}
+
+#[tokio::test]
+async fn test_create_items_never_overwrite() {
+    let store = MemoryStore::new();
+    create_submission(&store, "s1").await;
+    create_comment(&store, "c1", "s1").await;
+
+    let subm_cli = submission::Client::new(&store);
+    let comm_cli = comment::Client::new(&store);
+
+    let subm = SubmissionBuilder::new()
+        .with_id(SubmissionId::from("s1").unwrap())
+        .with_author_id("someone")
+        .with_topic("news")
+        .with_ranking_score(0)
+        .with_title("overwrite")
+        .with_url("")
+        .with_text("")
+        .build()
+        .unwrap();
+    let err = subm_cli.create_item(subm).await.unwrap_err();
+    assert!(matches!(err, super::result::Error::Conflict(_)));
+
+    // the same submission created twice is a conflict too, retries go through the idempotency keys
+    let subm = new_submission("news", 0, "sent twice");
+    subm_cli.create_item(subm.clone()).await.unwrap();
+    let err = subm_cli.create_item(subm).await.unwrap_err();
+    assert!(matches!(err, super::result::Error::Conflict(ref m) if m.contains("already exists")));
+
+    let comm = CommentBuilder::new()
+        .with_id(CommentId::from("c1").unwrap())
+        .with_submission_id(SubmissionId::from("s1").unwrap())
+        .with_author_id("someone")
+        .with_ranking_score(0)
+        .with_text("overwrite")
+        .build()
+        .unwrap();
+    let err = comm_cli.create_item(comm).await.unwrap_err();
+    assert!(matches!(err, super::result::Error::Conflict(ref m) if m.contains("already exists")));
+
+    let subm = subm_cli.get_item(submission::GetItemInput::new(SubmissionId::from("s1").unwrap())).await.unwrap();
+    assert_eq!(subm.title, "s1");
+    assert_eq!(subm.n_comments, 1);
+}
+
+#[tokio::test]
+async fn test_create_items_idempotent() {
+    let store = MemoryStore::new();
+    let subm_cli = submission::Client::new(&store);
+    let comm_cli = comment::Client::new(&store);
+
+    // a retried request builds another submission, with another id
+    let first = subm_cli.create_item_idempotent(new_submission("news", 0, "first"), "key1").await.unwrap();
+    let retried = subm_cli.create_item_idempotent(new_submission("news", 0, "first"), "key1").await.unwrap();
+    assert_eq!(retried, first);
+
+    // a key used again for another request is a conflict, not the first submission
+    let err = subm_cli.create_item_idempotent(new_submission("news", 0, "changed"), "key1").await.unwrap_err();
+    assert!(matches!(err, super::result::Error::Conflict(ref m) if m.contains("another request")));
+
+    let other = subm_cli.create_item_idempotent(new_submission("news", 0, "other"), "key2").await.unwrap();
+    assert_ne!(other.id, first.id);
+
+    let output = subm_cli.list_items_by_topic(submission::ListItemsByTopicInput::new("news")).await.unwrap();
+    assert_eq!(output.items.len(), 2);
+
+    // the keys are per author and per entity type
+    let new_comment = |author_id: &str| CommentBuilder::new()
+        .with_submission_id(first.id.clone())
+        .with_author_id(author_id)
+        .with_ranking_score(0)
+        .with_text("comment")
+        .build()
+        .unwrap();
+
+    let comm = comm_cli.create_item_idempotent(new_comment("someone"), "key1").await.unwrap();
+    let retried = comm_cli.create_item_idempotent(new_comment("someone"), "key1").await.unwrap();
+    assert_eq!(retried, comm);
+
+    let err = comm_cli.create_item_idempotent(new_comment("py0x"), "key1").await.unwrap_err();
+    assert!(matches!(err, super::result::Error::Conflict(_)));
+
+    let subm = subm_cli.get_item(submission::GetItemInput::new(first.id.clone())).await.unwrap();
+    assert_eq!(subm.n_comments, 1);
+
+    // an expired record that is not deleted yet frees its key
+    let created_at = Utc::now() - chrono::Duration::hours(IDEMPOTENCY_TTL_HOURS + 1);
+    let expired = IdempotencyRecord::new("py0x", "key3", EntityType::Submission, first.id.clone(), "SUBMS#x", "A", created_at);
+    store.put_item(PutItemInput::new(serde_dynamo::to_item(expired).unwrap())).await.unwrap();
+    let created = subm_cli.create_item_idempotent(new_submission("news", 0, "after expiry"), "key3").await.unwrap();
+    assert_eq!(created.title, "after expiry");
+    let retried = subm_cli.create_item_idempotent(new_submission("news", 0, "after expiry"), "key3").await.unwrap();
+    assert_eq!(retried, created);
+}
+
+#[tokio::test]
+async fn test_edit_items() {
+    let store = MemoryStore::new();
+    create_submission(&store, "s1").await;
+    create_comment(&store, "c1", "s1").await;
+
+    let subm_cli = submission::Client::new(&store);
+    let comm_cli = comment::Client::new(&store);
+    let s1 = SubmissionId::from("s1").unwrap();
+    let subm = subm_cli.get_item(submission::GetItemInput::new(s1.clone())).await.unwrap();
+
+    let mut input = submission::EditItemInput::new(s1.clone(), "py0x", subm.updated_at);
+    input.title = Some("edited".to_string());
+    input.topic = Some("tech".to_string());
+    let edited = subm_cli.edit_item(input).await.unwrap();
+    assert_eq!(edited.title, "edited");
+    assert_eq!(edited.n_comments, 1);
+    assert!(edited.updated_at > subm.updated_at);
+    assert_eq!(edited.created_at, subm.created_at);
+    assert_eq!(edited.topic_key.pk, "TOPIC#tech");
+    assert_eq!(edited.topic_time_key.pk, "TOPIC#tech");
+    assert_eq!(edited.author_key, subm.author_key);
+
+    // an edit whose response is lost is sent again, and returns the values written
+    store.fail_after_writes(1);
+    let mut input = submission::EditItemInput::new(s1.clone(), "py0x", edited.updated_at);
+    input.text = Some("sent twice".to_string());
+    let edited = subm_cli.edit_item(input).await.unwrap();
+    assert_eq!((edited.title.as_str(), edited.text.as_str()), ("edited", "sent twice"));
+    assert_eq!(subm_cli.get_item(submission::GetItemInput::new(s1.clone())).await.unwrap(), edited);
+
+    // the submission has moved to the new topic
+    let news = subm_cli.list_items_by_topic(submission::ListItemsByTopicInput::new("news")).await.unwrap();
+    let tech = subm_cli.list_items_by_topic(submission::ListItemsByTopicInput::new("tech")).await.unwrap();
+    assert!(news.items.is_empty());
+    assert_eq!(tech.items, vec![edited.clone()]);
+
+    // a concurrent edit based on the same version is rejected
+    let mut input = submission::EditItemInput::new(s1.clone(), "py0x", subm.updated_at);
+    input.text = Some("clobbered".to_string());
+    let err = subm_cli.edit_item(input).await.unwrap_err();
+    assert!(matches!(err, Error::Conflict(_)));
+
+    let input = submission::EditItemInput::new(s1.clone(), "py0x", edited.updated_at);
+    assert!(matches!(subm_cli.edit_item(input).await.unwrap_err(), Error::BadRequest(_)));
+
+    let input = submission::EditItemInput::new(SubmissionId::from("nope").unwrap(), "py0x", edited.updated_at);
+    let err = subm_cli.edit_item(submission::EditItemInput { title: Some("t".to_string()), ..input }).await.unwrap_err();
+    assert!(matches!(err, Error::NotFound(_)));
+
+    let c1 = CommentId::from("c1").unwrap();
+    let comm = comm_cli.get_item(comment::GetItemInput::new(c1.clone())).await.unwrap();
+    let edited = comm_cli.edit_item(comment::EditItemInput::new(c1.clone(), "py0x", comm.updated_at, "edited")).await.unwrap();
+    assert_eq!(edited.text, "edited");
+    assert_eq!(edited.submission_key, comm.submission_key);
+
+    let err = comm_cli.edit_item(comment::EditItemInput::new(c1, "py0x", comm.updated_at, "clobbered")).await.unwrap_err();
+    assert!(matches!(err, Error::Conflict(_)));
+}
+
+#[tokio::test]
+async fn test_list_revisions() {
+    let store = MemoryStore::new();
+    create_submission(&store, "s1").await;
+    create_comment(&store, "c1", "s1").await;
+
+    let comm_cli = comment::Client::new(&store);
+    let rev_cli = revision::Client::new(&store);
+    let c1 = CommentId::from("c1").unwrap();
+
+    let comm = comm_cli.get_item(comment::GetItemInput::new(c1.clone())).await.unwrap();
+    let first = comm_cli.edit_item(comment::EditItemInput::new(c1.clone(), "py0x", comm.updated_at, "first")).await.unwrap();
+    let second = comm_cli.edit_item(comment::EditItemInput::new(c1.clone(), "mod", first.updated_at, "second")).await.unwrap();
+
+    let input = revision::ListRevisionsInput::new(EntityType::Comment, EntityId::from("c1").unwrap());
+    let output = rev_cli.list_revisions(input.clone()).await.unwrap();
+    let changes: Vec<(&str, &str, &str)> = output.items.iter()
+        .map(|r| (r.editor_id.as_str(), r.previous["text"].as_str(), r.revised["text"].as_str()))
+        .collect();
+    assert_eq!(changes, vec![("mod", "first", "second"), ("py0x", "c1", "first")]);
+    assert_eq!(output.items[0].revised_at, second.updated_at);
+    assert_eq!(output.items[0].previous_updated_at, first.updated_at);
+
+    let mut input = input;
+    input.limit = Some(1);
+    input.reverse = Some(true);
+    let revisions: Vec<_> = rev_cli.stream_revisions(input, None)
+        .map(|r| r.unwrap().revised_at)
+        .collect()
+        .await;
+    assert_eq!(revisions, vec![first.updated_at, second.updated_at]);
+
+    // the revisions are not listed with the content
+    let output = comm_cli.list_items_by_submission(comment::ListItemsBySubmissionInput::new(SubmissionId::from("s1").unwrap())).await.unwrap();
+    assert_eq!(output.items, vec![second]);
+
+    // a rejected edit leaves no revision
+    let err = comm_cli.edit_item(comment::EditItemInput::new(c1, "py0x", first.updated_at, "third")).await.unwrap_err();
+    assert!(matches!(err, Error::Conflict(_)));
+
+    let input = revision::ListRevisionsInput::new(EntityType::Comment, EntityId::from("c1").unwrap());
+    assert_eq!(rev_cli.list_revisions(input).await.unwrap().items.len(), 2);
+
+    let input = revision::ListRevisionsInput::new(EntityType::Vote, EntityId::from("c1").unwrap());
+    assert!(matches!(rev_cli.list_revisions(input).await.unwrap_err(), Error::BadRequest(_)));
+}
+
+#[tokio::test]
+async fn test_soft_delete_and_purge() {
+    let store = MemoryStore::new();
+    create_submission(&store, "s1").await;
+    create_comment(&store, "c1", "s1").await;
+
+    let subm_cli = submission::Client::new(&store);
+    let comm_cli = comment::Client::new(&store);
+    let reply_cli = reply::Client::new(&store);
+    let rev_cli = revision::Client::new(&store);
+    let purge_cli = purge::Client::new(&store);
+
+    let s1 = SubmissionId::from("s1").unwrap();
+    let c1 = CommentId::from("c1").unwrap();
+    let new_reply = |id: &str| ReplyBuilder::new()
+        .with_id(ReplyId::from(id).unwrap())
+        .with_submission_id(s1.clone())
+        .with_comment_id(c1.clone())
+        .with_author_id("py0x")
+        .with_text(id)
+        .build()
+        .unwrap();
+    reply_cli.create_item_idempotent(new_reply("r1"), "key1").await.unwrap();
+    let vote_input = vote::VoteInput::new("py0x", EntityType::Comment, c1.clone());
+    vote::Client::new(&store).vote(vote_input.clone()).await.unwrap();
+
+    let comm = comm_cli.get_item(comment::GetItemInput::new(c1.clone())).await.unwrap();
+    comm_cli.edit_item(comment::EditItemInput::new(c1.clone(), "py0x", comm.updated_at, "edited")).await.unwrap();
+
+    comm_cli.delete_item(comment::DeleteItemInput::new(c1.clone(), "mod")).await.unwrap();
+    comm_cli.delete_item(comment::DeleteItemInput::new(c1.clone(), "mod")).await.unwrap();
+
+    // the tombstone keeps its place in the thread and its counters, without its text
+    let output = comm_cli.list_items_by_submission(comment::ListItemsBySubmissionInput::new(s1.clone())).await.unwrap();
+    assert_eq!(output.items.len(), 1);
+    let tombstone = &output.items[0];
+    assert!(tombstone.is_deleted());
+    assert_eq!(tombstone.deleted_by.as_deref(), Some("mod"));
+    assert_eq!(tombstone.text, "");
+    assert_eq!(tombstone.n_replies, 1);
+    let subm = subm_cli.get_item(submission::GetItemInput::new(s1.clone())).await.unwrap();
+    assert_eq!(subm.n_comments, 2);
+
+    let err = comm_cli.edit_item(comment::EditItemInput::new(c1.clone(), "py0x", tombstone.updated_at, "again")).await.unwrap_err();
+    assert!(matches!(err, Error::Conflict(_)));
+    let err = reply_cli.create_item(new_reply("r2")).await.unwrap_err();
+    assert!(matches!(err, Error::Conflict(_)));
+
+    comm_cli.undelete_item(comment::UndeleteItemInput::new(c1.clone())).await.unwrap();
+    let comm = comm_cli.get_item(comment::GetItemInput::new(c1.clone())).await.unwrap();
+    assert!(!comm.is_deleted());
+    assert_eq!(comm.text, "edited");
+
+    comm_cli.delete_item(comment::DeleteItemInput::new(c1.clone(), "mod")).await.unwrap();
+    reply_cli.delete_item(reply::DeleteItemInput::new(ReplyId::from("r1").unwrap(), "py0x")).await.unwrap();
+
+    // nothing is purged within the retention period
+    let output = purge_cli.purge_all_deleted(None).await.unwrap();
+    assert_eq!((output.n_scanned, output.n_erased, output.n_removed), (0, 0, 0));
+
+    // only the tombstones are read
+    let mut purge_input = purge::PurgeDeletedInput::new();
+    purge_input.now = Some(Utc::now() + chrono::Duration::days(31));
+    let output = purge_cli.purge_deleted(purge_input.clone()).await.unwrap();
+    assert_eq!(output.n_scanned, 2);
+    assert_eq!((output.n_erased, output.n_removed, output.n_revisions), (1, 1, 1));
+    // the idempotency record of the reply, the vote stays with the erased comment
+    assert_eq!(output.n_references, 1);
+    assert!(vote::Client::new(&store).has_voted(vote_input.clone()).await.unwrap());
+
+    // the comment still has a reply under it, so only its content is gone
+    let comm = comm_cli.get_item(comment::GetItemInput::new(c1.clone())).await.unwrap();
+    assert!(comm.purged_at.is_some());
+    let err = comm_cli.undelete_item(comment::UndeleteItemInput::new(c1.clone())).await.unwrap_err();
+    assert!(matches!(err, Error::Conflict(_)));
+
+    let raw = store.get_item(super::store::GetItemInput::new(Key::new("COMMT#c1", "A"))).await.unwrap().unwrap();
+    assert_eq!(raw["text"], AttributeValue::S(String::new()));
+
+    let input = revision::ListRevisionsInput::new(EntityType::Comment, EntityId::from("c1").unwrap());
+    assert!(rev_cli.list_revisions(input).await.unwrap().items.is_empty());
+
+    let err = reply_cli.get_item(reply::GetItemInput::new(ReplyId::from("r1").unwrap())).await.unwrap_err();
+    assert!(matches!(err, Error::NotFound(_)));
+    let err = reply_cli.delete_item(reply::DeleteItemInput::new(ReplyId::from("r1").unwrap(), "py0x")).await.unwrap_err();
+    assert!(matches!(err, Error::NotFound(_)));
+
+    // the removed reply is taken off the counters of its parents
+    assert_eq!(comm.n_replies, 0);
+    let subm = subm_cli.get_item(submission::GetItemInput::new(s1.clone())).await.unwrap();
+    assert_eq!(subm.n_comments, 1);
+
+    // the erased comment is only read again one retention period after its erasure
+    let output = purge_cli.purge_deleted(purge_input.clone()).await.unwrap();
+    assert_eq!(output.n_scanned, 0);
+
+    // and then removed with its vote
+    purge_input.now = Some(Utc::now() + chrono::Duration::days(62));
+    let output = purge_cli.purge_deleted(purge_input).await.unwrap();
+    assert_eq!((output.n_erased, output.n_removed, output.n_revisions, output.n_references), (0, 1, 0, 1));
+    let err = comm_cli.get_item(comment::GetItemInput::new(c1.clone())).await.unwrap_err();
+    assert!(matches!(err, Error::NotFound(_)));
+    let subm = subm_cli.get_item(submission::GetItemInput::new(s1.clone())).await.unwrap();
+    assert_eq!(subm.n_comments, 0);
+    assert!(!vote::Client::new(&store).has_voted(vote_input).await.unwrap());
+}
+
+#[tokio::test]
+async fn test_purge_with_missing_parent() {
+    let store = MemoryStore::new();
+    create_submission(&store, "s1").await;
+    create_comment(&store, "c1", "s1").await;
+
+    let reply_cli = reply::Client::new(&store);
+    let reply = ReplyBuilder::new()
+        .with_id(ReplyId::from("r1").unwrap())
+        .with_submission_id(SubmissionId::from("s1").unwrap())
+        .with_comment_id(CommentId::from("c1").unwrap())
+        .with_author_id("py0x")
+        .with_text("r1")
+        .build()
+        .unwrap();
+    reply_cli.create_item(reply).await.unwrap();
+    reply_cli.delete_item(reply::DeleteItemInput::new(ReplyId::from("r1").unwrap(), "py0x")).await.unwrap();
+
+    // the comment is gone, e.g. removed by hand
+    store.delete_item(super::store::DeleteItemInput::new(Key::new("COMMT#c1", "A"))).await.unwrap();
+
+    let mut purge_input = purge::PurgeDeletedInput::new();
+    purge_input.now = Some(Utc::now() + chrono::Duration::days(31));
+    let output = purge::Client::new(&store).purge_deleted(purge_input).await.unwrap();
+    assert_eq!(output.n_removed, 1);
+
+    // the counter of the submission is still taken down
+    let subm = submission::Client::new(&store).get_item(submission::GetItemInput::new(SubmissionId::from("s1").unwrap())).await.unwrap();
+    assert_eq!(subm.n_comments, 1);
+}
+
+#[tokio::test]
+async fn test_create_items_bump_counters() {
+    let store = MemoryStore::new();
+    create_submission(&store, "s1").await;
+    create_submission(&store, "s2").await;
+    create_comment(&store, "c1", "s1").await;
+    create_comment(&store, "c2", "s1").await;
+
+    let reply_cli = reply::Client::new(&store);
+    let new_reply = |comment_id: &str, submission_id: &str| ReplyBuilder::new()
+        .with_submission_id(SubmissionId::from(submission_id).unwrap())
+        .with_comment_id(CommentId::from(comment_id).unwrap())
+        .with_author_id("py0x")
+        .with_text("reply")
+        .build()
+        .unwrap();
+
+    reply_cli.create_item(new_reply("c1", "s1")).await.unwrap();
+    reply_cli.create_item(new_reply("c1", "s1")).await.unwrap();
+
+    let subm_cli = submission::Client::new(&store);
+    let comm_cli = comment::Client::new(&store);
+    let subm = subm_cli.get_item(submission::GetItemInput::new(SubmissionId::from("s1").unwrap())).await.unwrap();
+    let comm = comm_cli.get_item(comment::GetItemInput::new(CommentId::from("c1").unwrap())).await.unwrap();
+    assert_eq!(subm.n_comments, 4);
+    assert_eq!(comm.n_replies, 2);
+
+    // nothing is created without the parents
+    let comm = CommentBuilder::new()
+        .with_submission_id(SubmissionId::from("missing").unwrap())
+        .with_author_id("py0x")
+        .with_ranking_score(0)
+        .with_text("orphan")
+        .build()
+        .unwrap();
+    let err = comm_cli.create_item(comm.clone()).await.unwrap_err();
+    assert!(matches!(err, super::result::Error::Conflict(_)));
+    assert!(comm_cli.get_item(comment::GetItemInput::new(comm.id)).await.is_err());
+
+    // `c2` is not a comment of `s2`
+    let err = reply_cli.create_item(new_reply("c2", "s2")).await.unwrap_err();
+    assert!(matches!(err, super::result::Error::Conflict(_)));
+    let err = reply_cli.create_item(new_reply("missing", "s1")).await.unwrap_err();
+    assert!(matches!(err, super::result::Error::Conflict(_)));
+
+    let subm = subm_cli.get_item(submission::GetItemInput::new(SubmissionId::from("s2").unwrap())).await.unwrap();
+    assert_eq!(subm.n_comments, 0);
+}
+
+#[test]
+fn test_decode_mixed_items() {
+    let subm = new_submission("news", 1, "one");
+    let comm = CommentBuilder::new()
+        .with_submission_id(subm.id.clone())
+        .with_author_id("py0x")
+        .with_ranking_score(0)
+        .with_text("comment")
+        .build()
+        .unwrap();
+
+    let mut unknown = Key::new("POLLS#id1", "A").to_item();
+    unknown.insert("entity_type".to_string(), AttributeValue::S("poll".to_string()));
+
+    let mut corrupt: Item = serde_dynamo::to_item(comm.clone()).unwrap();
+    corrupt.remove("text");
+
+    let items = vec![
+        serde_dynamo::to_item(subm.clone()).unwrap(),
+        unknown,
+        Key::new("SUBMS#id2", "A").to_item(),
+        corrupt,
+        serde_dynamo::to_item(comm.clone()).unwrap(),
+    ];
+
+    let decoded = super::decode::decode_items(items);
+    assert_eq!(decoded.entities, vec![Entity::Submission(subm), Entity::Comment(comm)]);
+
+    let errors: Vec<String> = decoded.errors.iter().map(|e| e.to_string()).collect();
+    assert_eq!(errors.len(), 3);
+    assert_eq!(errors[0], "item `POLLS#id1/A` has an unknown entity type: `poll`");
+    assert_eq!(errors[1], "item `SUBMS#id2/A` has no `entity_type`");
+    assert!(matches!(decoded.errors[2], super::decode::DecodeError::CorruptItem(_, EntityType::Comment, _)));
+}
+
+#[test]
+fn test_error_http_status() {
+    use super::result::Error;
+
+    let cancelled = |reasons: Vec<Option<&str>>| {
+        Error::TransactionCancelled(reasons.into_iter().map(|r| r.map(String::from)).collect(), None)
+    };
+
+    assert_eq!(Error::BadRequest("limit".to_string()).http_status(), 400);
+    assert_eq!(Error::InvalidCursor("expired".to_string()).http_status(), 400);
+    assert_eq!(Error::Conflict("exists".to_string()).http_status(), 409);
+    assert_eq!(Error::ConditionFailed("attribute_exists(PK)".into()).http_status(), 409);
+    assert_eq!(Error::Transient("timeout".into()).http_status(), 503);
+    assert_eq!(Error::ServerError("internal".into()).http_status(), 500);
+
+    assert_eq!(cancelled(vec![None, Some("ConditionalCheckFailed")]).http_status(), 409);
+    assert_eq!(cancelled(vec![Some("ThrottlingError"), None]).http_status(), 503);
+    assert_eq!(cancelled(vec![Some("ValidationError")]).http_status(), 400);
+    assert_eq!(cancelled(vec![None, None]).http_status(), 500);
+}
+
+#[tokio::test]
+async fn test_retry_policy() {
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::time::Duration;
+    use super::result::Error;
+    use super::retry::*;
+
+    let policy = RetryPolicy::new()
+        .with_max_attempts(3)
+        .with_base_delay(Duration::from_millis(1))
+        .with_max_delay(Duration::from_millis(2));
+    for retry in 1..10 {
+        assert!(policy.delay(retry) <= Duration::from_millis(2));
+    }
+
+    // throttled twice, then sent
+    let metrics = RetryMetrics::default();
+    let attempts = AtomicU32::new(0);
+    let result = with_retry(&policy, &metrics, false, || async {
+        match attempts.fetch_add(1, Ordering::SeqCst) {
+            0 | 1 => Err(Error::Throttled("slow down".into())),
+            _ => Ok(42),
+        }
+    }).await;
+    assert_eq!(result.unwrap(), 42);
+    assert_eq!(metrics.stats(), RetryStats { requests: 1, retries: 2, throttled: 2, ..RetryStats::default() });
+
+    // a timed out counter update may have been applied
+    let attempts = AtomicU32::new(0);
+    let result: super::result::Result<()> = with_retry(&policy, &metrics, false, || async {
+        attempts.fetch_add(1, Ordering::SeqCst);
+        Err(Error::Transient("timeout".into()))
+    }).await;
+    assert!(matches!(result, Err(Error::Transient(_))));
+    assert_eq!(attempts.load(Ordering::SeqCst), 1);
+
+    // a read is sent until the last attempt
+    let attempts = AtomicU32::new(0);
+    let result: super::result::Result<()> = with_retry(&policy, &metrics, true, || async {
+        attempts.fetch_add(1, Ordering::SeqCst);
+        Err(Error::Transient("timeout".into()))
+    }).await;
+    assert!(matches!(result, Err(Error::Transient(_))));
+    assert_eq!(attempts.load(Ordering::SeqCst), 3);
+    assert_eq!(metrics.stats().exhausted, 1);
+
+    // a failed condition is never sent again
+    assert!(!is_retryable(&Error::ConditionFailed("attribute_exists(PK)".into()), true));
+    let conflict = Error::TransactionCancelled(vec![None, Some("TransactionConflict".to_string())], None);
+    assert!(is_retryable(&conflict, false));
+}
+
+#[tokio::test]
+async fn test_memory_store_conditional_writes() {
+    let store = MemoryStore::new();
+    let key = Key::new("SUBMS#id1", "A");
+
+    let mut put = PutItemInput::new(key.to_item());
+    put.condition = Some(Condition::AttributeNotExists("PK".to_string()));
+    store.put_item(put.clone()).await.unwrap();
+
+    let err = store.put_item(put).await.unwrap_err();
+    assert!(matches!(err, super::result::Error::ConditionFailed(_)));
+
+    let update = UpdateItemInput::new(key.clone(), vec![
+        UpdateAction::Add("n_votes".to_string(), 2),
+        UpdateAction::Set("title".to_string(), AttributeValue::S("hello".to_string())),
+    ]);
+    let item = store.update_item(update).await.unwrap();
+    assert_eq!(item.get("n_votes"), Some(&AttributeValue::N("2".to_string())));
+    assert_eq!(item.get("title"), Some(&AttributeValue::S("hello".to_string())));
+
+    let mut delete = DeleteItemInput::new(key.clone());
+    delete.condition = Some(Condition::Equals("n_votes".to_string(), AttributeValue::N("1".to_string())));
+    assert!(store.delete_item(delete).await.is_err());
+
+    let old = store.delete_item(DeleteItemInput::new(key.clone())).await.unwrap();
+    assert_eq!(old, Some(item));
+    assert_eq!(store.get_item(GetItemInput::new(key)).await.unwrap(), None);
+}
+
+#[tokio::test]
+async fn test_memory_store_transactions() {
+    let store = MemoryStore::new();
+    let key1 = Key::new("SUBMS#id1", "A");
+    let key2 = Key::new("SUBMS#id2", "A");
+    store.put_item(PutItemInput::new(key1.to_item())).await.unwrap();
+
+    let mut put = PutItemInput::new(key2.to_item());
+    put.condition = Some(Condition::AttributeNotExists("PK".to_string()));
+    let mut update = UpdateItemInput::new(key1.clone(), vec![UpdateAction::Add("n_votes".to_string(), 1)]);
+    update.condition = Some(Condition::AttributeExists("PK".to_string()));
+    let check = TransactWriteItem::ConditionCheck(Key::new("SUBMS#id3", "A"), Condition::AttributeExists("PK".to_string()));
+
+    // the condition check fails, so neither the put nor the update is applied
+    let writes = vec![TransactWriteItem::Put(put.clone()), TransactWriteItem::Update(update.clone()), check];
+    let err = store.transact_write_items(writes).await.unwrap_err();
+    let super::result::Error::TransactionCancelled(reasons, _) = err else { panic!("unexpected error: {err:?}") };
+    assert_eq!(reasons, vec![None, None, Some(CONDITIONAL_CHECK_FAILED.to_string())]);
+    assert_eq!(store.get_item(GetItemInput::new(key2.clone())).await.unwrap(), None);
+
+    let writes = vec![TransactWriteItem::Put(put), TransactWriteItem::Update(update)];
+    store.transact_write_items(writes).await.unwrap();
+    let item = store.get_item(GetItemInput::new(key1.clone())).await.unwrap().unwrap();
+    assert_eq!(item.get("n_votes"), Some(&AttributeValue::N("1".to_string())));
+    assert!(store.get_item(GetItemInput::new(key2.clone())).await.unwrap().is_some());
+
+    let writes = vec![
+        TransactWriteItem::Delete(DeleteItemInput::new(key1.clone())),
+        TransactWriteItem::ConditionCheck(key1, Condition::AttributeExists("PK".to_string())),
+    ];
+    let err = store.transact_write_items(writes).await.unwrap_err();
+    assert!(matches!(err, super::result::Error::BadRequest(_)));
+}
+
+#[tokio::test]
+async fn test_memory_store_lost_responses() {
+    use std::time::Duration;
+    use super::retry::RetryPolicy;
+
+    let store = MemoryStore::new()
+        .with_retry_policy(RetryPolicy::new().with_base_delay(Duration::from_millis(1)));
+    let key = Key::new("SUBMS#id1", "A");
+
+    // a conditional put is not sent again, it would fail its own condition
+    let mut put = PutItemInput::new(key.to_item());
+    put.condition = Some(Condition::AttributeNotExists("PK".to_string()));
+    store.fail_after_writes(1);
+    let err = store.put_item(put).await.unwrap_err();
+    assert!(matches!(err, super::result::Error::Transient(_)));
+    assert!(store.get_item(GetItemInput::new(key.clone())).await.unwrap().is_some());
+
+    // nor is a counter update, while a plain one is
+    store.fail_after_writes(1);
+    let update = UpdateItemInput::new(key.clone(), vec![UpdateAction::Add("n_votes".to_string(), 1)]);
+    assert!(store.update_item(update).await.is_err());
+    store.fail_after_writes(1);
+    let update = UpdateItemInput::new(key.clone(), vec![UpdateAction::Set("title".to_string(), AttributeValue::S("hello".to_string()))]);
+    let item = store.update_item(update).await.unwrap();
+    assert_eq!(item.get("n_votes"), Some(&AttributeValue::N("1".to_string())));
+
+    // a transaction is sent again with its token, and applied once
+    let mut update = UpdateItemInput::new(key.clone(), vec![UpdateAction::Add("n_votes".to_string(), 1)]);
+    update.condition = Some(Condition::AttributeExists("PK".to_string()));
+    store.fail_after_writes(2);
+    store.transact_write_items(vec![TransactWriteItem::Update(update)]).await.unwrap();
+    let item = store.get_item(GetItemInput::new(key)).await.unwrap().unwrap();
+    assert_eq!(item.get("n_votes"), Some(&AttributeValue::N("2".to_string())));
+    assert_eq!(store.retry_stats().retries, 3);
+}
+
+#[tokio::test]
+async fn test_vote_and_unvote() {
+    let store = MemoryStore::new();
+    let subm_cli = submission::Client::new(&store);
+    let vote_cli = vote::Client::new(&store);
+
+    let subm = new_submission("news", 1, "one");
+    subm_cli.create_item(subm.clone()).await.unwrap();
+
+    let get = submission::GetItemInput::new(subm.id.clone());
+    let input = |user_id: &str| vote::VoteInput::new(user_id, EntityType::Submission, subm.id.clone());
+
+    // voting twice only counts once
+    assert!(vote_cli.vote(input("py0x")).await.unwrap());
+    assert!(!vote_cli.vote(input("py0x")).await.unwrap());
+    assert!(vote_cli.vote(input("other")).await.unwrap());
+    assert_eq!(subm_cli.get_item(get.clone()).await.unwrap().n_votes, 2);
+    assert!(vote_cli.has_voted(input("py0x")).await.unwrap());
+
+    assert!(vote_cli.unvote(input("py0x")).await.unwrap());
+    assert!(!vote_cli.unvote(input("py0x")).await.unwrap());
+    assert_eq!(subm_cli.get_item(get).await.unwrap().n_votes, 1);
+    assert!(!vote_cli.has_voted(input("py0x")).await.unwrap());
+
+    let missing = vote::VoteInput::new("py0x", EntityType::Comment, CommentId::from("missing").unwrap());
+    let err = vote_cli.vote(missing).await.unwrap_err();
+    assert!(matches!(err, super::result::Error::NotFound(_)));
+    assert!(!vote_cli.has_voted(vote::VoteInput::new("py0x", EntityType::Comment, CommentId::from("missing").unwrap())).await.unwrap());
+
+    let reply = vote::VoteInput::new("py0x", EntityType::Reply, ReplyId::from("reply-id").unwrap());
+    assert!(matches!(vote_cli.vote(reply).await, Err(super::result::Error::BadRequest(_))));
+}
+
+#[tokio::test]
+async fn test_rerank_topic() {
+    let store = MemoryStore::new();
+    let subm_cli = submission::Client::new(&store);
+    let vote_cli = vote::Client::new(&store);
+    let now = Utc.timestamp_opt(1_000_000, 0).unwrap();
+
+    // (title, age in hours, votes), the old one has a score frozen at an earlier re-ranking
+    let subms = [("popular", 5, 3), ("fresh", 0, 0), ("stale", 10, 0), ("old", 100, 5)];
+    for (title, age, votes) in subms {
+        let subm = SubmissionBuilder::new()
+            .with_author_id("py0x")
+            .with_topic("news")
+            .with_ranking_score(if title == "old" { 1_000_000 } else { 0 })
+            .with_title(title)
+            .with_url("")
+            .with_text("")
+            .with_created_at(now - chrono::Duration::hours(age))
+            .build()
+            .unwrap();
+        subm_cli.create_item(subm.clone()).await.unwrap();
+
+        for i in 0..votes {
+            let input = vote::VoteInput::new(format!("user{i}"), EntityType::Submission, subm.id.clone());
+            vote_cli.vote(input).await.unwrap();
+        }
+    }
+
+    let mut input = ranking::RerankTopicInput::new("news");
+    input.now = Some(now);
+    let output = ranking::Client::new(&store).rerank_topic(input.clone()).await.unwrap();
+    assert_eq!(output.n_ranked, 3);
+    assert_eq!(output.n_updated, 3);
+    assert_eq!(output.n_aged_out, 1);
+
+    let listed = subm_cli.list_items_by_topic(submission::ListItemsByTopicInput::new("news")).await.unwrap();
+    let titles: Vec<&str> = listed.items.iter().map(|s| s.title.as_str()).collect();
+    assert_eq!(titles, vec!["fresh", "popular", "stale", "old"]);
+    assert_eq!(listed.items[3].ranking_score, ranking::AGED_OUT_SCORE);
+
+    // nothing has changed since, so nothing is written again
+    let output = ranking::Client::new(&store).rerank_topic(input).await.unwrap();
+    assert_eq!(output.n_updated, 0);
+    assert_eq!(output.n_aged_out, 0);
+}
+
+#[tokio::test]
+async fn test_list_items_by_topic_signed_scores() {
+    let store = MemoryStore::new();
+    let cli = submission::Client::new(&store);
+
+    let scores = [("a", -5), ("b", 10_000_000_000), ("c", 0), ("d", 100), ("e", 0), ("f", -10_000_000_000)];
+    for (id, score) in scores {
+        let subm = SubmissionBuilder::new()
+            .with_id(SubmissionId::from(id).unwrap())
+            .with_author_id("py0x")
+            .with_topic("news")
+            .with_ranking_score(score)
+            .with_title(id)
+            .with_url("")
+            .with_text("")
+            .build()
+            .unwrap();
+        cli.create_item(subm).await.unwrap();
+    }
+
+    let output = cli.list_items_by_topic(submission::ListItemsByTopicInput::new("news")).await.unwrap();
+    let ids: Vec<&str> = output.items.iter().map(|s| s.id.as_ref()).collect();
+    // equal scores are ordered by id
+    assert_eq!(ids, vec!["b", "d", "e", "c", "a", "f"]);
+}
+
+#[tokio::test]
+async fn test_migrate_keys() {
+    let store = MemoryStore::new();
+    create_submission(&store, "s1").await;
+    create_comment(&store, "c1", "s1").await;
+    vote::Client::new(&store)
+        .vote(vote::VoteInput::new("py0x", EntityType::Submission, SubmissionId::from("s1").unwrap()))
+        .await
+        .unwrap();
+
+    let current = |key: Key| {
+        let store = &store;
+        async move { store.get_item(GetItemInput::new(key)).await.unwrap().unwrap() }
+    };
+    let subm_key = Key::new("SUBMS#s1", "A");
+    let comm_key = Key::new("COMMT#c1", "A");
+    let migrated_subm = current(subm_key.clone()).await;
+    let migrated_comm = current(comm_key.clone()).await;
+
+    // the `{score:010}` keys written before the key codec
+    let old_keys = [
+        (subm_key.clone(), "GSI1_SK", "SUBMS#0000000000"),
+        (subm_key.clone(), "GSI2_SK", "SUBMS#0000001234"),
+        (comm_key.clone(), "GSI1_SK", "COMMT#0000000000"),
+    ];
+    for (key, attr, sk) in old_keys {
+        let update = UpdateItemInput::new(key, vec![UpdateAction::Set(attr.to_string(), AttributeValue::S(sk.to_string()))]);
+        store.update_item(update).await.unwrap();
+    }
+
+    // a submission written before the topic-time index
+    let no_time_key = vec![UpdateAction::Remove("GSI3_PK".to_string()), UpdateAction::Remove("GSI3_SK".to_string())];
+    store.update_item(UpdateItemInput::new(subm_key.clone(), no_time_key)).await.unwrap();
+    let subm = submission::Client::new(&store)
+        .get_item(submission::GetItemInput::new(SubmissionId::from("s1").unwrap()))
+        .await
+        .unwrap();
+    assert!(subm.topic_time_key.pk.is_empty());
+
+    // the items are scanned in key order: `COMMT#c1`, `SUBMS#s1` then the vote
+    let mut input = migration::MigrateKeysInput::new();
+    input.limit = Some(2);
+    let output = migration::Client::new(&store).migrate_keys(input).await.unwrap();
+    assert_eq!(output.n_scanned, 2);
+    assert_eq!(output.n_migrated, 2);
+    assert!(output.invalid_items.is_empty());
+    assert_eq!(current(subm_key).await, migrated_subm);
+    assert_eq!(current(comm_key).await, migrated_comm);
+
+    let mut input = migration::MigrateKeysInput::new();
+    input.start_cursor = output.next_cursor;
+    let output = migration::Client::new(&store).migrate_keys(input).await.unwrap();
+    assert_eq!(output.n_scanned, 1);
+    assert_eq!(output.n_migrated, 0);
+
+    let output = migration::Client::new(&store).migrate_all_keys().await.unwrap();
+    assert_eq!(output.n_scanned, 3);
+    assert_eq!(output.n_migrated, 0);
+}
+
+#[test]
+fn test_plan_schema() {
+    use aws_sdk_dynamodb::model::{
+        GlobalSecondaryIndexDescription, KeySchemaElement, KeyType, Projection, ProjectionType,
+        TableDescription, TimeToLiveDescription, TimeToLiveStatus,
+    };
+
+    let key = |pk: &str, sk: &str| vec![
+        KeySchemaElement::builder().attribute_name(pk).key_type(KeyType::Hash).build(),
+        KeySchemaElement::builder().attribute_name(sk).key_type(KeyType::Range).build(),
+    ];
+    let index = |name: &str, projection_type: ProjectionType| GlobalSecondaryIndexDescription::builder()
+        .index_name(name)
+        .set_key_schema(Some(key(&format!("{name}_PK"), &format!("{name}_SK"))))
+        .projection(Projection::builder().projection_type(projection_type).build())
+        .build();
+    let table = |indexes: Vec<GlobalSecondaryIndexDescription>| TableDescription::builder()
+        .set_key_schema(Some(key("PK", "SK")))
+        .set_global_secondary_indexes(Some(indexes))
+        .build();
+    let ttl = |attr: &str, status: TimeToLiveStatus| TimeToLiveDescription::builder()
+        .attribute_name(attr)
+        .time_to_live_status(status)
+        .build();
+
+    let schema = TableSchema::content("valnk-content");
+    assert_eq!(schema.plan(None, None).unwrap(), vec![
+        SchemaChange::CreateTable,
+        SchemaChange::EnableTtl(schema::TTL_ATTR.to_string()),
+    ]);
+
+    // a table created before `GSI3`, with no TTL yet
+    let old = table(vec![index("GSI1", ProjectionType::All), index("GSI2", ProjectionType::All)]);
+    assert_eq!(schema.plan(Some(&old), Some(&ttl("", TimeToLiveStatus::Disabled))).unwrap(), vec![
+        SchemaChange::CreateIndex("GSI3".to_string()),
+        SchemaChange::CreateIndex("GSI4".to_string()),
+        SchemaChange::EnableTtl("expires_at".to_string()),
+    ]);
+
+    let current = table(["GSI1", "GSI2", "GSI3", "GSI4"].map(|n| index(n, ProjectionType::All)).to_vec());
+    assert!(schema.plan(Some(&current), Some(&ttl("expires_at", TimeToLiveStatus::Enabled))).unwrap().is_empty());
+
+    // the differences that would drop data are left to an operator
+    let keys_only = table(vec![index("GSI1", ProjectionType::KeysOnly)]);
+    assert!(matches!(schema.plan(Some(&keys_only), None), Err(Error::Conflict(_))));
+    let other_ttl = ttl("ttl", TimeToLiveStatus::Enabled);
+    assert!(matches!(schema.plan(Some(&current), Some(&other_ttl)), Err(Error::Conflict(_))));
+    let other_keys = TableDescription::builder().set_key_schema(Some(key("id", "sort"))).build();
+    assert!(matches!(schema.plan(Some(&other_keys), None), Err(Error::Conflict(_))));
+}
+
+/// Runs against a local DynamoDB-compatible endpoint, e.g. DynamoDB Local:
+/// `VALNK_TEST_ENDPOINT=http://localhost:8000 cargo test -- --ignored`.
+#[tokio::test]
+#[ignore]
+async fn test_apply_schema_locally() {
+    let endpoint = std::env::var("VALNK_TEST_ENDPOINT").unwrap_or("http://localhost:8000".to_string());
+    let shared_config = aws_config::load_from_env().await;
+    let aws_cli = dynamodb_client(&shared_config, Some(&endpoint)).unwrap();
+
+    let table_name = format!("valnk-test-{}", EntityId::new());
+    let schema = TableSchema::content(&table_name);
+    assert!(schema.apply(&aws_cli).await.unwrap().contains(&SchemaChange::CreateTable));
+    assert!(schema.apply(&aws_cli).await.unwrap().is_empty());
+
+    let store = DynamoStore::new(aws_cli.clone(), &table_name);
+    let subm = new_submission("news", 1, "hello");
+    submission::Client::new(&store).create_item(subm.clone()).await.unwrap();
+    let output = submission::Client::new(&store)
+        .list_items_by_topic(submission::ListItemsByTopicInput::new("news"))
+        .await
+        .unwrap();
+    assert_eq!(output.items, vec![subm]);
+
+    aws_cli.delete_table().table_name(&table_name).send().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_memory_store_sparse_index() {
+    let store = MemoryStore::new();
+
+    let indexed = HashMap::from([
+        ("PK".to_string(), AttributeValue::S("SUBMS#id1".to_string())),
+        ("SK".to_string(), AttributeValue::S("A".to_string())),
+        ("GSI2_PK".to_string(), AttributeValue::S("AUTHR#py0x".to_string())),
+        ("GSI2_SK".to_string(), AttributeValue::S("SUBMS#0000001234".to_string())),
+    ]);
+    let not_indexed = Key::new("SUBMS#id2", "A").to_item();
+
+    store.put_item(PutItemInput::new(indexed.clone())).await.unwrap();
+    store.put_item(PutItemInput::new(not_indexed)).await.unwrap();
 
-    println!("output2: {:#?}", output2);
-    println!("========");
+    let mut query = QueryInput::new(Index::Gsi2, "AUTHR#py0x");
+    query.sk = Some(SkCondition::BeginsWith("SUBMS#".to_string()));
+    query.limit = Some(1);
 
-}
\ No newline at end of file
+    let output = store.query(query).await.unwrap();
+    assert_eq!(output.items, vec![indexed.clone()]);
+    // the page is cut by the limit, so it has a `LastEvaluatedKey` with the index keys
+    assert_eq!(output.last_evaluated_key, Some(indexed));
+}
@@ -0,0 +1,355 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::data::model::entity::Entity;
+use crate::data::model::submission::{self as subm_model, Submission, SubmissionId};
+use crate::data::model::comment::{self as comm_model, RankingScore};
+use crate::data::model::reply as reply_model;
+use crate::data::model::thread::ThreadIndexKey;
+
+use super::result::Result;
+use super::cursor::Cursor;
+use super::decode::{decode_items, DecodeError};
+use super::lookup::get_entity;
+use super::tombstone::blank_deleted;
+use super::page::{query_items, paginate, ItemStream, PageLimits};
+use super::store::{
+    ContentStore,
+    Index,
+    Item,
+    Key,
+    SkCondition,
+    QueryInput,
+};
+
+/// How deep the nodes are rendered before their replies are collapsed, by default.
+const DEFAULT_COLLAPSE_DEPTH: usize = 6;
+
+/// How many comments and replies a thread loads at most, by default.
+pub const DEFAULT_MAX_NODES: usize = 500;
+
+
+#[derive(Clone, Debug)]
+pub struct ListThreadInput {
+    pub submission_id: SubmissionId,
+    /// Only lists the subtree of the comment or reply with the path, the node included.
+    pub subtree_path: Option<String>,
+    pub limit: Option<i32>,
+    pub reverse: Option<bool>,
+    pub start_cursor: Option<Cursor>,
+}
+
+impl ListThreadInput {
+    pub fn new(submission_id: SubmissionId) -> Self {
+        Self {
+            submission_id,
+            subtree_path: None,
+            limit: None,
+            reverse: None,
+            start_cursor: None,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct ListThreadOutput {
+    /// The comments and replies, each one right before its subtree.
+    pub items: Vec<Entity>,
+    /// The items of the page that could not be decoded, they are left out of `items`.
+    pub invalid_items: Vec<DecodeError>,
+    pub next_cursor: Option<Cursor>,
+    /// The cursor of the page before this one, `None` on the first page.
+    pub prev_cursor: Option<Cursor>,
+}
+
+impl ListThreadOutput {
+    pub fn new(items: Vec<Entity>) -> Self {
+        Self {
+            items,
+            invalid_items: vec![],
+            next_cursor: None,
+            prev_cursor: None,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct LoadThreadInput {
+    pub submission_id: SubmissionId,
+    /// The nodes at this depth, or deeper, are collapsed, `DEFAULT_COLLAPSE_DEPTH` by default.
+    pub collapse_depth: Option<usize>,
+    /// The comments ranked below this score are collapsed, none by default.
+    pub collapse_below_score: Option<RankingScore>,
+    /// The number of comments and replies loaded at most, `DEFAULT_MAX_NODES` by default.
+    pub max_nodes: Option<usize>,
+}
+
+impl LoadThreadInput {
+    pub fn new(submission_id: SubmissionId) -> Self {
+        Self {
+            submission_id,
+            collapse_depth: None,
+            collapse_below_score: None,
+            max_nodes: None,
+        }
+    }
+}
+
+/// A comment or a reply, with the replies to it.
+#[derive(Clone, PartialEq, Debug)]
+pub struct ThreadNode {
+    pub entity: Entity,
+    /// `0` for a comment, `1` for a reply to it, and so on.
+    pub depth: usize,
+    /// The number of the replies to this node.
+    pub n_children: usize,
+    /// The number of the replies in the whole subtree of this node.
+    pub n_descendants: usize,
+    /// A hint to render the node folded, with its subtree hidden behind a link.
+    pub collapsed: bool,
+    /// The replies to this node, the oldest first.
+    pub children: Vec<ThreadNode>,
+}
+
+/// A submission with its discussion, up to the `max_nodes` of the load.
+#[derive(Clone, Debug)]
+pub struct Thread {
+    pub submission: Submission,
+    /// The comment trees, the comments by their ranking, the highest first.
+    pub comments: Vec<ThreadNode>,
+    /// The number of the comments and replies in the trees.
+    pub n_nodes: usize,
+    /// Whether the discussion has more nodes than `max_nodes`, the later ones are left out.
+    pub truncated: bool,
+    /// The items of the discussion that could not be decoded, they are left out of the trees.
+    pub invalid_items: Vec<DecodeError>,
+}
+
+/// The primary-key partition of a comment or a reply, the parents are referred to by it.
+fn node_pk(entity: &Entity) -> String {
+    return match entity {
+        Entity::Submission(subm) => subm.primary_key.pk.clone(),
+        Entity::Comment(comm) => comm.primary_key.pk.clone(),
+        Entity::Reply(reply) => reply.primary_key.pk.clone(),
+    };
+}
+
+/// The partition of the parent of a reply in its tree, the comment for a direct reply.
+fn parent_pk(reply: &reply_model::Reply) -> String {
+    return match &reply.parent_id {
+        Some(parent_id) => reply_model::PrimaryKey::new(parent_id).pk,
+        None => comm_model::PrimaryKey::new(&reply.comment_id).pk,
+    };
+}
+
+/// Assembles the comment trees: each node gets its replies, the oldest first.
+struct TreeBuilder<'a> {
+    children: HashMap<String, Vec<Entity>>,
+    input: &'a LoadThreadInput,
+}
+
+impl TreeBuilder<'_> {
+    fn node(&mut self, entity: Entity, depth: usize) -> ThreadNode {
+        let mut replies = self.children.remove(&node_pk(&entity)).unwrap_or_default();
+        replies.sort_by(|a, b| (a.created_at(), a.id().as_ref()).cmp(&(b.created_at(), b.id().as_ref())));
+
+        let children: Vec<ThreadNode> = replies.into_iter()
+            .map(|reply| self.node(reply, depth + 1))
+            .collect();
+        let n_descendants = children.iter().map(|c| 1 + c.n_descendants).sum();
+
+        let collapse_depth = self.input.collapse_depth.unwrap_or(DEFAULT_COLLAPSE_DEPTH);
+        let low_ranked = match (&entity, self.input.collapse_below_score) {
+            (Entity::Comment(comm), Some(score)) => comm.ranking_score < score,
+            _ => false,
+        };
+
+        return ThreadNode {
+            depth,
+            n_children: children.len(),
+            n_descendants,
+            collapsed: (depth >= collapse_depth && !children.is_empty()) || low_ranked,
+            children,
+            entity,
+        };
+    }
+}
+
+
+/// Reads the discussions of the submissions: their comment trees in the order of
+/// the materialised paths, see [`thread`](crate::data::model::thread), or assembled into trees.
+#[derive(Debug)]
+pub struct Client<'c> {
+    store: &'c dyn ContentStore,
+    page_limits: PageLimits,
+}
+
+impl<'c> Client<'c> {
+    pub fn new(store: &'c dyn ContentStore) -> Self {
+        return Self {
+            store,
+            page_limits: PageLimits::default(),
+        };
+    }
+
+    pub fn with_page_limits(mut self, page_limits: PageLimits) -> Self {
+        self.page_limits = page_limits;
+        self
+    }
+
+    /// Lists the comments and replies of a submission depth-first, in the order they are
+    /// rendered: a node right before its replies, the siblings the oldest first
+    /// (or all of it the other way round when `reverse` is set).
+    ///
+    /// # Example:
+    ///
+    /// ```no_run
+    /// use valnk::data::api::thread::*;
+    /// use valnk::data::model::entity::Entity;
+    /// use valnk::data::model::submission::SubmissionId;
+    /// use valnk::data::model::thread::depth;
+    ///
+    /// async fn print_thread(cli: &Client<'_>) {
+    ///     let input = ListThreadInput::new(SubmissionId::from("subm-id").unwrap());
+    ///     let output = cli.list_thread(input).await.unwrap();
+    ///     for item in output.items {
+    ///         match item {
+    ///             Entity::Comment(comm) => println!("{}", comm.text),
+    ///             Entity::Reply(reply) => println!("{}{}", "  ".repeat(depth(&reply.path)), reply.text),
+    ///             Entity::Submission(_) => unreachable!(),
+    ///         }
+    ///     }
+    /// }
+    /// ```
+    pub async fn list_thread(&self, input: ListThreadInput) -> Result<ListThreadOutput> {
+        let sk_prefix = match &input.subtree_path {
+            Some(path) => ThreadIndexKey::subtree_sk_prefix(path),
+            None => ThreadIndexKey::sk_prefix(),
+        };
+
+        let mut query = QueryInput::new(Index::Gsi3, ThreadIndexKey::pk(&input.submission_id));
+        query.sk = Some(SkCondition::BeginsWith(sk_prefix));
+        query.scan_index_forward = !input.reverse.unwrap_or(false);
+        query.limit = Some(self.page_limits.resolve(input.limit)?);
+
+        let mut page = query_items(self.store, query, input.start_cursor).await?;
+        page.items.iter_mut().for_each(blank_deleted);
+        let decoded = decode_items(page.items);
+
+        let mut output = ListThreadOutput::new(decoded.entities);
+        output.invalid_items = decoded.errors;
+        output.next_cursor = page.next_cursor;
+        output.prev_cursor = page.prev_cursor;
+
+        Ok(output)
+    }
+
+    /// Loads a submission with its comments and replies, assembled into their trees.
+    ///
+    /// The discussion is read in the order of the paths in `GSI3`, page after page of the
+    /// largest size of the page limits, up to `max_nodes` nodes: a node is always read
+    /// before its replies, so a truncated thread only misses the latest trees and subtrees,
+    /// and its counters only count the nodes it has. A reply whose parent reply is missing
+    /// is attached to its comment, and one whose comment is missing is left out.
+    /// Fails with `Error::NotFound` if the submission does not exist.
+    ///
+    /// # Example:
+    ///
+    /// ```no_run
+    /// use valnk::data::api::thread::*;
+    /// use valnk::data::model::submission::SubmissionId;
+    ///
+    /// fn render(node: &ThreadNode) {
+    ///     println!("{}{:?} ({} replies)", "  ".repeat(node.depth), node.entity.id(), node.n_descendants);
+    ///     if !node.collapsed {
+    ///         node.children.iter().for_each(render);
+    ///     }
+    /// }
+    ///
+    /// async fn print_thread(cli: &Client<'_>) {
+    ///     let thread = cli.load_thread(LoadThreadInput::new(SubmissionId::from("subm-id").unwrap())).await.unwrap();
+    ///     println!("{}", thread.submission.title);
+    ///     thread.comments.iter().for_each(render);
+    /// }
+    /// ```
+    pub async fn load_thread(&self, input: LoadThreadInput) -> Result<Thread> {
+        let subm_pk = subm_model::PrimaryKey::new(&input.submission_id);
+        let submission: Submission = get_entity(self.store, Key::new(subm_pk.pk, subm_pk.sk), false).await?;
+
+        let max_nodes = input.max_nodes.unwrap_or(DEFAULT_MAX_NODES);
+        // one node more than the cap tells if the discussion is larger, a last page
+        // that is exactly full still comes with a `last_evaluated_key`
+        let n_fetched = max_nodes.saturating_add(1);
+        let mut items: Vec<Item> = vec![];
+        let mut query = QueryInput::new(Index::Gsi3, ThreadIndexKey::pk(&input.submission_id));
+        query.sk = Some(SkCondition::BeginsWith(ThreadIndexKey::sk_prefix()));
+        while items.len() < n_fetched {
+            let remaining = i32::try_from(n_fetched - items.len()).unwrap_or(i32::MAX);
+            query.limit = Some(self.page_limits.max_limit.min(remaining));
+
+            let results = self.store.query(query.clone()).await?;
+            items.extend(results.items);
+            match results.last_evaluated_key {
+                Some(lk) => query.exclusive_start_key = Some(lk),
+                None => break,
+            }
+        }
+        let truncated = items.len() > max_nodes;
+        items.truncate(max_nodes);
+        items.iter_mut().for_each(blank_deleted);
+        let decoded = decode_items(items);
+
+        let mut comments = vec![];
+        let mut replies = vec![];
+        for entity in decoded.entities {
+            match entity {
+                Entity::Comment(comm) => comments.push(comm),
+                Entity::Reply(reply) => replies.push(reply),
+                Entity::Submission(_) => continue,
+            }
+        }
+        // the highest ranked first, like in `GSI1`
+        comments.sort_by(|a, b| (b.ranking_score, b.id.as_ref()).cmp(&(a.ranking_score, a.id.as_ref())));
+
+        let known: HashSet<String> = comments.iter()
+            .map(|c| c.primary_key.pk.clone())
+            .chain(replies.iter().map(|r| r.primary_key.pk.clone()))
+            .collect();
+        let mut children: HashMap<String, Vec<Entity>> = HashMap::new();
+        for reply in replies {
+            let mut parent = parent_pk(&reply);
+            if !known.contains(&parent) {
+                parent = comm_model::PrimaryKey::new(&reply.comment_id).pk;
+            }
+            children.entry(parent).or_default().push(Entity::Reply(reply));
+        }
+
+        let mut builder = TreeBuilder {
+            children,
+            input: &input,
+        };
+        let comments: Vec<ThreadNode> = comments.into_iter()
+            .map(|comm| builder.node(Entity::Comment(comm), 0))
+            .collect();
+
+        Ok(Thread {
+            submission,
+            n_nodes: comments.iter().map(|c| 1 + c.n_descendants).sum(),
+            comments,
+            truncated,
+            invalid_items: decoded.errors,
+        })
+    }
+
+    /// Streams the comment tree of a submission, following the cursors from `input.start_cursor`,
+    /// `input.limit` is the size of the pages and `max_items` caps the number of items.
+    pub fn stream_thread(&self, input: ListThreadInput, max_items: Option<usize>) -> ItemStream<'_, Entity> {
+        return paginate(input.start_cursor.clone(), max_items, move |cursor| {
+            let mut input = input.clone();
+            input.start_cursor = cursor;
+            async move {
+                let output = self.list_thread(input).await?;
+                Ok((output.items, output.next_cursor))
+            }
+        });
+    }
+}
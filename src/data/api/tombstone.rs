@@ -0,0 +1,104 @@
+//! Soft deletion: a deleted submission, comment or reply stays in its listings, with
+//! its counters, as a tombstone whose content is left blank by the reads.
+
+use aws_sdk_dynamodb::model::AttributeValue;
+use chrono::Utc;
+
+use crate::data::model::tombstone::DeletedIndexKey;
+
+use super::result::{Error, Result};
+use super::edit::time_value;
+use super::store::{
+    ContentStore,
+    Index,
+    Item,
+    Key,
+    Condition,
+    UpdateAction,
+    GetItemInput,
+    UpdateItemInput,
+};
+
+pub(crate) const DELETED_AT_ATTR: &str = "deleted_at";
+pub(crate) const DELETED_BY_ATTR: &str = "deleted_by";
+pub(crate) const PURGED_AT_ATTR: &str = "purged_at";
+
+/// The attributes of the content of the entities, blank in a tombstone.
+pub(crate) const CONTENT_ATTRS: [&str; 3] = ["title", "url", "text"];
+
+/// Blanks the content of the item if it is deleted, before it is decoded for a read.
+pub(crate) fn blank_deleted(item: &mut Item) {
+    if !item.contains_key(DELETED_AT_ATTR) {
+        return;
+    }
+
+    for attr in CONTENT_ATTRS {
+        if let Some(value) = item.get_mut(attr) {
+            *value = AttributeValue::S(String::new());
+        }
+    }
+}
+
+async fn get_raw(store: &dyn ContentStore, key: &Key) -> Result<Option<Item>> {
+    let mut input = GetItemInput::new(key.clone());
+    input.consistent_read = true;
+
+    return store.get_item(input).await;
+}
+
+/// Marks the entity with the key as deleted by `deleted_by`,
+/// deleting it again is a no-op, fails with `Error::NotFound` if there is none.
+///
+/// The tombstone is indexed by its deletion time in `GSI4`, for the purge.
+pub(crate) async fn soft_delete(store: &dyn ContentStore, target: Key, deleted_by: &str) -> Result<()> {
+    let deleted_at = Utc::now();
+    let deleted_key = DeletedIndexKey::new(&deleted_at, &target.pk);
+    let mut update = UpdateItemInput::new(target.clone(), vec![
+        UpdateAction::Set(DELETED_AT_ATTR.to_string(), time_value(&deleted_at)?),
+        UpdateAction::Set(DELETED_BY_ATTR.to_string(), AttributeValue::S(deleted_by.to_string())),
+        UpdateAction::Set(Index::Gsi4.pk_attr().to_string(), AttributeValue::S(deleted_key.pk)),
+        UpdateAction::Set(Index::Gsi4.sk_attr().to_string(), AttributeValue::S(deleted_key.sk)),
+    ]);
+    update.condition = Some(Condition::And(vec![
+        Condition::AttributeExists(Index::Primary.pk_attr().to_string()),
+        Condition::AttributeNotExists(DELETED_AT_ATTR.to_string()),
+    ]));
+
+    return match store.update_item(update).await {
+        Ok(_) => Ok(()),
+        Err(Error::ConditionFailed(_)) => match get_raw(store, &target).await? {
+            None => Err(Error::NotFound(target.pk)),
+            Some(_) => Ok(()),
+        },
+        Err(e) => Err(e),
+    };
+}
+
+/// Restores a deleted entity, restoring one that is not deleted is a no-op.
+///
+/// Fails with `Error::NotFound` if there is none, and with `Error::Conflict`
+/// if its content has been purged already.
+pub(crate) async fn undelete(store: &dyn ContentStore, target: Key) -> Result<()> {
+    let mut update = UpdateItemInput::new(target.clone(), vec![
+        UpdateAction::Remove(DELETED_AT_ATTR.to_string()),
+        UpdateAction::Remove(DELETED_BY_ATTR.to_string()),
+        UpdateAction::Remove(Index::Gsi4.pk_attr().to_string()),
+        UpdateAction::Remove(Index::Gsi4.sk_attr().to_string()),
+    ]);
+    update.condition = Some(Condition::And(vec![
+        Condition::AttributeExists(DELETED_AT_ATTR.to_string()),
+        Condition::AttributeNotExists(PURGED_AT_ATTR.to_string()),
+    ]));
+
+    return match store.update_item(update).await {
+        Ok(_) => Ok(()),
+        Err(Error::ConditionFailed(_)) => match get_raw(store, &target).await? {
+            None => Err(Error::NotFound(target.pk)),
+            Some(item) if item.contains_key(PURGED_AT_ATTR) => {
+                Err(Error::Conflict(format!("`{}` has been purged", target.pk)))
+            }
+            Some(_) => Ok(()),
+        },
+        Err(e) => Err(e),
+    };
+}
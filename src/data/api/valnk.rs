@@ -0,0 +1,111 @@
+use std::sync::Arc;
+
+use crate::config::ValnkConfig;
+
+use super::result::Result;
+use super::store::ContentStore;
+use super::{author, comment, reply, revision, submission, thread, vote};
+
+/// The entry point of the API: the clients of the entities on one store and one config.
+///
+/// It is cheap to clone, its clones share the store, and with it the DynamoDB client,
+/// so it can be kept in the managed state of Rocket or handed to background jobs.
+/// The clients it returns borrow it, one is made per call. This is the setup the
+/// examples of the clients and of the jobs start from.
+///
+/// # Example:
+///
+/// ```no_run
+/// use std::time::Duration;
+/// use tokio;
+/// use valnk::config::ValnkConfig;
+/// use valnk::data::api::Valnk;
+/// use valnk::data::api::purge::PurgeJob;
+/// use valnk::data::api::submission::ListItemsByTopicInput;
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let valnk = Valnk::from_config(ValnkConfig::load().unwrap()).await.unwrap();
+///
+///     let jobs = valnk.clone();
+///     tokio::spawn(async move {
+///         PurgeJob::new(Duration::from_secs(24 * 3600)).run(jobs.store()).await;
+///     });
+///
+///     let output = valnk.submissions()
+///         .list_items_by_topic(ListItemsByTopicInput::new("news"))
+///         .await
+///         .unwrap();
+/// }
+/// ```
+#[derive(Clone, Debug)]
+pub struct Valnk {
+    store: Arc<dyn ContentStore>,
+    config: Arc<ValnkConfig>,
+}
+
+impl Valnk {
+    /// # Examples
+    ///
+    /// ```
+    /// use valnk::config::ValnkConfig;
+    /// use valnk::data::api::Valnk;
+    /// use valnk::data::api::store::MemoryStore;
+    ///
+    /// let valnk = Valnk::new(MemoryStore::new(), ValnkConfig::default());
+    /// let shared = valnk.clone();
+    ///
+    /// std::thread::spawn(move || drop(shared.votes())).join().unwrap();
+    /// ```
+    pub fn new(store: impl ContentStore + 'static, config: ValnkConfig) -> Self {
+        return Self {
+            store: Arc::new(store),
+            config: Arc::new(config),
+        };
+    }
+
+    /// Opens the content table of the config, with a DynamoDB client made from it.
+    pub async fn from_config(config: ValnkConfig) -> Result<Self> {
+        let store = config.store().await?;
+
+        return Ok(Self::new(store, config));
+    }
+
+    pub fn config(&self) -> &ValnkConfig {
+        return &self.config;
+    }
+
+    /// The store of the clients, e.g. for the migration and purge jobs.
+    pub fn store(&self) -> &dyn ContentStore {
+        return self.store.as_ref();
+    }
+
+    pub fn submissions(&self) -> submission::Client<'_> {
+        return submission::Client::new(self.store()).with_page_limits(self.config.page_limits());
+    }
+
+    pub fn comments(&self) -> comment::Client<'_> {
+        return comment::Client::new(self.store()).with_page_limits(self.config.page_limits());
+    }
+
+    pub fn replies(&self) -> reply::Client<'_> {
+        return reply::Client::new(self.store()).with_page_limits(self.config.page_limits());
+    }
+
+    /// The activity of the users, what they have submitted, commented and replied.
+    pub fn users(&self) -> author::Client<'_> {
+        return author::Client::new(self.store()).with_page_limits(self.config.page_limits());
+    }
+
+    pub fn votes(&self) -> vote::Client<'_> {
+        return vote::Client::new(self.store());
+    }
+
+    pub fn threads(&self) -> thread::Client<'_> {
+        return thread::Client::new(self.store()).with_page_limits(self.config.page_limits());
+    }
+
+    pub fn revisions(&self) -> revision::Client<'_> {
+        return revision::Client::new(self.store()).with_page_limits(self.config.page_limits());
+    }
+}
@@ -0,0 +1,171 @@
+use serde_dynamo;
+
+use crate::data::model::entity::{EntityType, EntityId};
+use crate::data::model::submission as subm_model;
+use crate::data::model::comment as comm_model;
+use crate::data::model::vote::{PrimaryKey, VoteBuilder};
+
+use super::result::{Error, Result};
+use super::store::{
+    ContentStore,
+    Index,
+    Key,
+    Condition,
+    UpdateAction,
+    TransactWriteItem,
+    GetItemInput,
+    PutItemInput,
+    UpdateItemInput,
+    DeleteItemInput,
+    condition_failed,
+};
+
+/// The counter of the votes of a submission or a comment.
+const N_VOTES_ATTR: &str = "n_votes";
+
+#[derive(Clone, Debug)]
+pub struct VoteInput {
+    pub user_id: String,
+    pub target_type: EntityType,
+    pub target_id: EntityId,
+}
+
+impl VoteInput {
+    pub fn new(user_id: impl Into<String>, target_type: EntityType, target_id: EntityId) -> Self {
+        Self {
+            user_id: user_id.into(),
+            target_type,
+            target_id,
+        }
+    }
+}
+
+
+fn vote_key(input: &VoteInput) -> Key {
+    let pk = PrimaryKey::new(&input.user_id, &input.target_type, &input.target_id);
+    return Key::new(pk.pk, pk.sk);
+}
+
+fn target_key(input: &VoteInput) -> Result<Key> {
+    let (pk, sk) = match input.target_type {
+        EntityType::Submission => {
+            let pk = subm_model::PrimaryKey::new(&input.target_id);
+            (pk.pk, pk.sk)
+        }
+        EntityType::Comment => {
+            let pk = comm_model::PrimaryKey::new(&input.target_id);
+            (pk.pk, pk.sk)
+        }
+        _ => return Err(Error::BadRequest(format!("`{:?}` cannot be voted", input.target_type))),
+    };
+
+    return Ok(Key::new(pk, sk));
+}
+
+/// The votes of the users on submissions and comments.
+///
+/// A vote is written in the same transaction as the `n_votes` counter of its target,
+/// so voting twice or un-voting twice never changes the counter twice.
+#[derive(Debug)]
+pub struct Client<'c> {
+    store: &'c dyn ContentStore,
+}
+
+impl<'c> Client<'c> {
+    pub fn new(store: &'c dyn ContentStore) -> Self {
+        return Self {
+            store,
+        };
+    }
+
+    /// Up-votes a submission or a comment, returns `false` if the user has already voted on it.
+    ///
+    /// Fails with `Error::NotFound` if the target does not exist.
+    ///
+    /// # Example:
+    ///
+    /// ```no_run
+    /// use tokio;
+    /// use valnk::data::api::vote::*;
+    /// use valnk::data::api::store::{dynamodb_client, DynamoStore};
+    /// use valnk::data::model::entity::{EntityType, EntityId};
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let shared_config = aws_config::load_from_env().await;
+    ///     let aws_cli = dynamodb_client(&shared_config, None).unwrap();
+    ///     let store = DynamoStore::new(aws_cli, "valnk-content");
+    ///     let cli = Client::new(&store);
+    ///
+    ///     let input = VoteInput::new("py0x", EntityType::Submission, EntityId::from("subm-id").unwrap());
+    ///     let voted = cli.vote(input).await.unwrap();
+    /// }
+    /// ```
+    pub async fn vote(&self, input: VoteInput) -> Result<bool> {
+        let target = target_key(&input)?;
+
+        let vote = VoteBuilder::new()
+            .with_user_id(input.user_id.clone())
+            .with_target_type(input.target_type.clone())
+            .with_target_id(input.target_id.clone())
+            .build()
+            .map_err(|e| Error::BadRequest(e.to_string()))?;
+        let item = serde_dynamo::to_item(vote)
+            .map_err(Error::InvalidInputData)?;
+
+        let mut put = PutItemInput::new(item);
+        put.condition = Some(Condition::AttributeNotExists(Index::Primary.pk_attr().to_string()));
+
+        let mut update = UpdateItemInput::new(target.clone(), vec![
+            UpdateAction::Add(N_VOTES_ATTR.to_string(), 1),
+        ]);
+        update.condition = Some(Condition::AttributeExists(Index::Primary.pk_attr().to_string()));
+
+        let result = self.store
+            .transact_write_items(vec![TransactWriteItem::Put(put), TransactWriteItem::Update(update)])
+            .await;
+
+        return match result {
+            Ok(()) => Ok(true),
+            Err(Error::TransactionCancelled(reasons, _)) if condition_failed(&reasons, 1) => Err(Error::NotFound(target.pk)),
+            Err(Error::TransactionCancelled(reasons, _)) if condition_failed(&reasons, 0) => Ok(false),
+            Err(e) => Err(e),
+        };
+    }
+
+    /// Takes back the vote of a user, returns `false` if the user has not voted on the target.
+    ///
+    /// Fails with `Error::NotFound` if the target does not exist.
+    pub async fn unvote(&self, input: VoteInput) -> Result<bool> {
+        let target = target_key(&input)?;
+
+        let mut delete = DeleteItemInput::new(vote_key(&input));
+        delete.condition = Some(Condition::AttributeExists(Index::Primary.pk_attr().to_string()));
+
+        let mut update = UpdateItemInput::new(target.clone(), vec![
+            UpdateAction::Add(N_VOTES_ATTR.to_string(), -1),
+        ]);
+        update.condition = Some(Condition::AttributeExists(Index::Primary.pk_attr().to_string()));
+
+        let result = self.store
+            .transact_write_items(vec![TransactWriteItem::Delete(delete), TransactWriteItem::Update(update)])
+            .await;
+
+        return match result {
+            Ok(()) => Ok(true),
+            Err(Error::TransactionCancelled(reasons, _)) if condition_failed(&reasons, 1) => Err(Error::NotFound(target.pk)),
+            Err(Error::TransactionCancelled(reasons, _)) if condition_failed(&reasons, 0) => Ok(false),
+            Err(e) => Err(e),
+        };
+    }
+
+    /// Whether the user has voted on the target.
+    pub async fn has_voted(&self, input: VoteInput) -> Result<bool> {
+        let mut get = GetItemInput::new(vote_key(&input));
+        get.consistent_read = true;
+
+        let item = self.store.get_item(get).await?;
+
+        Ok(item.is_some())
+    }
+}
@@ -1,7 +1,14 @@
 pub mod entity;
+pub mod key_codec;
 pub mod submission;
 pub mod comment;
 pub mod reply;
+pub mod thread;
+pub mod vote;
+pub mod idempotency;
+pub mod revision;
+pub mod tombstone;
+pub mod ranking;
 
 
 #[cfg(test)]
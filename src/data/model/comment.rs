@@ -1,14 +1,12 @@
-use std::fmt;
-use std::ops::Sub;
 use serde::{Serialize, Deserialize};
-use serde_dynamo;
-use uuid::Uuid;
 use chrono::{DateTime, Utc};
 use thiserror::Error;
-use crate::data::model::reply::SubmissionCommentIndexKey;
 
 use super::entity::{EntityType, EntityId};
+use super::key_codec::{self, KeyCodecError};
 use super::submission::{SubmissionId, SUBMISSION_TAG};
+use super::tombstone::DeletedIndexKey;
+use super::thread::{self, ThreadIndexKey};
 
 pub const COMMENT_TAG: &str = "COMMT";
 const AUTHOR_TAG: &str = "AUTHR";
@@ -64,27 +62,43 @@ impl SubmissionIndexKey {
     ///
     /// ```
     /// use valnk::data::model::submission::SubmissionId;
-    /// use valnk::data::model::comment::SubmissionIndexKey;
+    /// use valnk::data::model::comment::{SubmissionIndexKey, CommentId};
     ///
     /// let subm = SubmissionId::from("submission_id_123").unwrap();
-    /// let score = 192;
+    /// let score = -192;
+    /// let id = CommentId::from("id1").unwrap();
     ///
-    /// let subm_key = SubmissionIndexKey::new(&subm, &score);
+    /// let subm_key = SubmissionIndexKey::new(&subm, &score, &id);
     /// let expected = SubmissionIndexKey {
     ///     pk: String::from("SUBMS#submission_id_123"),
-    ///     sk: String::from("COMMT#0000000192"),
+    ///     sk: String::from("COMMT#09223372036854775616#id1"),
     /// };
     /// assert_eq!(subm_key, expected);
+    /// assert_eq!(SubmissionIndexKey::decode_sk(&subm_key.sk), Ok((score, id)));
     /// ```
-    pub fn new(submission_id: &SubmissionId, score: &RankingScore) -> Self {
-        let pk = format!("{SUBMISSION_TAG}#{submission_id}");
-        let sk = format!("{COMMENT_TAG}#{score:010}");
-
+    pub fn new(submission_id: &SubmissionId, score: &RankingScore, id: &CommentId) -> Self {
         return Self {
-            pk,
-            sk,
+            pk: Self::pk(submission_id),
+            sk: Self::sk(score, id),
         };
     }
+
+    pub fn pk(submission_id: &SubmissionId) -> String {
+        format!("{SUBMISSION_TAG}#{submission_id}")
+    }
+
+    pub fn sk(score: &RankingScore, id: &CommentId) -> String {
+        return key_codec::encode_sort_key(&Self::sk_prefix(), *score, id);
+    }
+
+    /// The ranking score and the id of a sort key.
+    pub fn decode_sk(sk: &str) -> Result<(RankingScore, CommentId), KeyCodecError> {
+        return key_codec::decode_sort_key(&Self::sk_prefix(), sk);
+    }
+
+    pub fn sk_prefix() -> String {
+        return format!("{COMMENT_TAG}#");
+    }
 }
 
 /// For indexing comments by `author_id`.
@@ -100,31 +114,45 @@ impl AuthorIndexKey {
     /// # Examples
     ///
     /// ```
-    /// use valnk::data::model::comment::AuthorIndexKey;
-    /// use chrono::{DateTime, TimeZone, NaiveDateTime, Utc};
+    /// use valnk::data::model::comment::{AuthorIndexKey, CommentId};
+    /// use chrono::{TimeZone, Utc};
     ///
     /// let author_id = "py0x";
-    /// let created_at = DateTime::<Utc>::from_utc(NaiveDateTime::from_timestamp(1234,0), Utc);
+    /// let created_at = Utc.timestamp_opt(1234, 0).unwrap();
+    /// let id = CommentId::from("id1").unwrap();
     ///
-    /// let author_key = AuthorIndexKey::new(author_id, &created_at);
+    /// let author_key = AuthorIndexKey::new(author_id, &created_at, &id);
     /// let expected = AuthorIndexKey {
     ///     pk: String::from("AUTHR#py0x"),
-    ///     sk: String::from("COMMT#0000001234"),
+    ///     sk: String::from("COMMT#09223372036854777042#id1"),
     /// };
     ///
     /// assert_eq!(author_key, expected);
+    /// assert_eq!(AuthorIndexKey::decode_sk(&author_key.sk), Ok((created_at, id)));
     /// ```
-    pub fn new(author_id: &str, created_at: &DateTime<Utc>) -> Self {
-        let created_at_ts = created_at.timestamp();
-
-        let pk = format!("{AUTHOR_TAG}#{author_id}");
-        let sk = format!("{COMMENT_TAG}#{created_at_ts:010}");
-
+    pub fn new(author_id: &str, created_at: &DateTime<Utc>, id: &CommentId) -> Self {
         return Self {
-            pk,
-            sk,
+            pk: Self::pk(author_id),
+            sk: Self::sk(created_at, id),
         };
     }
+
+    pub fn pk(author_id: &str) -> String {
+        format!("{AUTHOR_TAG}#{author_id}")
+    }
+
+    pub fn sk(created_at: &DateTime<Utc>, id: &CommentId) -> String {
+        return key_codec::encode_sort_key(&Self::sk_prefix(), created_at.timestamp(), id);
+    }
+
+    /// The creation time and the id of a sort key.
+    pub fn decode_sk(sk: &str) -> Result<(DateTime<Utc>, CommentId), KeyCodecError> {
+        return key_codec::decode_time_sort_key(&Self::sk_prefix(), sk);
+    }
+
+    pub fn sk_prefix() -> String {
+        return format!("{COMMENT_TAG}#");
+    }
 }
 
 
@@ -137,6 +165,10 @@ pub struct Comment {
     pub submission_key: SubmissionIndexKey,
     #[serde(flatten)]
     pub author_key: AuthorIndexKey,
+    #[serde(flatten)]
+    pub deleted_key: DeletedIndexKey,
+    #[serde(flatten)]
+    pub thread_key: ThreadIndexKey,
 
     // data fields
     pub entity_type: EntityType,
@@ -146,12 +178,46 @@ pub struct Comment {
     pub author_id: String,
     pub ranking_score: RankingScore,
     pub text: String,
+    /// The materialised path of the comment, the root of its tree, see [`thread`](super::thread).
+    #[serde(default)]
+    pub path: String,
 
     pub n_votes: u64,
+    /// The number of the direct replies to the comment; the replies to
+    /// those are counted on the replies themselves.
     pub n_replies: u64,
 
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
+
+    /// When the comment was deleted, it stays in its thread as a tombstone until it is purged.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deleted_at: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deleted_by: Option<String>,
+    /// When the content of the deleted comment was erased for good, the purge moves it
+    /// forward each time it finds the comment still has children.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub purged_at: Option<DateTime<Utc>>,
+}
+
+impl Comment {
+    /// Rebuilds the index keys from the data fields, e.g. to migrate
+    /// an item written with an older key format.
+    pub fn rebuild_keys(&mut self) {
+        self.primary_key = PrimaryKey::new(&self.id);
+        self.deleted_key = DeletedIndexKey::of(&self.primary_key.pk, self.deleted_at.as_ref(), self.purged_at.as_ref());
+        self.submission_key = SubmissionIndexKey::new(&self.submission_id, &self.ranking_score, &self.id);
+        self.author_key = AuthorIndexKey::new(&self.author_id, &self.created_at, &self.id);
+        if self.path.is_empty() {
+            self.path = thread::segment(&self.created_at, &self.id);
+        }
+        self.thread_key = ThreadIndexKey::new(&self.submission_id, &self.path);
+    }
+
+    pub fn is_deleted(&self) -> bool {
+        return self.deleted_at.is_some();
+    }
 }
 
 #[derive(Default, Serialize, Deserialize, PartialEq, Debug)]
@@ -249,6 +315,8 @@ impl CommentBuilder {
     /// use valnk::data::model::entity::EntityType;
     /// use valnk::data::model::submission::SubmissionId;
     /// use valnk::data::model::comment::*;
+    /// use valnk::data::model::tombstone::DeletedIndexKey;
+    /// use valnk::data::model::thread::{segment, ThreadIndexKey};
     ///
     /// let current_dt = DateTime::<Utc>::from_utc(NaiveDateTime::from_timestamp(1234,0), Utc);
     /// let result = CommentBuilder::new()
@@ -263,26 +331,33 @@ impl CommentBuilder {
     ///     .unwrap();
     ///
     /// let submission_id = SubmissionId::from("subm111").unwrap();
+    /// let path = segment(&current_dt, &CommentId::from("id111").unwrap());
     /// let expected = Comment{
     ///     primary_key: PrimaryKey::new(&CommentId::from("id111").unwrap()),
-    ///     submission_key: SubmissionIndexKey::new(&submission_id, &999),
-    ///     author_key: AuthorIndexKey::new("author111", &current_dt),
+    ///     submission_key: SubmissionIndexKey::new(&submission_id, &999, &CommentId::from("id111").unwrap()),
+    ///     author_key: AuthorIndexKey::new("author111", &current_dt, &CommentId::from("id111").unwrap()),
+    ///     deleted_key: DeletedIndexKey::default(),
+    ///     thread_key: ThreadIndexKey::new(&submission_id, &path),
     ///     entity_type: EntityType::Comment,
     ///     id: CommentId::from("id111").unwrap(),
     ///     submission_id: submission_id.clone(),
     ///     author_id: "author111".to_string(),
     ///     ranking_score: 999,
     ///     text: "text111".to_string(),
+    ///     path: path.clone(),
     ///     n_votes: 0,
     ///     n_replies: 0,
     ///     created_at: current_dt,
     ///     updated_at: current_dt,
+    ///     deleted_at: None,
+    ///     deleted_by: None,
+    ///     purged_at: None,
     /// };
     ///
     /// assert_eq!(result, expected);
     /// ```
     pub fn build(self) -> Result<Comment, CommentBuildError> {
-        let id = self.id.unwrap_or(CommentId::new());
+        let id = self.id.unwrap_or_default();
 
         let submission_id = self.submission_id.ok_or(
             CommentBuildError::EmptyData("submission_id".to_string())
@@ -310,23 +385,31 @@ impl CommentBuilder {
 
 
         let primary_key = PrimaryKey::new(&id);
-        let submission_key = SubmissionIndexKey::new(&submission_id, &ranking_score);
-        let author_key = AuthorIndexKey::new(&author_id, &created_at);
+        let submission_key = SubmissionIndexKey::new(&submission_id, &ranking_score, &id);
+        let author_key = AuthorIndexKey::new(&author_id, &created_at, &id);
+        let path = thread::segment(&created_at, &id);
+        let thread_key = ThreadIndexKey::new(&submission_id, &path);
 
         Ok(Comment {
             primary_key,
             submission_key,
             author_key,
+            deleted_key: DeletedIndexKey::default(),
+            thread_key,
             entity_type: EntityType::Comment,
             id,
             submission_id,
             author_id,
             ranking_score,
             text,
+            path,
             n_votes,
             n_replies,
             created_at,
             updated_at,
+            deleted_at: None,
+            deleted_by: None,
+            purged_at: None,
         })
     }
 }
\ No newline at end of file
@@ -1,6 +1,14 @@
 use std::fmt;
 use serde::{Serialize, Deserialize};
 use uuid::Uuid;
+use chrono::{DateTime, Utc};
+
+use super::submission::{Submission, SUBMISSION_TAG};
+use super::comment::{Comment, COMMENT_TAG};
+use super::reply::{Reply, REPLY_TAG};
+use super::vote::VOTE_TAG;
+use super::idempotency::IDEMPOTENCY_TAG;
+use super::revision::REVISION_TAG;
 
 #[derive(Clone, Serialize, Deserialize, PartialEq, Debug)]
 #[serde(rename_all = "snake_case")]
@@ -8,6 +16,23 @@ pub enum EntityType {
     Submission,
     Comment,
     Reply,
+    Vote,
+    IdempotencyKey,
+    Revision,
+}
+
+impl EntityType {
+    /// The prefix of the primary key of the entity type, e.g. `SUBMS`.
+    pub fn tag(&self) -> &'static str {
+        return match self {
+            EntityType::Submission => SUBMISSION_TAG,
+            EntityType::Comment => COMMENT_TAG,
+            EntityType::Reply => REPLY_TAG,
+            EntityType::Vote => VOTE_TAG,
+            EntityType::IdempotencyKey => IDEMPOTENCY_TAG,
+            EntityType::Revision => REVISION_TAG,
+        };
+    }
 }
 
 #[derive(Clone, Serialize, Deserialize, PartialEq, Debug)]
@@ -22,7 +47,7 @@ impl EntityId {
 
     pub fn from(id: impl Into<String>) -> Result<EntityId, String> {
         let id_str = id.into();
-        if id_str.len() > 0 {
+        if !id_str.is_empty() {
             return Ok(EntityId(id_str));
         }
 
@@ -30,6 +55,12 @@ impl EntityId {
     }
 }
 
+impl Default for EntityId {
+    fn default() -> Self {
+        return EntityId::new();
+    }
+}
+
 impl AsRef<str> for EntityId {
     fn as_ref(&self) -> &str {
         return &self.0;
@@ -40,4 +71,89 @@ impl fmt::Display for EntityId {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "{}", self.0)
     }
-}
\ No newline at end of file
+}
+
+
+/// Any content entity stored in the content table, tagged by its `entity_type`.
+#[derive(Clone, PartialEq, Debug)]
+pub enum Entity {
+    Submission(Submission),
+    Comment(Comment),
+    Reply(Reply),
+}
+
+impl Entity {
+    pub fn entity_type(&self) -> EntityType {
+        return match self {
+            Entity::Submission(_) => EntityType::Submission,
+            Entity::Comment(_) => EntityType::Comment,
+            Entity::Reply(_) => EntityType::Reply,
+        };
+    }
+
+    pub fn id(&self) -> &EntityId {
+        return match self {
+            Entity::Submission(subm) => &subm.id,
+            Entity::Comment(comm) => &comm.id,
+            Entity::Reply(reply) => &reply.id,
+        };
+    }
+
+    pub fn author_id(&self) -> &str {
+        return match self {
+            Entity::Submission(subm) => &subm.author_id,
+            Entity::Comment(comm) => &comm.author_id,
+            Entity::Reply(reply) => &reply.author_id,
+        };
+    }
+
+    pub fn rebuild_keys(&mut self) {
+        match self {
+            Entity::Submission(subm) => subm.rebuild_keys(),
+            Entity::Comment(comm) => comm.rebuild_keys(),
+            Entity::Reply(reply) => reply.rebuild_keys(),
+        }
+    }
+
+    pub fn created_at(&self) -> &DateTime<Utc> {
+        return match self {
+            Entity::Submission(subm) => &subm.created_at,
+            Entity::Comment(comm) => &comm.created_at,
+            Entity::Reply(reply) => &reply.created_at,
+        };
+    }
+
+    pub fn deleted_at(&self) -> Option<&DateTime<Utc>> {
+        return match self {
+            Entity::Submission(subm) => subm.deleted_at.as_ref(),
+            Entity::Comment(comm) => comm.deleted_at.as_ref(),
+            Entity::Reply(reply) => reply.deleted_at.as_ref(),
+        };
+    }
+
+    pub fn purged_at(&self) -> Option<&DateTime<Utc>> {
+        return match self {
+            Entity::Submission(subm) => subm.purged_at.as_ref(),
+            Entity::Comment(comm) => comm.purged_at.as_ref(),
+            Entity::Reply(reply) => reply.purged_at.as_ref(),
+        };
+    }
+}
+
+impl From<Submission> for Entity {
+    fn from(subm: Submission) -> Self {
+        return Entity::Submission(subm);
+    }
+}
+
+impl From<Comment> for Entity {
+    fn from(comm: Comment) -> Self {
+        return Entity::Comment(comm);
+    }
+}
+
+impl From<Reply> for Entity {
+    fn from(reply: Reply) -> Self {
+        return Entity::Reply(reply);
+    }
+}
@@ -0,0 +1,164 @@
+use serde::{Serialize, Deserialize};
+use chrono::{DateTime, Duration, Utc};
+use sha2::{Digest, Sha256};
+
+use super::entity::{EntityType, EntityId};
+use super::tombstone::TargetIndexKey;
+
+pub const IDEMPOTENCY_TAG: &str = "IDEMP";
+
+/// How long a key is remembered, DynamoDB deletes the expired records (on `expires_at`) later.
+pub const IDEMPOTENCY_TTL_HOURS: i64 = 24;
+
+fn hex_digest(digest: &[u8]) -> String {
+    return digest.iter().map(|b| format!("{b:02x}")).collect();
+}
+
+/// The digest of the fields of a create request, so a key that is used again
+/// for another request is told apart. The names and values are hashed with their
+/// lengths, so two different lists of fields never run together.
+///
+/// # Examples:
+///
+/// ```
+/// use valnk::data::model::idempotency::request_digest;
+///
+/// let digest = request_digest(&[("title", "hello"), ("text", "")]);
+///
+/// assert_eq!(digest, request_digest(&[("title", "hello"), ("text", "")]));
+/// assert_ne!(digest, request_digest(&[("title", "hell"), ("text", "o")]));
+/// ```
+pub fn request_digest(fields: &[(&str, &str)]) -> String {
+    let mut hasher = Sha256::new();
+    for (name, value) in fields {
+        for part in [name, value] {
+            hasher.update((part.len() as u64).to_be_bytes());
+            hasher.update(part.as_bytes());
+        }
+    }
+
+    return hex_digest(&hasher.finalize());
+}
+
+/// The PrimaryKey of the `idempotency_key` item.
+///
+/// The keys are chosen by the clients, so they are scoped by author. Both may hold
+/// a `#`, so the key is hashed: its digest has a fixed width and no `#`, and the keys
+/// of two authors never meet.
+#[derive(Clone, Serialize, Deserialize, PartialEq, Debug)]
+pub struct PrimaryKey {
+    #[serde(rename(serialize = "PK", deserialize = "PK"))]
+    pub pk: String,
+    #[serde(rename(serialize = "SK", deserialize = "SK"))]
+    pub sk: String,
+}
+
+impl PrimaryKey {
+    /// # Examples:
+    ///
+    /// ```
+    /// use valnk::data::model::idempotency::PrimaryKey;
+    ///
+    /// let pk = PrimaryKey::new("py0x", "post-1234");
+    ///
+    /// assert!(pk.pk.starts_with("IDEMP#py0x#"));
+    /// assert_eq!(pk.pk.len(), "IDEMP#py0x#".len() + 64);
+    /// assert_eq!(pk.sk, pk.pk);
+    /// assert_ne!(PrimaryKey::new("a", "b#c"), PrimaryKey::new("a#b", "c"));
+    /// ```
+    pub fn new(author_id: &str, key: &str) -> Self {
+        let key_digest = hex_digest(&Sha256::digest(key.as_bytes()));
+        let pk = format!("{IDEMPOTENCY_TAG}#{author_id}#{key_digest}");
+        let sk = pk.clone();
+
+        return Self {
+            pk,
+            sk,
+        };
+    }
+}
+
+
+/// The record of an entity created with an idempotency key,
+/// a retried create with the same key returns the entity instead of creating another one.
+#[derive(Clone, Serialize, Deserialize, PartialEq, Debug)]
+pub struct IdempotencyRecord {
+    // index-key fields
+    #[serde(flatten)]
+    pub primary_key: PrimaryKey,
+    /// The record is removed with the created entity when the entity is purged.
+    #[serde(flatten)]
+    pub target_key: TargetIndexKey,
+
+    // data fields
+    pub entity_type: EntityType,
+
+    pub author_id: String,
+    pub key: String,
+
+    pub target_type: EntityType,
+    pub target_id: EntityId,
+    /// The primary key of the created entity.
+    pub target_pk: String,
+    pub target_sk: String,
+    /// The [`request_digest`] of the create, `None` for the records written before it was kept.
+    #[serde(default)]
+    pub request_digest: Option<String>,
+
+    pub created_at: DateTime<Utc>,
+    /// The unix timestamp the record expires at, the TTL attribute of the table.
+    pub expires_at: i64,
+}
+
+impl IdempotencyRecord {
+    /// # Examples:
+    ///
+    /// ```
+    /// use chrono::{TimeZone, Utc};
+    /// use valnk::data::model::entity::{EntityType, EntityId};
+    /// use valnk::data::model::idempotency::{IdempotencyRecord, PrimaryKey};
+    ///
+    /// let created_at = Utc.timestamp_opt(1000, 0).unwrap();
+    /// let id = EntityId::from("id1").unwrap();
+    /// let record = IdempotencyRecord::new("py0x", "post-1234", EntityType::Submission, id, "SUBMS#id1", "SUBMS#id1", created_at);
+    ///
+    /// assert_eq!(record.primary_key, PrimaryKey::new("py0x", "post-1234"));
+    /// assert_eq!(record.target_key.pk, "TARGT#SUBMS#id1");
+    /// assert_eq!(record.expires_at, 1000 + 24 * 3600);
+    /// ```
+    pub fn new(
+        author_id: impl Into<String>,
+        key: impl Into<String>,
+        target_type: EntityType,
+        target_id: EntityId,
+        target_pk: impl Into<String>,
+        target_sk: impl Into<String>,
+        created_at: DateTime<Utc>,
+    ) -> Self {
+        let author_id = author_id.into();
+        let key = key.into();
+        let expires_at = (created_at + Duration::hours(IDEMPOTENCY_TTL_HOURS)).timestamp();
+        let primary_key = PrimaryKey::new(&author_id, &key);
+        let target_pk = target_pk.into();
+
+        return Self {
+            target_key: TargetIndexKey::new(&target_pk, &primary_key.pk),
+            primary_key,
+            entity_type: EntityType::IdempotencyKey,
+            author_id,
+            key,
+            target_type,
+            target_id,
+            target_pk,
+            target_sk: target_sk.into(),
+            request_digest: None,
+            created_at,
+            expires_at,
+        };
+    }
+
+    pub fn with_request_digest(mut self, request_digest: impl Into<String>) -> Self {
+        self.request_digest = Some(request_digest.into());
+        self
+    }
+}
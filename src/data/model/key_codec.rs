@@ -0,0 +1,135 @@
+//! Order-preserving string encodings of the numbers in the index sort keys.
+//!
+//! DynamoDB compares string sort keys byte by byte, so a signed `i64` is shifted into
+//! the `u64` range (flipping its sign bit) and written with a fixed width of 20 digits:
+//! the lexicographic order of the encoded strings is the numeric order of the values.
+//!
+//! An index sort key is `<prefix><value>#<id>`, the id of the entity breaks the ties
+//! between equal values, so the order of the items in an index is stable.
+
+use chrono::{DateTime, TimeZone, Utc};
+use thiserror::Error;
+
+use super::entity::EntityId;
+
+/// The length of an encoded `i64`.
+pub const ENCODED_LEN: usize = 20;
+
+#[derive(Error, Clone, PartialEq, Debug)]
+pub enum KeyCodecError {
+    #[error("the key `{0}` does not start with `{1}`")]
+    MissingPrefix(String, String),
+
+    #[error("the key `{0}` has no valid encoded value")]
+    InvalidValue(String),
+
+    #[error("the key `{0}` has no entity id")]
+    MissingId(String),
+}
+
+/// # Examples
+///
+/// ```
+/// use valnk::data::model::key_codec::encode_i64;
+///
+/// assert_eq!(encode_i64(0), "09223372036854775808");
+/// assert!(encode_i64(-5) < encode_i64(-1));
+/// assert!(encode_i64(-1) < encode_i64(0));
+/// assert!(encode_i64(9_999_999_999) < encode_i64(10_000_000_000));
+/// assert!(encode_i64(i64::MIN) < encode_i64(i64::MAX));
+/// ```
+pub fn encode_i64(n: i64) -> String {
+    let shifted = (n as u64) ^ (1 << 63);
+    return format!("{shifted:0ENCODED_LEN$}");
+}
+
+/// # Examples
+///
+/// ```
+/// use valnk::data::model::key_codec::{encode_i64, decode_i64};
+///
+/// for n in [i64::MIN, -42, 0, 42, i64::MAX] {
+///     assert_eq!(decode_i64(&encode_i64(n)), Ok(n));
+/// }
+/// assert!(decode_i64("42").is_err());
+/// ```
+pub fn decode_i64(s: &str) -> Result<i64, KeyCodecError> {
+    if s.len() != ENCODED_LEN {
+        return Err(KeyCodecError::InvalidValue(s.to_string()));
+    }
+
+    let shifted: u64 = s.parse()
+        .map_err(|_| KeyCodecError::InvalidValue(s.to_string()))?;
+
+    return Ok((shifted ^ (1 << 63)) as i64);
+}
+
+/// Encodes a time as its unix timestamp, in seconds.
+pub fn encode_timestamp(dt: &DateTime<Utc>) -> String {
+    return encode_i64(dt.timestamp());
+}
+
+pub fn decode_timestamp(s: &str) -> Result<DateTime<Utc>, KeyCodecError> {
+    let ts = decode_i64(s)?;
+
+    return timestamp_to_time(ts, s);
+}
+
+fn timestamp_to_time(ts: i64, key: &str) -> Result<DateTime<Utc>, KeyCodecError> {
+    return Utc.timestamp_opt(ts, 0)
+        .single()
+        .ok_or(KeyCodecError::InvalidValue(key.to_string()));
+}
+
+/// `<prefix><value>#<id>`
+///
+/// # Examples
+///
+/// ```
+/// use valnk::data::model::entity::EntityId;
+/// use valnk::data::model::key_codec::{encode_sort_key, decode_sort_key};
+///
+/// let id = EntityId::from("id1").unwrap();
+/// let sk = encode_sort_key("SUBMS#", -3, &id);
+///
+/// assert_eq!(sk, "SUBMS#09223372036854775805#id1");
+/// assert_eq!(decode_sort_key("SUBMS#", &sk), Ok((-3, id)));
+/// ```
+pub fn encode_sort_key(prefix: &str, value: i64, id: &EntityId) -> String {
+    return format!("{prefix}{}#{id}", encode_i64(value));
+}
+
+pub fn decode_sort_key(prefix: &str, sk: &str) -> Result<(i64, EntityId), KeyCodecError> {
+    let rest = sk.strip_prefix(prefix)
+        .ok_or(KeyCodecError::MissingPrefix(sk.to_string(), prefix.to_string()))?;
+
+    let (value, id) = match rest.get(..ENCODED_LEN).zip(rest.get(ENCODED_LEN..)) {
+        Some(parts) => parts,
+        None => return Err(KeyCodecError::InvalidValue(sk.to_string())),
+    };
+    let value = decode_i64(value)?;
+
+    let id = id.strip_prefix('#')
+        .and_then(|id| EntityId::from(id).ok())
+        .ok_or(KeyCodecError::MissingId(sk.to_string()))?;
+
+    return Ok((value, id));
+}
+
+/// Decodes a `<prefix><timestamp>#<id>` sort key.
+pub fn decode_time_sort_key(prefix: &str, sk: &str) -> Result<(DateTime<Utc>, EntityId), KeyCodecError> {
+    let (ts, id) = decode_sort_key(prefix, sk)?;
+
+    return Ok((timestamp_to_time(ts, sk)?, id));
+}
+
+/// A bound that sorts before every sort key of the value, whatever its id.
+pub fn sort_key_start(prefix: &str, value: i64) -> String {
+    return format!("{prefix}{}", encode_i64(value));
+}
+
+/// A bound that sorts after every sort key of the value, whatever its id,
+/// `$` follows the `#` before the id.
+pub fn sort_key_end(prefix: &str, value: i64) -> String {
+    return format!("{prefix}{}$", encode_i64(value));
+}
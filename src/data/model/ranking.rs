@@ -0,0 +1,62 @@
+use chrono::{DateTime, Utc};
+
+use super::submission::{Submission, RankingScore};
+
+/// The parameters of the HN-style "hot" ranking:
+///
+/// `score = scale * points / (age_in_hours + 2) ^ gravity`,
+/// where `points = 1 + n_votes + comment_weight * n_comments`.
+///
+/// The `1` stands for the submission itself, so new submissions without votes
+/// still rank above older ones.
+#[derive(Clone, PartialEq, Debug)]
+pub struct HotRanking {
+    /// How fast the score decays with age.
+    pub gravity: f64,
+    /// How many votes a comment is worth.
+    pub comment_weight: f64,
+    /// The factor that turns the score into an integer `RankingScore`.
+    pub scale: f64,
+}
+
+impl Default for HotRanking {
+    fn default() -> Self {
+        return Self {
+            gravity: 1.8,
+            comment_weight: 0.5,
+            scale: 10_000.0,
+        };
+    }
+}
+
+impl HotRanking {
+    /// # Examples
+    ///
+    /// ```
+    /// use chrono::{Duration, TimeZone, Utc};
+    /// use valnk::data::model::ranking::HotRanking;
+    ///
+    /// let ranking = HotRanking::default();
+    /// let now = Utc.timestamp_opt(100_000, 0).unwrap();
+    ///
+    /// let fresh = ranking.score(10, 0, &now, &now);
+    /// let old = ranking.score(10, 0, &(now - Duration::hours(24)), &now);
+    /// let discussed = ranking.score(10, 20, &now, &now);
+    ///
+    /// assert!(fresh > old);
+    /// assert!(discussed > fresh);
+    /// ```
+    pub fn score(&self, n_votes: u64, n_comments: u64, created_at: &DateTime<Utc>, now: &DateTime<Utc>) -> RankingScore {
+        let points = 1.0 + n_votes as f64 + self.comment_weight * n_comments as f64;
+        // a submission from the future (clock skew) counts as a brand new one
+        let age_hours = (*now - *created_at).num_seconds().max(0) as f64 / 3600.0;
+
+        let score = self.scale * points / (age_hours + 2.0).powf(self.gravity);
+
+        return score.round() as RankingScore;
+    }
+
+    pub fn score_submission(&self, subm: &Submission, now: &DateTime<Utc>) -> RankingScore {
+        return self.score(subm.n_votes, subm.n_comments, &subm.created_at, now);
+    }
+}
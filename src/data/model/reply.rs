@@ -1,14 +1,13 @@
-use std::fmt;
-use std::ops::Sub;
 use serde::{Serialize, Deserialize};
-use serde_dynamo;
-use uuid::Uuid;
 use chrono::{DateTime, Utc};
 use thiserror::Error;
 
 use super::entity::{EntityType, EntityId};
+use super::key_codec::{self, KeyCodecError};
 use super::submission::{SubmissionId, SUBMISSION_TAG};
-use super::comment::{CommentId, COMMENT_TAG};
+use super::comment::CommentId;
+use super::tombstone::DeletedIndexKey;
+use super::thread::{self, ThreadIndexKey};
 
 pub const REPLY_TAG: &str = "REPLY";
 const AUTHOR_TAG: &str = "AUTHR";
@@ -64,31 +63,61 @@ impl SubmissionCommentIndexKey {
     /// ```
     /// use valnk::data::model::submission::SubmissionId;
     /// use valnk::data::model::comment::CommentId;
-    /// use valnk::data::model::reply::SubmissionCommentIndexKey;
-    /// use chrono::{DateTime, TimeZone, NaiveDateTime, Utc};
+    /// use valnk::data::model::reply::{SubmissionCommentIndexKey, ReplyId};
+    /// use chrono::{TimeZone, Utc};
     ///
     /// let subm = SubmissionId::from("submission_id_123").unwrap();
     /// let comm = CommentId::from("comment_id_123").unwrap();
-    /// let created_at = DateTime::<Utc>::from_utc(NaiveDateTime::from_timestamp(1234,0), Utc);
+    /// let created_at = Utc.timestamp_opt(1234, 0).unwrap();
+    /// let id = ReplyId::from("id1").unwrap();
     ///
-    /// let subm_key = SubmissionCommentIndexKey::new(&subm, &comm, &created_at);
+    /// let subm_key = SubmissionCommentIndexKey::new(&subm, &comm, &created_at, &id);
     /// let expected = SubmissionCommentIndexKey {
     ///     pk: String::from("SUBMS#submission_id_123"),
-    ///     sk: String::from("REPLY#comment_id_123#0000001234"),
+    ///     sk: String::from("REPLY#comment_id_123#09223372036854777042#id1"),
     /// };
     /// assert_eq!(subm_key, expected);
+    /// assert_eq!(SubmissionCommentIndexKey::decode_sk(&comm, &subm_key.sk), Ok((created_at, id)));
     /// ```
-    pub fn new(submission_id: &SubmissionId, comment_id: &CommentId, created_at: &DateTime<Utc>) -> Self {
-        let created_at_ts = created_at.timestamp();
-
-        let pk = format!("{SUBMISSION_TAG}#{submission_id}");
-        let sk = format!("{REPLY_TAG}#{comment_id}#{created_at_ts:010}");
+    pub fn new(submission_id: &SubmissionId, comment_id: &CommentId, created_at: &DateTime<Utc>, id: &ReplyId) -> Self {
+        let pk = Self::pk(submission_id);
+        let pfx = Self::comment_sk_prefix(comment_id);
+        let sk = key_codec::encode_sort_key(&pfx, created_at.timestamp(), id);
 
         return Self {
             pk,
             sk,
         };
     }
+
+    pub fn pk(submission_id: &SubmissionId) -> String {
+        format!("{SUBMISSION_TAG}#{submission_id}")
+    }
+
+    /// The sort-key prefix of all the replies of a submission.
+    pub fn sk_prefix() -> String {
+        return format!("{REPLY_TAG}#");
+    }
+
+    /// The sort-key prefix of the replies to one comment.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use valnk::data::model::comment::CommentId;
+    /// use valnk::data::model::reply::SubmissionCommentIndexKey;
+    ///
+    /// let comm = CommentId::from("comment_id_123").unwrap();
+    /// assert_eq!(SubmissionCommentIndexKey::comment_sk_prefix(&comm), "REPLY#comment_id_123#");
+    /// ```
+    pub fn comment_sk_prefix(comment_id: &CommentId) -> String {
+        return format!("{REPLY_TAG}#{comment_id}#");
+    }
+
+    /// The creation time and the id of the sort key of a reply to the comment.
+    pub fn decode_sk(comment_id: &CommentId, sk: &str) -> Result<(DateTime<Utc>, ReplyId), KeyCodecError> {
+        return key_codec::decode_time_sort_key(&Self::comment_sk_prefix(comment_id), sk);
+    }
 }
 
 /// For indexing replys by `author_id`.
@@ -104,31 +133,45 @@ impl AuthorIndexKey {
     /// # Examples
     ///
     /// ```
-    /// use valnk::data::model::reply::AuthorIndexKey;
-    /// use chrono::{DateTime, TimeZone, NaiveDateTime, Utc};
+    /// use valnk::data::model::reply::{AuthorIndexKey, ReplyId};
+    /// use chrono::{TimeZone, Utc};
     ///
     /// let author_id = "py0x";
-    /// let created_at = DateTime::<Utc>::from_utc(NaiveDateTime::from_timestamp(1234,0), Utc);
+    /// let created_at = Utc.timestamp_opt(1234, 0).unwrap();
+    /// let id = ReplyId::from("id1").unwrap();
     ///
-    /// let author_key = AuthorIndexKey::new(author_id, &created_at);
+    /// let author_key = AuthorIndexKey::new(author_id, &created_at, &id);
     /// let expected = AuthorIndexKey {
     ///     pk: String::from("AUTHR#py0x"),
-    ///     sk: String::from("REPLY#0000001234"),
+    ///     sk: String::from("REPLY#09223372036854777042#id1"),
     /// };
     ///
     /// assert_eq!(author_key, expected);
+    /// assert_eq!(AuthorIndexKey::decode_sk(&author_key.sk), Ok((created_at, id)));
     /// ```
-    pub fn new(author_id: &str, created_at: &DateTime<Utc>) -> Self {
-        let created_at_ts = created_at.timestamp();
-
-        let pk = format!("{AUTHOR_TAG}#{author_id}");
-        let sk = format!("{REPLY_TAG}#{created_at_ts:010}");
-
+    pub fn new(author_id: &str, created_at: &DateTime<Utc>, id: &ReplyId) -> Self {
         return Self {
-            pk,
-            sk,
+            pk: Self::pk(author_id),
+            sk: Self::sk(created_at, id),
         };
     }
+
+    pub fn pk(author_id: &str) -> String {
+        format!("{AUTHOR_TAG}#{author_id}")
+    }
+
+    pub fn sk(created_at: &DateTime<Utc>, id: &ReplyId) -> String {
+        return key_codec::encode_sort_key(&Self::sk_prefix(), created_at.timestamp(), id);
+    }
+
+    /// The creation time and the id of a sort key.
+    pub fn decode_sk(sk: &str) -> Result<(DateTime<Utc>, ReplyId), KeyCodecError> {
+        return key_codec::decode_time_sort_key(&Self::sk_prefix(), sk);
+    }
+
+    pub fn sk_prefix() -> String {
+        return format!("{REPLY_TAG}#");
+    }
 }
 
 #[derive(Clone, Serialize, Deserialize, PartialEq, Debug)]
@@ -140,26 +183,101 @@ pub struct Reply {
     pub submission_comment_key: SubmissionCommentIndexKey,
     #[serde(flatten)]
     pub author_key: AuthorIndexKey,
+    #[serde(flatten)]
+    pub deleted_key: DeletedIndexKey,
+    #[serde(flatten)]
+    pub thread_key: ThreadIndexKey,
 
     // data fields
     pub entity_type: EntityType,
 
     pub id: ReplyId,
     pub submission_id: SubmissionId,
+    /// The comment at the root of the tree of the reply.
     pub comment_id: CommentId,
+    /// The reply this one answers, `None` for a reply to the comment itself.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parent_id: Option<ReplyId>,
     pub author_id: String,
     pub text: String,
+    /// The materialised path of the reply, under the one of its parent,
+    /// see [`thread`](super::thread).
+    #[serde(default)]
+    pub path: String,
+
+    /// The number of the direct replies to this one, like
+    /// [`Comment::n_replies`](super::comment::Comment::n_replies).
+    #[serde(default)]
+    pub n_replies: u64,
 
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
+
+    /// When the reply was deleted, it stays in its thread as a tombstone until it is purged.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deleted_at: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deleted_by: Option<String>,
+    /// When the content of the deleted reply was erased for good, the purge moves it
+    /// forward each time it finds the reply still has children.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub purged_at: Option<DateTime<Utc>>,
 }
 
 
+impl Reply {
+    /// Rebuilds the index keys from the data fields, e.g. to migrate
+    /// an item written with an older key format.
+    pub fn rebuild_keys(&mut self) {
+        self.primary_key = PrimaryKey::new(&self.id);
+        self.deleted_key = DeletedIndexKey::of(&self.primary_key.pk, self.deleted_at.as_ref(), self.purged_at.as_ref());
+        self.submission_comment_key = SubmissionCommentIndexKey::new(&self.submission_id, &self.comment_id, &self.created_at, &self.id);
+        self.author_key = AuthorIndexKey::new(&self.author_id, &self.created_at, &self.id);
+        if !self.path.is_empty() {
+            self.thread_key = ThreadIndexKey::new(&self.submission_id, &self.path);
+        }
+    }
+
+    /// Places the reply in its tree, under the node with `parent_path`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use valnk::data::model::submission::SubmissionId;
+    /// use valnk::data::model::comment::CommentId;
+    /// use valnk::data::model::reply::ReplyBuilder;
+    /// use valnk::data::model::thread::depth;
+    ///
+    /// let mut reply = ReplyBuilder::new()
+    ///     .with_submission_id(SubmissionId::from("s1").unwrap())
+    ///     .with_comment_id(CommentId::from("c1").unwrap())
+    ///     .with_author_id("py0x")
+    ///     .with_text("hello")
+    ///     .build()
+    ///     .unwrap();
+    /// assert!(reply.path.is_empty());
+    ///
+    /// reply.place_under("0001");
+    /// assert!(reply.path.starts_with("0001/"));
+    /// assert_eq!(depth(&reply.path), 1);
+    /// assert_eq!(reply.thread_key.pk, "SUBMS#s1");
+    /// ```
+    pub fn place_under(&mut self, parent_path: &str) {
+        self.path = thread::child_path(parent_path, &self.created_at, &self.id);
+        self.thread_key = ThreadIndexKey::new(&self.submission_id, &self.path);
+    }
+
+    pub fn is_deleted(&self) -> bool {
+        return self.deleted_at.is_some();
+    }
+}
+
 #[derive(Default, Serialize, Deserialize, PartialEq, Debug)]
 pub struct ReplyBuilder {
     pub id: Option<ReplyId>,
     pub submission_id: Option<SubmissionId>,
     pub comment_id: Option<CommentId>,
+    pub parent_id: Option<ReplyId>,
     pub author_id: Option<String>,
     pub text: Option<String>,
 
@@ -202,6 +320,12 @@ impl ReplyBuilder {
         self
     }
 
+    /// Replies to another reply of the tree of the comment, rather than to the comment itself.
+    pub fn with_parent_id(mut self, parent_id: ReplyId) -> Self {
+        self.parent_id = Some(parent_id);
+        self
+    }
+
     pub fn with_author_id(mut self, author_id: impl Into<String>) -> Self {
         self.author_id = Some(author_id.into());
         self
@@ -232,6 +356,8 @@ impl ReplyBuilder {
     /// use valnk::data::model::submission::SubmissionId;
     /// use valnk::data::model::comment::CommentId;
     /// use valnk::data::model::reply::*;
+    /// use valnk::data::model::tombstone::DeletedIndexKey;
+    /// use valnk::data::model::thread::ThreadIndexKey;
     ///
     /// let current_dt = DateTime::<Utc>::from_utc(NaiveDateTime::from_timestamp(1234,0), Utc);
     /// let reply_id = ReplyId::from("id111".to_string()).unwrap();
@@ -251,22 +377,30 @@ impl ReplyBuilder {
     ///
     /// let expected = Reply{
     ///     primary_key: PrimaryKey::new(&reply_id),
-    ///     submission_comment_key: SubmissionCommentIndexKey::new(&submission_id, &comment_id, &current_dt),
-    ///     author_key: AuthorIndexKey::new("author111", &current_dt),
+    ///     submission_comment_key: SubmissionCommentIndexKey::new(&submission_id, &comment_id, &current_dt, &reply_id),
+    ///     author_key: AuthorIndexKey::new("author111", &current_dt, &reply_id),
+    ///     deleted_key: DeletedIndexKey::default(),
+    ///     thread_key: ThreadIndexKey::default(),
     ///     entity_type: EntityType::Reply,
     ///     id: reply_id.clone(),
     ///     submission_id: submission_id.clone(),
     ///     comment_id: comment_id.clone(),
+    ///     parent_id: None,
     ///     author_id: "author111".to_string(),
     ///     text: "text111".to_string(),
+    ///     path: String::new(),
+    ///     n_replies: 0,
     ///     created_at: current_dt,
     ///     updated_at: current_dt,
+    ///     deleted_at: None,
+    ///     deleted_by: None,
+    ///     purged_at: None,
     /// };
     ///
     /// assert_eq!(result, expected);
     /// ```
     pub fn build(self) -> Result<Reply, ReplyBuildError> {
-        let id = self.id.unwrap_or(ReplyId::new());
+        let id = self.id.unwrap_or_default();
 
         let submission_id = self.submission_id.ok_or(
             ReplyBuildError::EmptyData("submission_id".to_string())
@@ -290,21 +424,30 @@ impl ReplyBuilder {
 
 
         let primary_key = PrimaryKey::new(&id);
-        let submission_comment_key = SubmissionCommentIndexKey::new(&submission_id, &comment_id, &created_at);
-        let author_key = AuthorIndexKey::new(&author_id, &created_at);
+        let submission_comment_key = SubmissionCommentIndexKey::new(&submission_id, &comment_id, &created_at, &id);
+        let author_key = AuthorIndexKey::new(&author_id, &created_at, &id);
 
         Ok(Reply {
             primary_key,
             submission_comment_key,
             author_key,
+            deleted_key: DeletedIndexKey::default(),
+            // placed in its tree on creation, under its parent
+            thread_key: ThreadIndexKey::default(),
             entity_type: EntityType::Reply,
             id,
             submission_id,
             comment_id,
+            parent_id: self.parent_id,
             author_id,
             text,
+            path: String::new(),
+            n_replies: 0,
             created_at,
             updated_at,
+            deleted_at: None,
+            deleted_by: None,
+            purged_at: None,
         })
     }
 }
\ No newline at end of file
@@ -0,0 +1,134 @@
+use std::collections::BTreeMap;
+
+use serde::{Serialize, Deserialize};
+use chrono::{DateTime, Utc};
+
+use super::entity::{EntityType, EntityId};
+use super::key_codec;
+
+pub const REVISION_TAG: &str = "REV";
+
+/// The PrimaryKey of the `revision` item.
+///
+/// The revisions live in the partition of the edited entity,
+/// sorted by the time of the edit.
+#[derive(Clone, Serialize, Deserialize, PartialEq, Debug)]
+pub struct PrimaryKey {
+    #[serde(rename(serialize = "PK", deserialize = "PK"))]
+    pub pk: String,
+    #[serde(rename(serialize = "SK", deserialize = "SK"))]
+    pub sk: String,
+}
+
+impl PrimaryKey {
+    /// # Examples:
+    ///
+    /// ```
+    /// use chrono::{TimeZone, Utc};
+    /// use valnk::data::model::entity::{EntityType, EntityId};
+    /// use valnk::data::model::revision::PrimaryKey;
+    ///
+    /// let revised_at = Utc.timestamp_opt(1234, 0).unwrap();
+    /// let pk = PrimaryKey::new(&EntityType::Submission, &EntityId::from("id1").unwrap(), &revised_at);
+    ///
+    /// assert_eq!(pk, PrimaryKey {
+    ///     pk: String::from("SUBMS#id1"),
+    ///     sk: String::from("REV#09223372038088775808"),
+    /// });
+    /// ```
+    pub fn new(target_type: &EntityType, target_id: &EntityId, revised_at: &DateTime<Utc>) -> Self {
+        return Self {
+            pk: Self::pk(target_type, target_id),
+            sk: Self::sk(revised_at),
+        };
+    }
+
+    /// The partition key of the edited entity.
+    pub fn pk(target_type: &EntityType, target_id: &EntityId) -> String {
+        return format!("{}#{target_id}", target_type.tag());
+    }
+
+    /// The sort key of a revision, the time of the edit in microseconds.
+    pub fn sk(revised_at: &DateTime<Utc>) -> String {
+        return format!("{}{}", Self::sk_prefix(), key_codec::encode_i64(revised_at.timestamp_micros()));
+    }
+
+    pub fn sk_prefix() -> String {
+        return format!("{REVISION_TAG}#");
+    }
+}
+
+
+/// A past edit of a submission, a comment or a reply, with the values
+/// of the edited fields before and after it.
+#[derive(Clone, Serialize, Deserialize, PartialEq, Debug)]
+pub struct Revision {
+    // index-key fields
+    #[serde(flatten)]
+    pub primary_key: PrimaryKey,
+
+    // data fields
+    pub entity_type: EntityType,
+
+    pub target_type: EntityType,
+    pub target_id: EntityId,
+    /// The user who made the edit, the author or a moderator.
+    pub editor_id: String,
+
+    /// The `updated_at` of the entity before the edit.
+    pub previous_updated_at: DateTime<Utc>,
+    /// The `updated_at` of the entity after the edit.
+    pub revised_at: DateTime<Utc>,
+
+    /// The edited fields before the edit, by name, e.g. `text`.
+    pub previous: BTreeMap<String, String>,
+    /// The edited fields after the edit.
+    pub revised: BTreeMap<String, String>,
+}
+
+impl Revision {
+    /// # Examples:
+    ///
+    /// ```
+    /// use std::collections::BTreeMap;
+    /// use chrono::{TimeZone, Utc};
+    /// use valnk::data::model::entity::{EntityType, EntityId};
+    /// use valnk::data::model::revision::Revision;
+    ///
+    /// let created_at = Utc.timestamp_opt(1000, 0).unwrap();
+    /// let revised_at = Utc.timestamp_opt(1234, 0).unwrap();
+    /// let revision = Revision::new(
+    ///     EntityType::Comment,
+    ///     EntityId::from("id1").unwrap(),
+    ///     "py0x",
+    ///     created_at,
+    ///     revised_at,
+    ///     BTreeMap::from([("text".to_string(), "helo".to_string())]),
+    ///     BTreeMap::from([("text".to_string(), "hello".to_string())]),
+    /// );
+    ///
+    /// assert_eq!(revision.primary_key.pk, "COMMT#id1");
+    /// assert_eq!(revision.entity_type, EntityType::Revision);
+    /// ```
+    pub fn new(
+        target_type: EntityType,
+        target_id: EntityId,
+        editor_id: impl Into<String>,
+        previous_updated_at: DateTime<Utc>,
+        revised_at: DateTime<Utc>,
+        previous: BTreeMap<String, String>,
+        revised: BTreeMap<String, String>,
+    ) -> Self {
+        return Self {
+            primary_key: PrimaryKey::new(&target_type, &target_id, &revised_at),
+            entity_type: EntityType::Revision,
+            target_type,
+            target_id,
+            editor_id: editor_id.into(),
+            previous_updated_at,
+            revised_at,
+            previous,
+            revised,
+        };
+    }
+}
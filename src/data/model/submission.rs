@@ -1,11 +1,10 @@
-use std::fmt;
 use serde::{Serialize, Deserialize};
-use serde_dynamo;
-use uuid::Uuid;
 use chrono::{DateTime, Utc};
 use thiserror::Error;
 
 use super::entity::{EntityType, EntityId};
+use super::key_codec::{self, KeyCodecError};
+use super::tombstone::DeletedIndexKey;
 
 pub const SUBMISSION_TAG: &str = "SUBMS";
 const TOPIC_TAG: &str = "TOPIC";
@@ -64,22 +63,24 @@ impl TopicIndexKey {
     /// # Examples
     ///
     /// ```
-    /// use valnk::data::model::submission::TopicIndexKey;
+    /// use valnk::data::model::submission::{TopicIndexKey, SubmissionId};
     ///
     /// let topic = "topic_xxx";
     /// let score = 192;
+    /// let id = SubmissionId::from("id1").unwrap();
     ///
-    /// let topic_key = TopicIndexKey::new(topic, &score);
+    /// let topic_key = TopicIndexKey::new(topic, &score, &id);
     /// let expected = TopicIndexKey {
     ///     pk: String::from("TOPIC#topic_xxx"),
-    ///     sk: String::from("SUBMS#0000000192"),
+    ///     sk: String::from("SUBMS#09223372036854776000#id1"),
     /// };
     /// assert_eq!(topic_key, expected);
+    /// assert_eq!(TopicIndexKey::decode_sk(&topic_key.sk), Ok((score, id)));
     /// ```
-    pub fn new(topic: &str, score: &RankingScore) -> Self {
+    pub fn new(topic: &str, score: &RankingScore, id: &SubmissionId) -> Self {
         return Self {
             pk: Self::pk(topic),
-            sk: Self::sk(score),
+            sk: Self::sk(score, id),
         };
     }
 
@@ -87,9 +88,72 @@ impl TopicIndexKey {
         format!("{TOPIC_TAG}#{topic}")
     }
 
-    pub fn sk(score: &RankingScore) -> String {
-        let pfx = Self::sk_prefix();
-        return format!("{pfx}{score:010}");
+    pub fn sk(score: &RankingScore, id: &SubmissionId) -> String {
+        return key_codec::encode_sort_key(&Self::sk_prefix(), *score, id);
+    }
+
+    /// The ranking score and the id of a sort key.
+    pub fn decode_sk(sk: &str) -> Result<(RankingScore, SubmissionId), KeyCodecError> {
+        return key_codec::decode_sort_key(&Self::sk_prefix(), sk);
+    }
+
+    pub fn sk_prefix() -> String {
+        return format!("{SUBMISSION_TAG}#");
+    }
+}
+
+/// For indexing submissions by `topic` and creation time,
+/// so the recent submissions of a topic are read without the older ones.
+///
+/// It shares `GSI3` with the comment trees, whose partitions are the submissions.
+/// The keys are missing on the submissions written before this index, until they are migrated.
+#[derive(Clone, Default, Serialize, Deserialize, PartialEq, Debug)]
+pub struct TopicTimeIndexKey {
+    #[serde(default, skip_serializing_if = "String::is_empty", rename(serialize = "GSI3_PK", deserialize = "GSI3_PK"))]
+    pub pk: String,
+    #[serde(default, skip_serializing_if = "String::is_empty", rename(serialize = "GSI3_SK", deserialize = "GSI3_SK"))]
+    pub sk: String,
+}
+
+impl TopicTimeIndexKey {
+    pub const INDEX_NAME: &'static str = "GSI3";
+
+    /// # Examples
+    ///
+    /// ```
+    /// use valnk::data::model::submission::{TopicTimeIndexKey, SubmissionId};
+    /// use chrono::{TimeZone, Utc};
+    ///
+    /// let created_at = Utc.timestamp_opt(1234, 0).unwrap();
+    /// let id = SubmissionId::from("id1").unwrap();
+    ///
+    /// let time_key = TopicTimeIndexKey::new("news", &created_at, &id);
+    /// let expected = TopicTimeIndexKey {
+    ///     pk: String::from("TOPIC#news"),
+    ///     sk: String::from("SUBMS#09223372036854777042#id1"),
+    /// };
+    ///
+    /// assert_eq!(time_key, expected);
+    /// assert!(TopicTimeIndexKey::sk_start(&created_at) <= time_key.sk);
+    /// ```
+    pub fn new(topic: &str, created_at: &DateTime<Utc>, id: &SubmissionId) -> Self {
+        return Self {
+            pk: Self::pk(topic),
+            sk: Self::sk(created_at, id),
+        };
+    }
+
+    pub fn pk(topic: &str) -> String {
+        format!("{TOPIC_TAG}#{topic}")
+    }
+
+    pub fn sk(created_at: &DateTime<Utc>, id: &SubmissionId) -> String {
+        return key_codec::encode_sort_key(&Self::sk_prefix(), created_at.timestamp(), id);
+    }
+
+    /// A bound that sorts before the sort keys of the submissions created at `since` or later.
+    pub fn sk_start(since: &DateTime<Utc>) -> String {
+        return key_codec::sort_key_start(&Self::sk_prefix(), since.timestamp());
     }
 
     pub fn sk_prefix() -> String {
@@ -110,31 +174,45 @@ impl AuthorIndexKey {
     /// # Examples
     ///
     /// ```
-    /// use valnk::data::model::submission::AuthorIndexKey;
-    /// use chrono::{DateTime, TimeZone, NaiveDateTime, Utc};
+    /// use valnk::data::model::submission::{AuthorIndexKey, SubmissionId};
+    /// use chrono::{TimeZone, Utc};
     ///
     /// let author_id = "py0x";
-    /// let created_at = DateTime::<Utc>::from_utc(NaiveDateTime::from_timestamp(1234,0), Utc);
+    /// let created_at = Utc.timestamp_opt(1234, 0).unwrap();
+    /// let id = SubmissionId::from("id1").unwrap();
     ///
-    /// let author_key = AuthorIndexKey::new(author_id, &created_at);
+    /// let author_key = AuthorIndexKey::new(author_id, &created_at, &id);
     /// let expected = AuthorIndexKey {
     ///     pk: String::from("AUTHR#py0x"),
-    ///     sk: String::from("SUBMS#0000001234"),
+    ///     sk: String::from("SUBMS#09223372036854777042#id1"),
     /// };
     ///
     /// assert_eq!(author_key, expected);
+    /// assert_eq!(AuthorIndexKey::decode_sk(&author_key.sk), Ok((created_at, id)));
     /// ```
-    pub fn new(author_id: &str, created_at: &DateTime<Utc>) -> Self {
-        let created_at_ts = created_at.timestamp();
-
-        let pk = format!("{AUTHOR_TAG}#{author_id}");
-        let sk = format!("{SUBMISSION_TAG}#{created_at_ts:010}");
-
+    pub fn new(author_id: &str, created_at: &DateTime<Utc>, id: &SubmissionId) -> Self {
         return Self {
-            pk,
-            sk,
+            pk: Self::pk(author_id),
+            sk: Self::sk(created_at, id),
         };
     }
+
+    pub fn pk(author_id: &str) -> String {
+        format!("{AUTHOR_TAG}#{author_id}")
+    }
+
+    pub fn sk(created_at: &DateTime<Utc>, id: &SubmissionId) -> String {
+        return key_codec::encode_sort_key(&Self::sk_prefix(), created_at.timestamp(), id);
+    }
+
+    /// The creation time and the id of a sort key.
+    pub fn decode_sk(sk: &str) -> Result<(DateTime<Utc>, SubmissionId), KeyCodecError> {
+        return key_codec::decode_time_sort_key(&Self::sk_prefix(), sk);
+    }
+
+    pub fn sk_prefix() -> String {
+        return format!("{SUBMISSION_TAG}#");
+    }
 }
 
 
@@ -147,6 +225,10 @@ pub struct Submission {
     pub topic_key: TopicIndexKey,
     #[serde(flatten)]
     pub author_key: AuthorIndexKey,
+    #[serde(flatten)]
+    pub topic_time_key: TopicTimeIndexKey,
+    #[serde(flatten)]
+    pub deleted_key: DeletedIndexKey,
 
     // data fields
     pub entity_type: EntityType,
@@ -160,10 +242,37 @@ pub struct Submission {
     pub text: String,
 
     pub n_votes: u64,
+    /// The number of comments and replies of the submission.
     pub n_comments: u64,
 
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
+
+    /// When the submission was deleted, it stays in its thread as a tombstone until it is purged.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deleted_at: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deleted_by: Option<String>,
+    /// When the content of the deleted submission was erased for good, the purge moves it
+    /// forward each time it finds the submission still has children.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub purged_at: Option<DateTime<Utc>>,
+}
+
+impl Submission {
+    /// Rebuilds the index keys from the data fields, e.g. to migrate
+    /// an item written with an older key format.
+    pub fn rebuild_keys(&mut self) {
+        self.primary_key = PrimaryKey::new(&self.id);
+        self.deleted_key = DeletedIndexKey::of(&self.primary_key.pk, self.deleted_at.as_ref(), self.purged_at.as_ref());
+        self.topic_key = TopicIndexKey::new(&self.topic, &self.ranking_score, &self.id);
+        self.author_key = AuthorIndexKey::new(&self.author_id, &self.created_at, &self.id);
+        self.topic_time_key = TopicTimeIndexKey::new(&self.topic, &self.created_at, &self.id);
+    }
+
+    pub fn is_deleted(&self) -> bool {
+        return self.deleted_at.is_some();
+    }
 }
 
 #[derive(Default, Serialize, Deserialize, PartialEq, Debug)]
@@ -266,6 +375,7 @@ impl SubmissionBuilder {
     /// use chrono::{DateTime, TimeZone, NaiveDateTime, Utc};
     /// use valnk::data::model::entity::EntityType;
     /// use valnk::data::model::submission::*;
+    /// use valnk::data::model::tombstone::DeletedIndexKey;
     ///
     /// let current_dt = DateTime::<Utc>::from_utc(NaiveDateTime::from_timestamp(1234,0), Utc);
     /// let result = SubmissionBuilder::new()
@@ -283,8 +393,10 @@ impl SubmissionBuilder {
     ///
     /// let expected = Submission{
     ///     primary_key: PrimaryKey::new(&SubmissionId::from("id111".to_string()).unwrap()),
-    ///     topic_key: TopicIndexKey::new("topic111", &999),
-    ///     author_key: AuthorIndexKey::new("author111", &current_dt),
+    ///     topic_key: TopicIndexKey::new("topic111", &999, &SubmissionId::from("id111").unwrap()),
+    ///     author_key: AuthorIndexKey::new("author111", &current_dt, &SubmissionId::from("id111").unwrap()),
+    ///     topic_time_key: TopicTimeIndexKey::new("topic111", &current_dt, &SubmissionId::from("id111").unwrap()),
+    ///     deleted_key: DeletedIndexKey::default(),
     ///     entity_type: EntityType::Submission,
     ///
     ///     id: SubmissionId::from("id111".to_string()).unwrap(),
@@ -298,12 +410,15 @@ impl SubmissionBuilder {
     ///     n_comments: 0,
     ///     created_at: current_dt,
     ///     updated_at: current_dt,
+    ///     deleted_at: None,
+    ///     deleted_by: None,
+    ///     purged_at: None,
     /// };
     ///
     /// assert_eq!(result, expected);
     /// ```
     pub fn build(self) -> Result<Submission, SubmissionBuildError> {
-        let id = self.id.unwrap_or(SubmissionId::new());
+        let id = self.id.unwrap_or_default();
 
         let author_id = self.author_id.ok_or(
             SubmissionBuildError::EmptyData("author_id".to_string())
@@ -338,13 +453,16 @@ impl SubmissionBuilder {
 
 
         let primary_key = PrimaryKey::new(&id);
-        let topic_key = TopicIndexKey::new(&topic, &ranking_score);
-        let author_key = AuthorIndexKey::new(&author_id, &created_at);
+        let topic_key = TopicIndexKey::new(&topic, &ranking_score, &id);
+        let author_key = AuthorIndexKey::new(&author_id, &created_at, &id);
+        let topic_time_key = TopicTimeIndexKey::new(&topic, &created_at, &id);
 
         Ok(Submission {
             primary_key,
             topic_key,
             author_key,
+            topic_time_key,
+            deleted_key: DeletedIndexKey::default(),
             entity_type: EntityType::Submission,
             id,
             author_id,
@@ -357,6 +475,9 @@ impl SubmissionBuilder {
             n_comments,
             created_at,
             updated_at,
+            deleted_at: None,
+            deleted_by: None,
+            purged_at: None,
         })
     }
 }
\ No newline at end of file
@@ -1,19 +1,15 @@
-use std::collections::HashMap;
 use super::submission::*;
-use super::entity::EntityType;
-use chrono::{DateTime, TimeZone, NaiveDateTime, Utc};
+use crate::data::api::store::{ContentStore, MemoryStore, Index, Key, PutItemInput, GetItemInput, QueryInput};
+use chrono::{TimeZone, Utc};
 
 use tokio;
 
 use serde_dynamo;
 use serde_json;
-use aws_sdk_dynamodb;
-use aws_config;
-use serde_dynamo::Item;
 
 // #[test]
 // fn test_submission_builder() {
-//     let current_dt = DateTime::<Utc>::from_utc(NaiveDateTime::from_timestamp(1234, 0), Utc);
+//     let current_dt = Utc.timestamp_opt(1234, 0).unwrap();
 //     let result = SubmissionBuilder::new()
 //         // .with_id(SubmissionId::from("id111".to_string()).unwrap())
 //         .with_author_id("author111".to_string())
@@ -36,7 +32,7 @@ use serde_dynamo::Item;
 
 #[tokio::test]
 async fn test_put_item() {
-    let current_dt = DateTime::<Utc>::from_utc(NaiveDateTime::from_timestamp(1234, 0), Utc);
+    let current_dt = Utc.timestamp_opt(1234, 0).unwrap();
     let result = SubmissionBuilder::new()
         // .with_id(SubmissionId::from("id111".to_string()).unwrap())
         .with_author_id("author111".to_string())
@@ -45,26 +41,21 @@ async fn test_put_item() {
         .with_title("title111".to_string())
         .with_url("url111".to_string())
         .with_text("text111".to_string())
-        // .with_created_at(current_dt)
-        // .with_updated_at(current_dt)
+        .with_created_at(current_dt)
+        .with_updated_at(current_dt)
         .build()
         .unwrap();
 
-    // println!("{result:#?}");
+    let item = serde_dynamo::to_item(result.clone()).unwrap();
 
-    let item = serde_dynamo::to_item(result).unwrap();
+    let store = MemoryStore::new();
+    store.put_item(PutItemInput::new(item)).await.unwrap();
 
-    let shared_config = aws_config::load_from_env().await;
-    let client = aws_sdk_dynamodb::Client::new(&shared_config);
+    let key = Key::new(result.primary_key.pk.clone(), result.primary_key.sk.clone());
+    let stored = store.get_item(GetItemInput::new(key)).await.unwrap().unwrap();
+    let subm: Submission = serde_dynamo::from_item(stored).unwrap();
 
-    let res = client
-        .put_item()
-        .table_name("valnk-content")
-        .set_item(Some(item))
-        .send()
-        .await.unwrap();
-
-    println!("{res:#?}");
+    assert_eq!(subm, result);
 }
 
 // #[tokio::test]
@@ -75,23 +66,36 @@ async fn test_put_item() {
 
 #[tokio::test]
 async fn test_scan_item() {
-    let shared_config = aws_config::load_from_env().await;
-    let client = aws_sdk_dynamodb::Client::new(&shared_config);
+    let store = MemoryStore::new();
+
+    for score in [3, 1, 2] {
+        let subm = SubmissionBuilder::new()
+            .with_author_id("author111")
+            .with_topic("topic111")
+            .with_ranking_score(score)
+            .with_title("title111")
+            .with_url("url111")
+            .with_text("text111")
+            .build()
+            .unwrap();
+
+        let item = serde_dynamo::to_item(subm).unwrap();
+        store.put_item(PutItemInput::new(item)).await.unwrap();
+    }
 
-    // Get documents from DynamoDB
-    let result = client.scan().table_name("valnk-content").send().await.unwrap();
+    let query = QueryInput::new(Index::Gsi1, TopicIndexKey::pk("topic111"));
+    let result = store.query(query).await.unwrap();
 
-    for item in result.items.unwrap() {
-        let subm: Submission = serde_dynamo::from_item(item).unwrap();
-        println!("subm: {subm:#?}");
-    }
+    let subms: Vec<Submission> = serde_dynamo::from_items(result.items).unwrap();
+    let scores: Vec<RankingScore> = subms.iter().map(|s| s.ranking_score).collect();
+    assert_eq!(scores, vec![1, 2, 3]);
 }
 
 #[test]
 fn test_json_topic_key() {
     let topic = "topic_xxx";
     let score = 192;
-    let topic_key = TopicIndexKey::new(topic, &score);
+    let topic_key = TopicIndexKey::new(topic, &score, &SubmissionId::from("id1").unwrap());
 
     let tkj = serde_json::to_string(&topic_key).unwrap();
 
@@ -0,0 +1,129 @@
+//! The materialised paths of the comment trees.
+//!
+//! Every comment and reply of a submission has a path: the path of its parent followed
+//! by a segment of its own, a comment being the root of its tree. A segment is the
+//! creation time of the node and a digest of its id, both with a fixed width, so the
+//! paths sort depth-first: a node right after its parent, its subtree before its
+//! next sibling, and the siblings by time.
+
+use chrono::{DateTime, Utc};
+use serde::{Serialize, Deserialize};
+use sha2::{Digest, Sha256};
+
+use super::entity::EntityId;
+use super::key_codec;
+use super::submission::{SubmissionId, SUBMISSION_TAG};
+
+pub const THREAD_TAG: &str = "THRD";
+
+/// The deepest a reply can be nested, a comment is at depth `0`.
+///
+/// A segment takes 37 bytes of the sort key, that DynamoDB caps at 1024 bytes.
+pub const MAX_THREAD_DEPTH: usize = 24;
+
+const SEGMENT_SEP: char = '/';
+const ID_DIGEST_LEN: usize = 8;
+
+/// The segment of a node in the paths of its subtree.
+///
+/// # Examples
+///
+/// ```
+/// use chrono::{TimeZone, Utc};
+/// use valnk::data::model::entity::EntityId;
+/// use valnk::data::model::thread::segment;
+///
+/// let created_at = Utc.timestamp_opt(1234, 0).unwrap();
+/// let seg = segment(&created_at, &EntityId::from("id1").unwrap());
+///
+/// assert!(seg.starts_with("09223372036856009808"));
+/// assert_eq!(seg.len(), 36);
+/// ```
+pub fn segment(created_at: &DateTime<Utc>, id: &EntityId) -> String {
+    let digest = Sha256::digest(id.as_ref().as_bytes());
+    let id_digest: String = digest[..ID_DIGEST_LEN].iter().map(|b| format!("{b:02x}")).collect();
+
+    return format!("{}{id_digest}", key_codec::encode_i64(created_at.timestamp_millis()));
+}
+
+/// The path of a child of the node with `parent_path`.
+///
+/// # Examples
+///
+/// ```
+/// use chrono::{TimeZone, Utc};
+/// use valnk::data::model::entity::EntityId;
+/// use valnk::data::model::thread::{child_path, depth, segment};
+///
+/// let root = segment(&Utc.timestamp_opt(1000, 0).unwrap(), &EntityId::from("c1").unwrap());
+/// let child = child_path(&root, &Utc.timestamp_opt(2000, 0).unwrap(), &EntityId::from("r1").unwrap());
+/// let sibling = segment(&Utc.timestamp_opt(1500, 0).unwrap(), &EntityId::from("c2").unwrap());
+///
+/// assert!(child.starts_with(&root));
+/// assert!(root < child && child < sibling);
+/// assert_eq!((depth(&root), depth(&child)), (0, 1));
+/// ```
+pub fn child_path(parent_path: &str, created_at: &DateTime<Utc>, id: &EntityId) -> String {
+    return format!("{parent_path}{SEGMENT_SEP}{}", segment(created_at, id));
+}
+
+/// The depth of the node with the path, `0` for a comment.
+pub fn depth(path: &str) -> usize {
+    return path.matches(SEGMENT_SEP).count();
+}
+
+
+/// For loading the comment tree of a submission in the order of its paths.
+///
+/// The keys are missing on the items written before the threads, until they are migrated,
+/// and on a reply until it is placed under its parent.
+#[derive(Clone, Default, Serialize, Deserialize, PartialEq, Debug)]
+pub struct ThreadIndexKey {
+    #[serde(default, skip_serializing_if = "String::is_empty", rename(serialize = "GSI3_PK", deserialize = "GSI3_PK"))]
+    pub pk: String,
+    #[serde(default, skip_serializing_if = "String::is_empty", rename(serialize = "GSI3_SK", deserialize = "GSI3_SK"))]
+    pub sk: String,
+}
+
+impl ThreadIndexKey {
+    pub const INDEX_NAME: &'static str = "GSI3";
+
+    /// # Examples
+    ///
+    /// ```
+    /// use valnk::data::model::submission::SubmissionId;
+    /// use valnk::data::model::thread::ThreadIndexKey;
+    ///
+    /// let subm = SubmissionId::from("submission_id_123").unwrap();
+    /// let thread_key = ThreadIndexKey::new(&subm, "0001/0002");
+    ///
+    /// assert_eq!(thread_key, ThreadIndexKey {
+    ///     pk: String::from("SUBMS#submission_id_123"),
+    ///     sk: String::from("THRD#0001/0002"),
+    /// });
+    /// ```
+    pub fn new(submission_id: &SubmissionId, path: &str) -> Self {
+        return Self {
+            pk: Self::pk(submission_id),
+            sk: Self::sk(path),
+        };
+    }
+
+    pub fn pk(submission_id: &SubmissionId) -> String {
+        format!("{SUBMISSION_TAG}#{submission_id}")
+    }
+
+    pub fn sk(path: &str) -> String {
+        return format!("{}{path}", Self::sk_prefix());
+    }
+
+    /// The sort-key prefix of the whole tree of a submission.
+    pub fn sk_prefix() -> String {
+        return format!("{THREAD_TAG}#");
+    }
+
+    /// The sort-key prefix of the subtree of the node with the path, the node included.
+    pub fn subtree_sk_prefix(path: &str) -> String {
+        return Self::sk(path);
+    }
+}
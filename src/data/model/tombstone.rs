@@ -0,0 +1,118 @@
+//! The index of the deleted submissions, comments and replies, by deletion time.
+//!
+//! The index is sparse: an entity only has its keys while it is deleted, so the purge
+//! reads the tombstones and nothing else. They all share one partition, there are few
+//! of them at any time since the purge removes them after their retention period.
+//!
+//! The votes and the idempotency records of an entity are indexed by its primary key,
+//! so the purge removes them with it.
+
+use chrono::{DateTime, Utc};
+use serde::{Serialize, Deserialize};
+
+use super::key_codec;
+
+pub const TOMBSTONE_TAG: &str = "TOMBS";
+pub const TARGET_TAG: &str = "TARGT";
+
+/// For indexing the deleted entities by `deleted_at`.
+///
+/// The keys are empty, and left out of the item, while the entity is not deleted.
+#[derive(Clone, Default, Serialize, Deserialize, PartialEq, Debug)]
+pub struct DeletedIndexKey {
+    #[serde(default, skip_serializing_if = "String::is_empty", rename(serialize = "GSI4_PK", deserialize = "GSI4_PK"))]
+    pub pk: String,
+    #[serde(default, skip_serializing_if = "String::is_empty", rename(serialize = "GSI4_SK", deserialize = "GSI4_SK"))]
+    pub sk: String,
+}
+
+impl DeletedIndexKey {
+    pub const INDEX_NAME: &'static str = "GSI4";
+
+    /// # Examples
+    ///
+    /// ```
+    /// use chrono::{TimeZone, Utc};
+    /// use valnk::data::model::tombstone::DeletedIndexKey;
+    ///
+    /// let deleted_at = Utc.timestamp_opt(1234, 0).unwrap();
+    /// let deleted_key = DeletedIndexKey::new(&deleted_at, "COMMT#id1");
+    ///
+    /// assert_eq!(deleted_key, DeletedIndexKey {
+    ///     pk: String::from("TOMBS"),
+    ///     sk: String::from("09223372036854777042#COMMT#id1"),
+    /// });
+    /// assert!(deleted_key.sk < DeletedIndexKey::sk_end(&deleted_at));
+    /// ```
+    pub fn new(deleted_at: &DateTime<Utc>, entity_pk: &str) -> Self {
+        return Self {
+            pk: Self::pk(),
+            sk: Self::sk(deleted_at, entity_pk),
+        };
+    }
+
+    /// The key of the entity with the primary key `entity_pk`, empty unless it is deleted.
+    ///
+    /// A tombstone whose content has been purged is indexed by `purged_at` instead,
+    /// the purge checks it again for its children one retention period later.
+    pub fn of(entity_pk: &str, deleted_at: Option<&DateTime<Utc>>, purged_at: Option<&DateTime<Utc>>) -> Self {
+        return match deleted_at {
+            Some(deleted_at) => Self::new(purged_at.unwrap_or(deleted_at), entity_pk),
+            None => Self::default(),
+        };
+    }
+
+    pub fn pk() -> String {
+        return TOMBSTONE_TAG.to_string();
+    }
+
+    /// The primary key of the entity breaks the ties between equal deletion times.
+    pub fn sk(deleted_at: &DateTime<Utc>, entity_pk: &str) -> String {
+        return format!("{}#{entity_pk}", key_codec::encode_timestamp(deleted_at));
+    }
+
+    /// A bound that sorts after the sort keys of the entities deleted at `deleted_at` or before.
+    pub fn sk_end(deleted_at: &DateTime<Utc>) -> String {
+        return key_codec::sort_key_end("", deleted_at.timestamp());
+    }
+}
+
+
+/// For indexing the items that refer to an entity, its votes and idempotency records,
+/// by the primary key of the entity.
+///
+/// The keys are empty for the items written before they were indexed.
+#[derive(Clone, Default, Serialize, Deserialize, PartialEq, Debug)]
+pub struct TargetIndexKey {
+    #[serde(default, skip_serializing_if = "String::is_empty", rename(serialize = "GSI1_PK", deserialize = "GSI1_PK"))]
+    pub pk: String,
+    #[serde(default, skip_serializing_if = "String::is_empty", rename(serialize = "GSI1_SK", deserialize = "GSI1_SK"))]
+    pub sk: String,
+}
+
+impl TargetIndexKey {
+    pub const INDEX_NAME: &'static str = "GSI1";
+
+    /// # Examples
+    ///
+    /// ```
+    /// use valnk::data::model::tombstone::TargetIndexKey;
+    ///
+    /// let target_key = TargetIndexKey::new("COMMT#id1", "VOTES#py0x");
+    ///
+    /// assert_eq!(target_key, TargetIndexKey {
+    ///     pk: String::from("TARGT#COMMT#id1"),
+    ///     sk: String::from("VOTES#py0x"),
+    /// });
+    /// ```
+    pub fn new(target_pk: &str, item_pk: &str) -> Self {
+        return Self {
+            pk: Self::pk(target_pk),
+            sk: item_pk.to_string(),
+        };
+    }
+
+    pub fn pk(target_pk: &str) -> String {
+        return format!("{TARGET_TAG}#{target_pk}");
+    }
+}
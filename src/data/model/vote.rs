@@ -0,0 +1,192 @@
+use serde::{Serialize, Deserialize};
+use chrono::{DateTime, Utc};
+use thiserror::Error;
+
+use super::entity::{EntityType, EntityId};
+use super::tombstone::TargetIndexKey;
+
+pub const VOTE_TAG: &str = "VOTES";
+
+/// The PrimaryKey of the `vote` item.
+///
+/// There is one vote item per user and target, so a user can only vote once on an entity.
+#[derive(Clone, Serialize, Deserialize, PartialEq, Debug)]
+pub struct PrimaryKey {
+    #[serde(rename(serialize = "PK", deserialize = "PK"))]
+    pub pk: String,
+    #[serde(rename(serialize = "SK", deserialize = "SK"))]
+    pub sk: String,
+}
+
+impl PrimaryKey {
+    /// # Examples:
+    ///
+    /// ```
+    /// use valnk::data::model::entity::{EntityType, EntityId};
+    /// use valnk::data::model::vote::PrimaryKey;
+    ///
+    /// let target_id = EntityId::from("id1").unwrap();
+    /// let pk = PrimaryKey::new("py0x", &EntityType::Submission, &target_id);
+    ///
+    /// assert_eq!(pk, PrimaryKey {
+    ///     pk: String::from("VOTES#py0x"),
+    ///     sk: String::from("SUBMS#id1"),
+    /// });
+    /// ```
+    pub fn new(user_id: &str, target_type: &EntityType, target_id: &EntityId) -> Self {
+        let pk = format!("{VOTE_TAG}#{user_id}");
+        let sk = format!("{}#{target_id}", target_type.tag());
+
+        return Self {
+            pk,
+            sk,
+        };
+    }
+}
+
+
+#[derive(Clone, Serialize, Deserialize, PartialEq, Debug)]
+pub struct Vote {
+    // index-key fields
+    #[serde(flatten)]
+    pub primary_key: PrimaryKey,
+    /// The vote is removed with its target when the target is purged.
+    #[serde(flatten)]
+    pub target_key: TargetIndexKey,
+
+    // data fields
+    pub entity_type: EntityType,
+
+    pub user_id: String,
+    pub target_type: EntityType,
+    pub target_id: EntityId,
+
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Default, Serialize, Deserialize, PartialEq, Debug)]
+pub struct VoteBuilder {
+    user_id: Option<String>,
+    target_type: Option<EntityType>,
+    target_id: Option<EntityId>,
+
+    created_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Error, Debug)]
+pub enum VoteBuildError {
+    #[error("the data for field `{0}` cannot be empty")]
+    EmptyData(String),
+
+    #[error("the data for field `{0}` is not valid, reason: `{1}`")]
+    InvalidData(String, String),
+
+    #[error("failed to build vote, reason: `{0}`")]
+    Error(String),
+
+    #[error("unknown vote build error")]
+    Unknown,
+}
+
+impl VoteBuilder {
+    pub fn new() -> Self {
+        return VoteBuilder::default();
+    }
+
+    pub fn with_user_id(mut self, user_id: impl Into<String>) -> Self {
+        self.user_id = Some(user_id.into());
+        self
+    }
+
+    pub fn with_target_type(mut self, target_type: EntityType) -> Self {
+        self.target_type = Some(target_type);
+        self
+    }
+
+    pub fn with_target_id(mut self, target_id: EntityId) -> Self {
+        self.target_id = Some(target_id);
+        self
+    }
+
+    pub fn with_created_at(mut self, created_at: DateTime<Utc>) -> Self {
+        self.created_at = Some(created_at);
+        self
+    }
+
+    /// Build a `Vote` step by step, only submissions and comments can be voted.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use chrono::{TimeZone, Utc};
+    /// use valnk::data::model::entity::{EntityType, EntityId};
+    /// use valnk::data::model::tombstone::TargetIndexKey;
+    /// use valnk::data::model::vote::*;
+    ///
+    /// let current_dt = Utc.timestamp_opt(1234, 0).unwrap();
+    /// let result = VoteBuilder::new()
+    ///     .with_user_id("user111")
+    ///     .with_target_type(EntityType::Comment)
+    ///     .with_target_id(EntityId::from("comm111").unwrap())
+    ///     .with_created_at(current_dt)
+    ///     .build()
+    ///     .unwrap();
+    ///
+    /// let target_id = EntityId::from("comm111").unwrap();
+    /// let expected = Vote {
+    ///     primary_key: PrimaryKey::new("user111", &EntityType::Comment, &target_id),
+    ///     target_key: TargetIndexKey::new("COMMT#comm111", "VOTES#user111"),
+    ///     entity_type: EntityType::Vote,
+    ///     user_id: "user111".to_string(),
+    ///     target_type: EntityType::Comment,
+    ///     target_id,
+    ///     created_at: current_dt,
+    /// };
+    ///
+    /// assert_eq!(result, expected);
+    ///
+    /// let result = VoteBuilder::new()
+    ///     .with_user_id("user111")
+    ///     .with_target_type(EntityType::Reply)
+    ///     .with_target_id(EntityId::from("reply111").unwrap())
+    ///     .build();
+    ///
+    /// assert!(matches!(result, Err(VoteBuildError::InvalidData(_, _))));
+    /// ```
+    pub fn build(self) -> Result<Vote, VoteBuildError> {
+        let user_id = self.user_id.ok_or(
+            VoteBuildError::EmptyData("user_id".to_string())
+        )?;
+
+        let target_type = self.target_type.ok_or(
+            VoteBuildError::EmptyData("target_type".to_string())
+        )?;
+
+        if !matches!(target_type, EntityType::Submission | EntityType::Comment) {
+            return Err(VoteBuildError::InvalidData(
+                "target_type".to_string(),
+                format!("`{target_type:?}` cannot be voted"),
+            ));
+        }
+
+        let target_id = self.target_id.ok_or(
+            VoteBuildError::EmptyData("target_id".to_string())
+        )?;
+
+        let created_at = self.created_at.unwrap_or_else(Utc::now);
+
+        let primary_key = PrimaryKey::new(&user_id, &target_type, &target_id);
+        // the sort key of the vote is the primary key of its target
+        let target_key = TargetIndexKey::new(&primary_key.sk, &primary_key.pk);
+
+        Ok(Vote {
+            primary_key,
+            target_key,
+            entity_type: EntityType::Vote,
+            user_id,
+            target_type,
+            target_id,
+            created_at,
+        })
+    }
+}
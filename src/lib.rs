@@ -1 +1,5 @@
-pub mod data;
\ No newline at end of file
+pub mod data;
+#[allow(clippy::needless_return)]
+pub mod config;
+#[allow(clippy::needless_return)]
+pub mod web;
@@ -1,26 +1,20 @@
-mod data;
+#![allow(clippy::result_large_err)]
 
-// use model::submission;
-
-use rocket::{get, routes};
-use rocket::response::status;
-
-#[get("/")]
-fn index() -> &'static str {
-    "Hello, there!"
-}
-
-#[get("/doc")]
-fn doc() -> &'static str {
-    "Hello, doc!"
-}
+use valnk::config::ValnkConfig;
+use valnk::data::api::Valnk;
+use valnk::web::api;
 
 #[rocket::main]
-async fn main() -> Result<(), rocket::Error> {
+async fn main() -> anyhow::Result<()> {
+    let config = ValnkConfig::load()?;
+    let valnk = Valnk::from_config(config).await?;
+
     let _rocket = rocket::build()
-        .mount("/hello", routes![index, doc])
+        .manage(valnk)
+        .attach(api::cursor_codec())
+        .mount("/api", api::routes())
         .launch()
         .await?;
 
     Ok(())
-}
\ No newline at end of file
+}
@@ -0,0 +1,4 @@
+pub mod api;
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,250 @@
+//! The JSON API for browsing the topics, the submissions and their discussions.
+//!
+//! The models are returned as they are stored, less the key attributes of the table
+//! and the internal ones, e.g. the materialised paths of the threads, and the lists are paged with opaque cursors, see [`CursorCodec`].
+//!
+//! | route                                                  | returns                           |
+//! |--------------------------------------------------------|-----------------------------------|
+//! | `GET /api/topics/<topic>/submissions?limit&cursor&order` | a page of submissions, ranked   |
+//! | `GET /api/submissions/<id>`                            | a submission                      |
+//! | `GET /api/submissions/<id>/comments`                   | the comment trees of a submission |
+//! | `GET /api/users/<id>/activity?limit&cursor&type`       | a page of what the user posted    |
+
+use rocket::{get, routes, Request, Route, State};
+use rocket::fairing::AdHoc;
+use rocket::http::Status;
+use rocket::response::{self, Responder};
+use rocket::serde::json::{json, Json, Value};
+use serde::Serialize;
+
+use crate::data::api::Valnk;
+use crate::data::api::author::ListItemsByAuthorInput;
+use crate::data::api::cursor::{Cursor, CursorCodec};
+use crate::data::api::result::Error;
+use crate::data::api::store::Index;
+use crate::data::api::submission::{GetItemInput, ListItemsByTopicInput};
+use crate::data::api::thread::{LoadThreadInput, ThreadNode};
+use crate::data::model::entity::{Entity, EntityId, EntityType};
+
+/// The error of a request, answered with its HTTP status and `{"error": <message>}`.
+#[derive(Debug)]
+pub struct ApiError(pub Error);
+
+impl From<Error> for ApiError {
+    fn from(err: Error) -> Self {
+        return ApiError(err);
+    }
+}
+
+impl<'r> Responder<'r, 'static> for ApiError {
+    fn respond_to(self, req: &'r Request<'_>) -> response::Result<'static> {
+        let status = Status::from_code(self.0.http_status()).unwrap_or(Status::InternalServerError);
+        let body = json!({ "error": self.0.to_string() });
+
+        return (status, Json(body)).respond_to(req);
+    }
+}
+
+pub type ApiResult<T> = Result<Json<T>, ApiError>;
+
+/// A page of a list, with the tokens of the pages around it.
+#[derive(Serialize, Debug)]
+pub struct Page {
+    pub items: Vec<Value>,
+    pub next_cursor: Option<String>,
+    pub prev_cursor: Option<String>,
+}
+
+impl Page {
+    fn new(items: Vec<Value>, next_cursor: Option<Cursor>, prev_cursor: Option<Cursor>, codec: &CursorCodec) -> Self {
+        return Self {
+            items,
+            next_cursor: next_cursor.map(|cur| codec.encode(&cur)),
+            prev_cursor: prev_cursor.map(|cur| codec.encode(&cur)),
+        };
+    }
+}
+
+/// A fairing that manages the [`CursorCodec`] of the API, on the `secret_key`
+/// of the Rocket config, see [`CursorCodec::from_figment`].
+///
+/// The launch fails if no `secret_key` is configured.
+pub fn cursor_codec() -> AdHoc {
+    return AdHoc::try_on_ignite("Cursor codec", |rocket| async {
+        match CursorCodec::from_figment(rocket.figment()) {
+            Ok(codec) => Ok(rocket.manage(codec)),
+            Err(e) => {
+                log::error!("failed to build the cursor codec: {e}");
+                Err(rocket)
+            }
+        }
+    });
+}
+
+/// The routes of the API, to be mounted on `/api` with a [`Valnk`]
+/// and a [`CursorCodec`] in the managed state, e.g. by [`cursor_codec`].
+///
+/// # Example:
+///
+/// ```no_run
+/// use valnk::config::ValnkConfig;
+/// use valnk::data::api::Valnk;
+/// use valnk::web::api;
+///
+/// #[rocket::main]
+/// async fn main() {
+///     let config = ValnkConfig::load().unwrap();
+///     let valnk = Valnk::from_config(config).await.unwrap();
+///
+///     let _rocket = rocket::build()
+///         .manage(valnk)
+///         .attach(api::cursor_codec())
+///         .mount("/api", api::routes())
+///         .launch()
+///         .await
+///         .unwrap();
+/// }
+/// ```
+pub fn routes() -> Vec<Route> {
+    return routes![list_topic_submissions, get_submission, get_submission_comments, list_user_activity];
+}
+
+/// The attributes of the models that are kept from the API users: the materialised
+/// path of the threads, who deleted a tombstone and when it was purged.
+const INTERNAL_ATTRS: [&str; 3] = ["path", "deleted_by", "purged_at"];
+
+/// Whether the attribute is one of the keys of the table or of its indexes.
+fn is_key_attr(attr: &str) -> bool {
+    return [Index::Primary, Index::Gsi1, Index::Gsi2, Index::Gsi3, Index::Gsi4].iter()
+        .any(|index| attr == index.pk_attr() || attr == index.sk_attr());
+}
+
+/// The JSON of a model, without its key and internal attributes.
+fn public_json(model: &impl Serialize) -> Result<Value, ApiError> {
+    let mut value = serde_json::to_value(model)
+        .map_err(|e| Error::Unknown(format!("failed to serialize a model: {e}")))?;
+    if let Value::Object(fields) = &mut value {
+        fields.retain(|attr, _| !is_key_attr(attr) && !INTERNAL_ATTRS.contains(&attr.as_str()));
+    }
+
+    return Ok(value);
+}
+
+fn entity_json(entity: &Entity) -> Result<Value, ApiError> {
+    return match entity {
+        Entity::Submission(subm) => public_json(subm),
+        Entity::Comment(comm) => public_json(comm),
+        Entity::Reply(reply) => public_json(reply),
+    };
+}
+
+/// The JSON of a comment or a reply, with its replies nested in `children`.
+fn node_json(node: &ThreadNode) -> Result<Value, ApiError> {
+    let mut value = entity_json(&node.entity)?;
+    let children = node.children.iter()
+        .map(node_json)
+        .collect::<Result<Vec<Value>, ApiError>>()?;
+
+    if let Value::Object(fields) = &mut value {
+        fields.insert("depth".to_string(), json!(node.depth));
+        fields.insert("n_children".to_string(), json!(node.n_children));
+        fields.insert("n_descendants".to_string(), json!(node.n_descendants));
+        fields.insert("collapsed".to_string(), json!(node.collapsed));
+        fields.insert("children".to_string(), Value::Array(children));
+    }
+
+    return Ok(value);
+}
+
+fn entity_id(id: &str) -> Result<EntityId, ApiError> {
+    return EntityId::from(id).map_err(|e| ApiError(Error::BadRequest(e)));
+}
+
+fn start_cursor(cursor: Option<&str>, codec: &CursorCodec) -> Result<Option<Cursor>, ApiError> {
+    return Ok(cursor.map(|token| codec.decode(token)).transpose()?);
+}
+
+/// `desc`, the highest ranked first, by default, or `asc`.
+fn is_reverse(order: Option<&str>) -> Result<bool, ApiError> {
+    return match order {
+        None | Some("desc") => Ok(false),
+        Some("asc") => Ok(true),
+        Some(order) => Err(ApiError(Error::BadRequest(format!("invalid order `{order}`, `desc` or `asc`")))),
+    };
+}
+
+#[get("/topics/<topic>/submissions?<limit>&<cursor>&<order>")]
+async fn list_topic_submissions(
+    topic: &str,
+    limit: Option<i32>,
+    cursor: Option<&str>,
+    order: Option<&str>,
+    valnk: &State<Valnk>,
+    codec: &State<CursorCodec>,
+) -> ApiResult<Page> {
+    let mut input = ListItemsByTopicInput::new(topic);
+    input.limit = limit;
+    input.reverse = Some(is_reverse(order)?);
+    input.start_cursor = start_cursor(cursor, codec)?;
+
+    let output = valnk.submissions().list_items_by_topic(input).await?;
+    let items = output.items.iter()
+        .map(public_json)
+        .collect::<Result<Vec<Value>, ApiError>>()?;
+
+    return Ok(Json(Page::new(items, output.next_cursor, output.prev_cursor, codec)));
+}
+
+#[get("/submissions/<id>")]
+async fn get_submission(id: &str, valnk: &State<Valnk>) -> ApiResult<Value> {
+    let subm = valnk.submissions().get_item(GetItemInput::new(entity_id(id)?)).await?;
+
+    return Ok(Json(public_json(&subm)?));
+}
+
+/// The comments of a submission by their ranking, each with its tree of replies,
+/// `truncated` is set when the discussion is too large to be loaded whole.
+#[get("/submissions/<id>/comments")]
+async fn get_submission_comments(id: &str, valnk: &State<Valnk>) -> ApiResult<Value> {
+    let thread = valnk.threads().load_thread(LoadThreadInput::new(entity_id(id)?)).await?;
+    let comments = thread.comments.iter()
+        .map(node_json)
+        .collect::<Result<Vec<Value>, ApiError>>()?;
+
+    return Ok(Json(json!({
+        "submission_id": thread.submission.id,
+        "n_nodes": thread.n_nodes,
+        "truncated": thread.truncated,
+        "comments": comments,
+    })));
+}
+
+/// What a user has submitted, commented and replied, the latest first,
+/// `type` keeps one of `submission`, `comment` or `reply`.
+#[get("/users/<id>/activity?<limit>&<cursor>&<type>")]
+async fn list_user_activity(
+    id: &str,
+    limit: Option<i32>,
+    cursor: Option<&str>,
+    r#type: Option<&str>,
+    valnk: &State<Valnk>,
+    codec: &State<CursorCodec>,
+) -> ApiResult<Page> {
+    let mut input = ListItemsByAuthorInput::new(id);
+    input.limit = limit;
+    input.start_cursor = start_cursor(cursor, codec)?;
+    input.entity_type = match r#type {
+        None => None,
+        Some("submission") => Some(EntityType::Submission),
+        Some("comment") => Some(EntityType::Comment),
+        Some("reply") => Some(EntityType::Reply),
+        Some(t) => return Err(ApiError(Error::BadRequest(format!("invalid type `{t}`")))),
+    };
+
+    let output = valnk.users().list_items_by_author(input).await?;
+    let items = output.items.iter()
+        .map(entity_json)
+        .collect::<Result<Vec<Value>, ApiError>>()?;
+
+    return Ok(Json(Page::new(items, output.next_cursor, output.prev_cursor, codec)));
+}
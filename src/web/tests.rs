@@ -0,0 +1,163 @@
+use rocket::http::Status;
+use rocket::local::asynchronous::Client;
+use rocket::serde::json::Value;
+use chrono::{TimeZone, Utc};
+
+use crate::config::ValnkConfig;
+use crate::data::api::Valnk;
+use crate::data::api::store::MemoryStore;
+use crate::data::api::reply::DeleteItemInput;
+use crate::data::model::submission::{SubmissionBuilder, SubmissionId};
+use crate::data::model::comment::{CommentBuilder, CommentId};
+use crate::data::model::reply::{ReplyBuilder, ReplyId};
+
+use super::api;
+
+async fn new_client() -> Client {
+    let valnk = Valnk::new(MemoryStore::new(), ValnkConfig::default());
+
+    for (id, score) in [("s1", 3), ("s2", 2), ("s3", 1)] {
+        let subm = SubmissionBuilder::new()
+            .with_id(SubmissionId::from(id).unwrap())
+            .with_author_id("py0x")
+            .with_topic("news")
+            .with_ranking_score(score)
+            .with_title(id)
+            .with_url("https://example.com")
+            .with_text("")
+            .with_created_at(Utc.timestamp_opt(1000 + score, 0).unwrap())
+            .build()
+            .unwrap();
+        valnk.submissions().create_item(subm).await.unwrap();
+    }
+
+    let comm = CommentBuilder::new()
+        .with_id(CommentId::from("c1").unwrap())
+        .with_submission_id(SubmissionId::from("s1").unwrap())
+        .with_author_id("py0x")
+        .with_ranking_score(0)
+        .with_text("first")
+        .with_created_at(Utc.timestamp_opt(2000, 0).unwrap())
+        .build()
+        .unwrap();
+    valnk.comments().create_item(comm).await.unwrap();
+    let reply = ReplyBuilder::new()
+        .with_id(ReplyId::from("r1").unwrap())
+        .with_submission_id(SubmissionId::from("s1").unwrap())
+        .with_comment_id(CommentId::from("c1").unwrap())
+        .with_author_id("someone")
+        .with_text("second")
+        .with_created_at(Utc.timestamp_opt(3000, 0).unwrap())
+        .build()
+        .unwrap();
+    valnk.replies().create_item(reply).await.unwrap();
+
+    let figment = rocket::Config::figment().merge(("secret_key", "hPRYyVRiMyxpw5sBB1XeCMN1kFsDCqKvBi2QJxBVHQk="));
+    let rocket = rocket::custom(figment)
+        .manage(valnk)
+        .attach(api::cursor_codec())
+        .mount("/api", api::routes());
+
+    return Client::tracked(rocket).await.unwrap();
+}
+
+async fn get_json(client: &Client, uri: &str) -> (Status, Value) {
+    let response = client.get(uri.to_string()).dispatch().await;
+    let status = response.status();
+
+    return (status, response.into_json().await.unwrap());
+}
+
+fn ids(page: &Value) -> Vec<&str> {
+    return page["items"].as_array().unwrap()
+        .iter()
+        .map(|it| it["id"].as_str().unwrap())
+        .collect();
+}
+
+#[rocket::async_test]
+async fn test_list_topic_submissions() {
+    let client = new_client().await;
+
+    let (status, page) = get_json(&client, "/api/topics/news/submissions?limit=2").await;
+    assert_eq!(status, Status::Ok);
+    assert_eq!(ids(&page), vec!["s1", "s2"]);
+    assert!(page["prev_cursor"].is_null());
+
+    // the models go out without the keys of the table
+    let item = page["items"][0].as_object().unwrap();
+    assert_eq!(item["title"], "s1");
+    assert!(item.keys().all(|k| !k.starts_with("PK") && !k.starts_with("SK") && !k.starts_with("GSI")));
+
+    let cursor = page["next_cursor"].as_str().unwrap();
+    let (_, next) = get_json(&client, &format!("/api/topics/news/submissions?limit=2&cursor={cursor}")).await;
+    assert_eq!(ids(&next), vec!["s3"]);
+    assert!(next["next_cursor"].is_null());
+
+    let (_, page) = get_json(&client, "/api/topics/news/submissions?order=asc").await;
+    assert_eq!(ids(&page), vec!["s3", "s2", "s1"]);
+
+    let (status, err) = get_json(&client, "/api/topics/news/submissions?cursor=forged.token").await;
+    assert_eq!(status, Status::BadRequest);
+    assert!(err["error"].is_string());
+    let (status, _) = get_json(&client, "/api/topics/news/submissions?order=sideways").await;
+    assert_eq!(status, Status::BadRequest);
+}
+
+#[rocket::async_test]
+async fn test_get_submission_and_comments() {
+    let client = new_client().await;
+
+    let (status, subm) = get_json(&client, "/api/submissions/s1").await;
+    assert_eq!(status, Status::Ok);
+    assert_eq!((subm["id"].as_str(), subm["n_comments"].as_u64()), (Some("s1"), Some(2)));
+    assert!(subm.get("PK").is_none());
+
+    let (status, _) = get_json(&client, "/api/submissions/missing").await;
+    assert_eq!(status, Status::NotFound);
+
+    let (status, thread) = get_json(&client, "/api/submissions/s1/comments").await;
+    assert_eq!(status, Status::Ok);
+    assert_eq!(thread["n_nodes"], 2);
+    assert_eq!(thread["truncated"], false);
+    let comm = &thread["comments"][0];
+    assert_eq!((comm["id"].as_str(), comm["depth"].as_u64(), comm["n_descendants"].as_u64()), (Some("c1"), Some(0), Some(1)));
+    assert_eq!(comm["children"][0]["text"], "second");
+    assert!(comm["children"][0].get("GSI3_SK").is_none());
+    assert!(comm.get("path").is_none());
+
+    // a tombstone does not tell who deleted it
+    let valnk = client.rocket().state::<Valnk>().unwrap();
+    valnk.replies().delete_item(DeleteItemInput::new(ReplyId::from("r1").unwrap(), "mod")).await.unwrap();
+    let (_, thread) = get_json(&client, "/api/submissions/s1/comments").await;
+    let tombstone = &thread["comments"][0]["children"][0];
+    assert!(tombstone["deleted_at"].is_string());
+    assert!(tombstone.get("deleted_by").is_none());
+    assert_eq!(tombstone["text"], "");
+}
+
+#[rocket::async_test]
+async fn test_list_user_activity() {
+    let client = new_client().await;
+
+    let (status, page) = get_json(&client, "/api/users/py0x/activity").await;
+    assert_eq!(status, Status::Ok);
+    assert_eq!(ids(&page), vec!["c1", "s1", "s2", "s3"]);
+    assert_eq!(page["items"][0]["entity_type"], "comment");
+
+    let (_, page) = get_json(&client, "/api/users/py0x/activity?type=submission&limit=1").await;
+    assert_eq!(ids(&page), vec!["s1"]);
+    assert!(page["next_cursor"].is_string());
+
+    let (status, _) = get_json(&client, "/api/users/py0x/activity?type=vote").await;
+    assert_eq!(status, Status::BadRequest);
+}
+
+#[rocket::async_test]
+async fn test_launch_without_secret_key() {
+    let rocket = rocket::custom(rocket::Config::debug_default())
+        .attach(api::cursor_codec());
+
+    let err = rocket.ignite().await.unwrap_err();
+    assert!(matches!(err.kind(), rocket::error::ErrorKind::FailedFairings(_)));
+}